@@ -0,0 +1,112 @@
+use crate::{inretval::Interval, ray::Ray, vec3::Point3};
+
+/// Axis-aligned bounding box stored as one interval per axis.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub x: Interval,
+    pub y: Interval,
+    pub z: Interval,
+}
+
+impl Aabb {
+    pub const EMPTY: Aabb = Aabb {
+        x: Interval::EMPTY,
+        y: Interval::EMPTY,
+        z: Interval::EMPTY,
+    };
+
+    pub fn new(x: Interval, y: Interval, z: Interval) -> Self {
+        let mut bbox = Self { x, y, z };
+        bbox.pad_to_minimums();
+        bbox
+    }
+
+    /// Box spanned by two opposite corners given in any order.
+    pub fn from_points(a: Point3, b: Point3) -> Self {
+        Self::new(
+            Interval::new(a.x.min(b.x), a.x.max(b.x)),
+            Interval::new(a.y.min(b.y), a.y.max(b.y)),
+            Interval::new(a.z.min(b.z), a.z.max(b.z)),
+        )
+    }
+
+    pub fn surrounding(a: Aabb, b: Aabb) -> Self {
+        Self {
+            x: Interval::union(a.x, b.x),
+            y: Interval::union(a.y, b.y),
+            z: Interval::union(a.z, b.z),
+        }
+    }
+
+    pub fn axis_interval(&self, n: usize) -> Interval {
+        match n {
+            0 => self.x,
+            1 => self.y,
+            _ => self.z,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.x.min > self.x.max || self.y.min > self.y.max || self.z.min > self.z.max
+    }
+
+    pub fn centroid(&self) -> Point3 {
+        Point3::new(
+            0.5 * (self.x.min + self.x.max),
+            0.5 * (self.y.min + self.y.max),
+            0.5 * (self.z.min + self.z.max),
+        )
+    }
+
+    pub fn surface_area(&self) -> f64 {
+        if self.is_empty() {
+            return 0.0;
+        }
+        let (dx, dy, dz) = (self.x.size(), self.y.size(), self.z.size());
+        2.0 * (dx * dy + dy * dz + dz * dx)
+    }
+
+    pub fn longest_axis(&self) -> usize {
+        if self.x.size() > self.y.size() {
+            if self.x.size() > self.z.size() { 0 } else { 2 }
+        } else if self.y.size() > self.z.size() {
+            1
+        } else {
+            2
+        }
+    }
+
+    /// Slab test; returns whether the ray enters the box within `ray_t`.
+    pub fn hit(&self, r: &Ray, mut ray_t: Interval) -> bool {
+        for axis in 0..3 {
+            let ax = self.axis_interval(axis);
+            let adinv = 1.0 / r.direction[axis];
+
+            let t0 = (ax.min - r.origin[axis]) * adinv;
+            let t1 = (ax.max - r.origin[axis]) * adinv;
+            let (t0, t1) = if t0 < t1 { (t0, t1) } else { (t1, t0) };
+
+            ray_t.min = ray_t.min.max(t0);
+            ray_t.max = ray_t.max.min(t1);
+            if ray_t.max <= ray_t.min {
+                return false;
+            }
+        }
+        true
+    }
+
+    // Flat shapes (triangles, quads) would give a box with zero thickness,
+    // which the slab test can miss because of rounding.
+    fn pad_to_minimums(&mut self) {
+        const DELTA: f64 = 0.0001;
+        if self.x.size() < DELTA {
+            self.x = self.x.expand(DELTA);
+        }
+        if self.y.size() < DELTA {
+            self.y = self.y.expand(DELTA);
+        }
+        if self.z.size() < DELTA {
+            self.z = self.z.expand(DELTA);
+        }
+    }
+}
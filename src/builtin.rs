@@ -0,0 +1,104 @@
+//! Scenes compiled into the renderer, selectable by name.
+
+use std::path::Path;
+
+use crate::{
+    camera::Camera,
+    color::Color,
+    hittable_list::HittableList,
+    material::{Dialectric, Lambertian, Metal},
+    scene::Scene,
+    sphere::Sphere,
+    util::{Rng, random_f64, random_f64_range},
+    vec3::{Point3, Vec3},
+};
+
+pub const NAMES: &[&str] = &[
+    "random_spheres",
+    "three_spheres",
+    "cornell_box",
+    "cornell_smoke",
+    "cloud",
+];
+
+/// Builds the named scene; randomly generated scenes are derived from `seed`.
+pub fn load(name: &str, seed: u64) -> Option<Scene> {
+    match name {
+        "random_spheres" => Some(random_spheres(seed)),
+        "three_spheres" => Some(parse(include_str!("../scenes/three_spheres.scene"))),
+        "cornell_box" => Some(parse(include_str!("../scenes/cornell_box.scene"))),
+        "cornell_smoke" => Some(parse(include_str!("../scenes/cornell_smoke.scene"))),
+        "cloud" => Some(parse(include_str!("../scenes/cloud.scene"))),
+        _ => None,
+    }
+}
+
+fn parse(source: &str) -> Scene {
+    Scene::parse(source, Path::new(".")).expect("built-in scene is valid")
+}
+
+/// Small spheres of random materials around three large ones.
+fn random_spheres(seed: u64) -> Scene {
+    let mut rng = Rng::with_seed(seed);
+    let mut world = HittableList::new();
+    let ground_material = Lambertian::new(Color::new(0.5, 0.5, 0.5));
+    world.add(Sphere::new(
+        Point3::new(0.0, -1000.0, 0.0),
+        1000.0,
+        ground_material,
+    ));
+    for a in -11..11 {
+        for b in -11..11 {
+            let choose_mat = random_f64(&mut rng);
+            let center = Point3::new(
+                a as f64 + 0.9 * random_f64(&mut rng),
+                0.2,
+                b as f64 + 0.9 * random_f64(&mut rng),
+            );
+            if (center - Point3::new(4.0, 0.2, 0.0)).length() > 0.9 {
+                if choose_mat < 0.8 {
+                    let albedo = Color::random(&mut rng) * Color::random(&mut rng);
+                    let sphere_material = Lambertian::new(albedo);
+                    world.add(Sphere::new(center, 0.2, sphere_material));
+                } else if choose_mat < 0.95 {
+                    let albedo = Color::random_range(&mut rng, 0.5, 1.0);
+                    let fuzz = random_f64_range(&mut rng, 0.0, 0.5);
+                    let sphere_material = Metal::new(albedo, fuzz);
+                    world.add(Sphere::new(center, 0.2, sphere_material));
+                } else {
+                    let sphere_material = Dialectric::new(1.5);
+                    world.add(Sphere::new(center, 0.2, sphere_material));
+                }
+            }
+        }
+    }
+    let material1 = Dialectric::new(1.5);
+    world.add(Sphere::new(Point3::new(0.0, 1.0, 0.0), 1.0, material1));
+
+    let material2 = Lambertian::new(Color::new(0.4, 0.2, 0.1));
+    world.add(Sphere::new(Point3::new(-4.0, 1.0, 0.0), 1.0, material2));
+
+    let material3 = Metal::new(Color::new(0.7, 0.6, 0.5), 0.0);
+    world.add(Sphere::new(Point3::new(4.0, 1.0, 0.0), 1.0, material3));
+
+    let mut cam = Camera::new();
+    cam.aspect_ratio = 16.0 / 9.0;
+    cam.image_width = 400;
+    cam.samples_per_pixel = 100;
+    cam.max_depth = 20;
+
+    cam.vfov = 20.0;
+    cam.lookfrom = Point3::new(13.0, 2.0, 3.0);
+    cam.lookat = Point3::new(0.0, 0.0, 0.0);
+    cam.vup = Vec3::new(0.0, 1.0, 0.0);
+
+    cam.defocus_angle = 0.6;
+    cam.focus_dist = 10.0;
+    cam.seed = seed;
+
+    Scene {
+        world,
+        lights: HittableList::new(),
+        camera: cam,
+    }
+}
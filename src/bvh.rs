@@ -0,0 +1,230 @@
+use crate::{
+    aabb::Aabb,
+    hittable::{HitRecord, Hittable},
+    hittable_list::HittableList,
+    inretval::Interval,
+    ray::Ray,
+    util::Rng,
+};
+
+/// Cost of visiting a node relative to intersecting one primitive.
+const TRAVERSAL_COST: f64 = 0.125;
+/// Nodes with more primitives than this are always split.
+const MAX_LEAF_SIZE: usize = 4;
+
+/// Bounding volume hierarchy built with the surface area heuristic.
+pub struct BvhNode<'a> {
+    bbox: Aabb,
+    kind: NodeKind<'a>,
+}
+
+enum NodeKind<'a> {
+    Leaf(Vec<Box<dyn Hittable + 'a>>),
+    Split(Box<BvhNode<'a>>, Box<BvhNode<'a>>),
+}
+
+type Primitive<'a> = (Aabb, Box<dyn Hittable + 'a>);
+
+impl<'a> BvhNode<'a> {
+    pub fn new(list: HittableList<'a>) -> Self {
+        Self::from_objects(list.into_objects())
+    }
+
+    pub fn from_objects(objects: Vec<Box<dyn Hittable + 'a>>) -> Self {
+        let primitives = objects
+            .into_iter()
+            .map(|object| (object.bounding_box(), object))
+            .collect();
+        Self::build(primitives)
+    }
+
+    fn build(mut primitives: Vec<Primitive<'a>>) -> Self {
+        let bbox = primitives
+            .iter()
+            .fold(Aabb::EMPTY, |acc, (b, _)| Aabb::surrounding(acc, *b));
+        let n = primitives.len();
+        if n <= 1 {
+            return Self::leaf(bbox, primitives);
+        }
+
+        let (axis, split, cost) = Self::best_split(&mut primitives, bbox);
+        if n <= MAX_LEAF_SIZE && cost >= n as f64 {
+            return Self::leaf(bbox, primitives);
+        }
+
+        sort_by_centroid(&mut primitives, axis);
+        let right = primitives.split_off(split);
+        Self {
+            bbox,
+            kind: NodeKind::Split(
+                Box::new(Self::build(primitives)),
+                Box::new(Self::build(right)),
+            ),
+        }
+    }
+
+    fn leaf(bbox: Aabb, primitives: Vec<Primitive<'a>>) -> Self {
+        Self {
+            bbox,
+            kind: NodeKind::Leaf(primitives.into_iter().map(|(_, object)| object).collect()),
+        }
+    }
+
+    /// Returns the axis and index of the cheapest split and its estimated cost.
+    fn best_split(primitives: &mut [Primitive<'a>], bbox: Aabb) -> (usize, usize, f64) {
+        let n = primitives.len();
+        let parent_area = bbox.surface_area();
+        let mut best = (0, n / 2, f64::INFINITY);
+        if parent_area <= 0.0 {
+            return best;
+        }
+
+        let mut right_areas = vec![0.0; n];
+        for axis in 0..3 {
+            sort_by_centroid(primitives, axis);
+
+            let mut right = Aabb::EMPTY;
+            for i in (1..n).rev() {
+                right = Aabb::surrounding(right, primitives[i].0);
+                right_areas[i] = right.surface_area();
+            }
+
+            let mut left = Aabb::EMPTY;
+            for i in 1..n {
+                left = Aabb::surrounding(left, primitives[i - 1].0);
+                let cost = TRAVERSAL_COST
+                    + (left.surface_area() * i as f64 + right_areas[i] * (n - i) as f64)
+                        / parent_area;
+                if cost < best.2 {
+                    best = (axis, i, cost);
+                }
+            }
+        }
+        best
+    }
+}
+
+fn sort_by_centroid(primitives: &mut [Primitive<'_>], axis: usize) {
+    primitives.sort_by(|a, b| a.0.centroid()[axis].total_cmp(&b.0.centroid()[axis]));
+}
+
+impl<'a> Hittable for BvhNode<'a> {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        if !self.bbox.hit(r, ray_t) {
+            return None;
+        }
+        match &self.kind {
+            NodeKind::Leaf(objects) => {
+                let mut rec = None;
+                let mut closest_so_far = ray_t.max;
+                for object in objects {
+                    if let Some(hrec) = object.hit(r, Interval::new(ray_t.min, closest_so_far)) {
+                        closest_so_far = hrec.t;
+                        rec = Some(hrec);
+                    }
+                }
+                rec
+            }
+            NodeKind::Split(left, right) => {
+                let hit_left = left.hit(r, ray_t);
+                let max = hit_left.as_ref().map_or(ray_t.max, |rec| rec.t);
+                let hit_right = right.hit(r, Interval::new(ray_t.min, max));
+                hit_right.or(hit_left)
+            }
+        }
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    fn transmittance(&self, r: &Ray, ray_t: Interval, rng: &mut Rng) -> f64 {
+        if !self.bbox.hit(r, ray_t) {
+            return 1.0;
+        }
+        match &self.kind {
+            NodeKind::Leaf(objects) => {
+                let mut transmittance = 1.0;
+                for object in objects {
+                    transmittance *= object.transmittance(r, ray_t, rng);
+                    if transmittance == 0.0 {
+                        break;
+                    }
+                }
+                transmittance
+            }
+            NodeKind::Split(left, right) => match left.transmittance(r, ray_t, rng) {
+                0.0 => 0.0,
+                t => t * right.transmittance(r, ray_t, rng),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        sphere::Sphere,
+        test_util::gray,
+        util::{Rng, random_f64_range},
+        vec3::{Point3, Vec3},
+    };
+
+    fn random_spheres<'a>(seed: u64, count: usize) -> HittableList<'a> {
+        let mut rng = Rng::with_seed(seed);
+        let mut world = HittableList::new();
+        for _ in 0..count {
+            let center = Point3::random_range(&mut rng, -10.0, 10.0);
+            let radius = random_f64_range(&mut rng, 0.05, 1.0);
+            world.add(Sphere::new(center, radius, gray()));
+        }
+        world
+    }
+
+    #[test]
+    fn test_bvh_matches_linear_list() {
+        let list = random_spheres(3, 300);
+        let bvh = BvhNode::new(random_spheres(3, 300));
+        assert_eq!(list.bounding_box(), bvh.bounding_box());
+
+        let mut rng = Rng::with_seed(11);
+        let mut hits = 0;
+        for _ in 0..2000 {
+            let origin = Point3::random_range(&mut rng, -15.0, 15.0);
+            let direction = Vec3::random_unit_vector(&mut rng);
+            let r = Ray::new(origin, direction);
+            let expected = list.hit(&r, Interval::new(0.001, f64::INFINITY));
+            let actual = bvh.hit(&r, Interval::new(0.001, f64::INFINITY));
+            match (expected, actual) {
+                (None, None) => {}
+                (Some(e), Some(a)) => {
+                    hits += 1;
+                    assert_eq!(e.t, a.t);
+                    assert_eq!(e.p, a.p);
+                    assert_eq!(e.normal, a.normal);
+                }
+                (e, a) => panic!(
+                    "bvh disagrees with list: {:?} vs {:?}",
+                    e.map(|rec| rec.t),
+                    a.map(|rec| rec.t)
+                ),
+            }
+        }
+        assert!(hits > 100);
+    }
+
+    #[test]
+    fn test_bvh_respects_interval() {
+        let bvh = BvhNode::new(random_spheres(5, 50));
+        let r = Ray::new(Point3::new(0.0, 0.0, -50.0), Vec3::new(0.0, 0.0, 1.0));
+        assert!(bvh.hit(&r, Interval::new(0.001, 1.0)).is_none());
+    }
+
+    #[test]
+    fn test_empty_bvh() {
+        let bvh = BvhNode::new(HittableList::new());
+        let r = Ray::new(Point3::origin(), Vec3::new(1.0, 0.0, 0.0));
+        assert!(bvh.hit(&r, Interval::UNIVERSE).is_none());
+    }
+}
@@ -1,20 +1,45 @@
-use crate::color::write_color;
-use crate::util::random_f64;
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+
+use crate::environment::Environment;
+use crate::framebuffer::{Aov, Framebuffer};
+use crate::integrator::{Context, Integrator, PathTracer};
+use crate::pdf::Heuristic;
+use crate::util::{Rng, pixel_rng, random_f64};
 use crate::vec3::{Point3, Vec3};
-use crate::{color::Color, hittable::Hittable, inretval::Interval, ray::Ray};
+use crate::{
+    color::Color, hittable::Hittable, hittable_list::HittableList, inretval::Interval, ray::Ray,
+};
 
-#[derive(Default)]
 pub struct Camera {
     pub aspect_ratio: f64,  // = 1.0
     pub image_width: usize, // = 100
     pub samples_per_pixel: usize,
     pub max_depth: usize,
+    /// Surfaces a path meets before Russian roulette may end it early,
+    /// which leaves `max_depth` only as a backstop.
+    pub roulette_depth: usize,
     pub vfov: f64,
     pub lookfrom: Point3,
     pub lookat: Point3,
     pub vup: Vec3,
     pub defocus_angle: f64,
     pub focus_dist: f64,
+    /// Light seen by rays that leave the scene without hitting anything.
+    pub background: Environment,
+    /// Number of worker threads, 0 uses every available core.
+    pub threads: usize,
+    /// Edge length in pixels of the square tiles handed to the workers.
+    pub tile_size: usize,
+    /// Seed of the per-pixel random streams; equal seeds give identical images.
+    pub seed: u64,
+    /// Weighs light found by sampling lights against light found by
+    /// bouncing off a material.
+    pub heuristic: Heuristic,
+    /// The algorithm that estimates the light along each camera ray.
+    pub integrator: Box<dyn Integrator>,
 
     image_height: usize,
     center: Point3,
@@ -29,6 +54,12 @@ pub struct Camera {
     defocus_disk_v: Vec3,
 }
 
+impl Default for Camera {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Camera {
     pub fn new() -> Self {
         Camera {
@@ -37,30 +68,145 @@ impl Camera {
             image_width: 100,
             samples_per_pixel: 10,
             max_depth: 10,
+            roulette_depth: 3,
             vfov: 90.0,
             lookfrom: Point3::new(0.0, 0.0, 0.0),
             lookat: Point3::new(0.0, 0.0, -1.0),
             vup: Vec3::new(0.0, 1.0, 0.0),
-            ..Default::default()
+            defocus_angle: 0.0,
+            focus_dist: 10.0,
+            background: Environment::default(),
+            threads: 0,
+            tile_size: 16,
+            seed: 0,
+            heuristic: Heuristic::default(),
+            integrator: Box::new(PathTracer),
+            image_height: 0,
+            center: Point3::origin(),
+            pixel100_loc: Point3::origin(),
+            pixel_delta_u: Vec3::zero(),
+            pixel_delta_v: Vec3::zero(),
+            pixel_samples_scale: 0.0,
+            u: Vec3::zero(),
+            v: Vec3::zero(),
+            w: Vec3::zero(),
+            defocus_disk_u: Vec3::zero(),
+            defocus_disk_v: Vec3::zero(),
         }
     }
 
-    pub fn render(&mut self, world: &impl Hittable) {
+    /// Renders `world` with the camera's integrator. Those that sample
+    /// lights aim shadow rays at the emitters in `lights`, which must also
+    /// be part of `world`.
+    pub fn render(&mut self, world: &impl Hittable, lights: &HittableList) -> Framebuffer {
         self.initialize();
-        let out = std::io::stdout();
-        println!("P3\n{} {}\n255", self.image_width, self.image_height);
-        for j in 0..self.image_height {
-            eprint!("\rScanlines remaning: {} ", self.image_height - j);
-            for i in 0..self.image_width {
+        let camera = &*self;
+        let tiles = camera.tiles();
+        let next_tile = AtomicUsize::new(0);
+        let mut image = Framebuffer::new(camera.image_width, camera.image_height);
+
+        thread::scope(|s| {
+            let (tx, rx) = mpsc::channel();
+            for _ in 0..camera.thread_count().min(tiles.len()) {
+                let tx = tx.clone();
+                let tiles = &tiles;
+                let next_tile = &next_tile;
+                s.spawn(move || {
+                    while let Some(tile) = tiles.get(next_tile.fetch_add(1, Ordering::Relaxed)) {
+                        let pixels = camera.render_tile(tile, world, lights);
+                        if tx.send((tile, pixels)).is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+            drop(tx);
+
+            for (done, (tile, pixels)) in rx.into_iter().enumerate() {
+                eprint!("\rTiles remaning: {} ", tiles.len() - done - 1);
+                tile.copy_into(&mut image, &pixels);
+            }
+        });
+        eprintln!("\rDone                           ");
+        image
+    }
+
+    fn thread_count(&self) -> usize {
+        if self.threads > 0 {
+            self.threads
+        } else {
+            thread::available_parallelism().map_or(1, NonZeroUsize::get)
+        }
+    }
+
+    fn tiles(&self) -> Vec<Tile> {
+        let size = self.tile_size.max(1);
+        let mut tiles = Vec::new();
+        for y in (0..self.image_height).step_by(size) {
+            for x in (0..self.image_width).step_by(size) {
+                tiles.push(Tile {
+                    x,
+                    y,
+                    width: size.min(self.image_width - x),
+                    height: size.min(self.image_height - y),
+                });
+            }
+        }
+        tiles
+    }
+
+    fn render_tile(
+        &self,
+        tile: &Tile,
+        world: &impl Hittable,
+        lights: &HittableList,
+    ) -> Vec<(Color, Aov)> {
+        let ctx = Context {
+            world,
+            lights,
+            background: &self.background,
+            max_depth: self.max_depth,
+            roulette_depth: self.roulette_depth,
+            heuristic: self.heuristic,
+        };
+        let mut pixels = Vec::with_capacity(tile.width * tile.height);
+        for j in tile.y..tile.y + tile.height {
+            for i in tile.x..tile.x + tile.width {
+                let mut rng = pixel_rng(self.seed, i, j);
                 let mut pixel_color = Color::new(0.0, 0.0, 0.0);
-                for sample in 0..self.samples_per_pixel {
-                    let r = self.get_ray(i, j);
-                    pixel_color += self.ray_color(&r, self.max_depth, world);
+                let mut hits = 0;
+                let mut depth = 0.0;
+                let mut normal = Vec3::new(0.0, 0.0, 0.0);
+                for _ in 0..self.samples_per_pixel {
+                    let r = self.get_ray(i, j, &mut rng);
+                    // The primary hit is traced here so the passes can share it.
+                    let hit = world.hit(&r, Interval::new(0.001, f64::INFINITY));
+                    if let Some(rec) = &hit {
+                        hits += 1;
+                        depth += rec.t * r.direction.length();
+                        normal += rec.normal;
+                    }
+                    if self.max_depth > 0 {
+                        pixel_color += self.integrator.radiance(&r, hit.as_ref(), &ctx, &mut rng);
+                    }
                 }
-                write_color(&out, self.pixel_samples_scale * pixel_color);
+                let aov = Aov {
+                    alpha: hits as f64 * self.pixel_samples_scale,
+                    depth: if hits > 0 {
+                        depth / hits as f64
+                    } else {
+                        f64::INFINITY
+                    },
+                    normal: if normal.length_squared() > 0.0 {
+                        normal.unit_vector()
+                    } else {
+                        normal
+                    },
+                };
+                pixels.push((self.pixel_samples_scale * pixel_color, aov));
             }
         }
-        eprintln!("\rDone                           ");
+        pixels
     }
 
     fn initialize(&mut self) {
@@ -100,42 +246,143 @@ impl Camera {
         self.defocus_disk_v = defocus_radius * self.v;
     }
 
-    fn get_ray(&self, i: usize, j: usize) -> Ray {
-        let offset = self.sample_square();
+    fn get_ray(&self, i: usize, j: usize, rng: &mut Rng) -> Ray {
+        let offset = self.sample_square(rng);
         let pixel_sample = self.pixel100_loc
             + ((i as f64 + offset.x) * self.pixel_delta_u)
             + ((j as f64 + offset.y) * self.pixel_delta_v);
         let ray_origin = if self.defocus_angle <= 0.0 {
             self.center
         } else {
-            self.defocus_disk_sample()
+            self.defocus_disk_sample(rng)
         };
         let ray_direction = pixel_sample - ray_origin;
         Ray::new(ray_origin, ray_direction)
     }
 
-    fn defocus_disk_sample(&self) -> Point3 {
-        let p = Vec3::random_in_unit_disk();
+    fn defocus_disk_sample(&self, rng: &mut Rng) -> Point3 {
+        let p = Vec3::random_in_unit_disk(rng);
         self.center + (p.x * self.defocus_disk_u) + (p.y * self.defocus_disk_v)
     }
 
-    fn sample_square(&self) -> Vec3 {
-        Vec3::new(random_f64() - 0.5, random_f64() - 0.5, 0.0)
+    fn sample_square(&self, rng: &mut Rng) -> Vec3 {
+        Vec3::new(random_f64(rng) - 0.5, random_f64(rng) - 0.5, 0.0)
     }
+}
 
-    fn ray_color(&self, r: &Ray, depth: usize, world: &impl Hittable) -> Color {
-        if depth == 0 {
-            return Color::new(0.0, 0.0, 0.0);
-        }
-        if let Some(rec) = world.hit(r, Interval::new(0.001, f64::INFINITY)) {
-            if let Some((attentuation, scattered)) = rec.mat.scatter(r, &rec) {
-                return attentuation * self.ray_color(&scattered, depth - 1, world);
-            } else {
-                return Color::new(0.0, 0.0, 0.0);
+/// Rectangular block of pixels rendered by a single worker.
+struct Tile {
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+}
+
+impl Tile {
+    fn copy_into(&self, image: &mut Framebuffer, pixels: &[(Color, Aov)]) {
+        for (row, colors) in pixels.chunks(self.width).enumerate() {
+            for (col, &(color, aov)) in colors.iter().enumerate() {
+                image.set(self.x + col, self.y + row, color);
+                image.set_aov(self.x + col, self.y + row, aov);
             }
         }
-        let unit_direction = r.direction.unit_vector();
-        let a = 0.5 * (unit_direction.y + 1.0);
-        (1.0 - a) * Color::new(1.0, 1.0, 1.0) + a * Color::new(0.5, 0.7, 1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        hittable_list::HittableList,
+        material::{Dialectric, DiffuseLight, Lambertian, Metal},
+        sphere::Sphere,
+    };
+
+    fn small_scene<'a>() -> HittableList<'a> {
+        let mut world = HittableList::new();
+        let ground = Lambertian::new(Color::new(0.5, 0.5, 0.5));
+        world.add(Sphere::new(Point3::new(0.0, -100.5, -1.0), 100.0, ground));
+        let glass = Dialectric::new(1.5);
+        world.add(Sphere::new(Point3::new(-0.6, 0.0, -1.0), 0.5, glass));
+        let metal = Metal::new(Color::new(0.8, 0.6, 0.2), 0.3);
+        world.add(Sphere::new(Point3::new(0.6, 0.0, -1.0), 0.5, metal));
+        world
+    }
+
+    fn small_camera(threads: usize, seed: u64) -> Camera {
+        let mut cam = Camera::new();
+        cam.image_width = 24;
+        cam.aspect_ratio = 4.0 / 3.0;
+        cam.samples_per_pixel = 4;
+        cam.focus_dist = 1.0;
+        cam.tile_size = 5;
+        cam.threads = threads;
+        cam.seed = seed;
+        cam
+    }
+
+    #[test]
+    fn test_render_is_reproducible_across_thread_counts() {
+        let world = small_scene();
+        let single = small_camera(1, 7).render(&world, &HittableList::new());
+        let multi = small_camera(4, 7).render(&world, &HittableList::new());
+        assert_eq!(single, multi);
+    }
+
+    #[test]
+    fn test_render_depends_on_seed() {
+        let world = small_scene();
+        let a = small_camera(2, 1).render(&world, &HittableList::new());
+        let b = small_camera(2, 2).render(&world, &HittableList::new());
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn test_emission_reaches_camera() {
+        let mut world = HittableList::new();
+        let light = DiffuseLight::new(Color::new(2.0, 1.0, 0.5));
+        world.add(Sphere::new(Point3::origin(), 10.0, light));
+        let image = small_camera(1, 3).render(&world, &HittableList::new());
+        assert!(
+            image
+                .pixels()
+                .iter()
+                .all(|&pixel| pixel == Color::new(2.0, 1.0, 0.5))
+        );
+    }
+
+    #[test]
+    fn test_background_fills_empty_scene() {
+        let mut cam = small_camera(2, 5);
+        cam.background = Environment::Solid(Color::new(0.25, 0.5, 0.75));
+        let image = cam.render(&HittableList::new(), &HittableList::new());
+        assert!(
+            image
+                .pixels()
+                .iter()
+                .all(|&pixel| pixel == Color::new(0.25, 0.5, 0.75))
+        );
+    }
+
+    #[test]
+    fn test_passes() {
+        let mut world = HittableList::new();
+        let ground = Lambertian::new(Color::new(0.5, 0.5, 0.5));
+        world.add(Sphere::new(Point3::new(0.0, 0.0, -3.0), 1.0, ground));
+        let image = small_camera(1, 9).render(&world, &HittableList::new());
+
+        let center = image.aov(12, 9);
+        assert_eq!(center.alpha, 1.0);
+        assert!((center.depth - 2.0).abs() < 0.05);
+        assert!(center.normal.z > 0.95);
+        let corner = image.aov(0, 0);
+        assert_eq!(corner.alpha, 0.0);
+        assert_eq!(corner.depth, f64::INFINITY);
+        assert!(
+            image
+                .aovs()
+                .iter()
+                .any(|aov| aov.alpha > 0.0 && aov.alpha < 1.0)
+        );
     }
 }
@@ -0,0 +1,291 @@
+use std::path::PathBuf;
+
+use ray_tracing::{integrator, output};
+
+const USAGE: &str = "\
+Usage: ray-tracing [render] [OPTIONS]
+       ray-tracing scenes
+       ray-tracing help
+
+Commands:
+  render                  Render a scene (the default)
+  scenes                  List the built-in scenes
+  help                    Show this message
+
+Options:
+  -s, --scene <FILE>      Scene description file to render
+  -b, --builtin <NAME>    Built-in scene to render [default: random_spheres]
+  -w, --width <PIXELS>    Image width
+      --aspect <RATIO>    Width to height ratio, e.g. 1.7778 or 16:9
+  -n, --spp <SAMPLES>     Samples per pixel
+  -d, --depth <BOUNCES>   Maximum ray depth
+  -i, --integrator <NAME> Rendering algorithm: path (shadow rays with MIS),
+                          naive, direct, ao [default: path]
+      --seed <SEED>       Random seed for sampling and generated scenes
+  -j, --threads <N>       Worker threads, 0 for every core
+  -o, --output <PATH>     Output file [default: stdout]
+{formats}
+
+Options override the values set in a scene file.";
+
+/// The help text, listing the formats [`output::FORMATS`] knows.
+pub fn usage() -> String {
+    let names: Vec<&str> = output::FORMATS.iter().map(|(name, _)| *name).collect();
+    let text = format!(
+        "Output format: {} [default: from the output extension]",
+        names.join(", ")
+    );
+    let mut formats = String::from("  -f, --format <FORMAT>  ");
+    let mut width = formats.len();
+    for word in text.split(' ') {
+        if width + 1 + word.len() > 78 {
+            formats.push_str("\n                         ");
+            width = 25;
+        }
+        formats.push(' ');
+        formats.push_str(word);
+        width += 1 + word.len();
+    }
+    USAGE.replace("{formats}", &formats)
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Render(RenderArgs),
+    ListScenes,
+    Help,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum SceneSource {
+    File(PathBuf),
+    Builtin(String),
+}
+
+#[derive(Debug, PartialEq)]
+pub struct RenderArgs {
+    pub scene: SceneSource,
+    pub width: Option<usize>,
+    pub aspect: Option<f64>,
+    pub spp: Option<usize>,
+    pub depth: Option<usize>,
+    /// Name of the integrator, one of `integrator::NAMES`.
+    pub integrator: Option<String>,
+    pub seed: Option<u64>,
+    pub threads: Option<usize>,
+    pub output: Option<PathBuf>,
+    /// Name of the output format, one of `output::FORMATS`.
+    pub format: Option<String>,
+}
+
+impl Default for RenderArgs {
+    fn default() -> Self {
+        Self {
+            scene: SceneSource::Builtin("random_spheres".to_string()),
+            width: None,
+            aspect: None,
+            spp: None,
+            depth: None,
+            integrator: None,
+            seed: None,
+            threads: None,
+            output: None,
+            format: None,
+        }
+    }
+}
+
+/// Parses the arguments that follow the program name.
+pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Command, String> {
+    let mut args = args.into_iter().peekable();
+    match args.peek().map(String::as_str) {
+        Some("scenes") => return no_more(args.skip(1), Command::ListScenes),
+        Some("help" | "-h" | "--help") => return Ok(Command::Help),
+        Some("render") => {
+            args.next();
+        }
+        _ => {}
+    }
+
+    let mut render = RenderArgs::default();
+    while let Some(arg) = args.next() {
+        let (flag, inline) = match arg.split_once('=') {
+            Some((flag, value)) if flag.starts_with("--") => {
+                (flag.to_string(), Some(value.to_string()))
+            }
+            _ => (arg, None),
+        };
+        if flag == "-h" || flag == "--help" {
+            return Ok(Command::Help);
+        }
+        let mut value = || {
+            inline
+                .clone()
+                .or_else(|| args.next())
+                .ok_or_else(|| format!("{flag} needs a value"))
+        };
+        match flag.as_str() {
+            "-s" | "--scene" => render.scene = SceneSource::File(PathBuf::from(value()?)),
+            "-b" | "--builtin" => render.scene = SceneSource::Builtin(value()?),
+            "-w" | "--width" => render.width = Some(positive(&flag, &value()?)?),
+            "--aspect" => render.aspect = Some(aspect(&value()?)?),
+            "-n" | "--spp" => render.spp = Some(positive(&flag, &value()?)?),
+            "-d" | "--depth" => render.depth = Some(number(&flag, &value()?)?),
+            "-i" | "--integrator" => {
+                let name = value()?;
+                if integrator::for_name(&name).is_none() {
+                    return Err(format!("unknown integrator `{name}`"));
+                }
+                render.integrator = Some(name);
+            }
+            "--seed" => render.seed = Some(number(&flag, &value()?)?),
+            "-j" | "--threads" => render.threads = Some(number(&flag, &value()?)?),
+            "-o" | "--output" => render.output = Some(PathBuf::from(value()?)),
+            "-f" | "--format" => {
+                let name = value()?;
+                if output::writer_for_name(&name).is_none() {
+                    return Err(format!("unknown output format `{name}`"));
+                }
+                render.format = Some(name);
+            }
+            _ => return Err(format!("unexpected argument `{flag}`")),
+        }
+    }
+    Ok(Command::Render(render))
+}
+
+fn no_more(mut args: impl Iterator<Item = String>, command: Command) -> Result<Command, String> {
+    match args.next() {
+        Some(arg) => Err(format!("unexpected argument `{arg}`")),
+        None => Ok(command),
+    }
+}
+
+fn number<T: std::str::FromStr>(flag: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("{flag} expects a whole number, got `{value}`"))
+}
+
+fn positive(flag: &str, value: &str) -> Result<usize, String> {
+    Some(number(flag, value)?)
+        .filter(|&n| n > 0)
+        .ok_or_else(|| format!("{flag} expects a positive whole number, got `{value}`"))
+}
+
+fn aspect(value: &str) -> Result<f64, String> {
+    let ratio = match value.split_once(':') {
+        Some((w, h)) => w
+            .parse::<f64>()
+            .ok()
+            .zip(h.parse::<f64>().ok())
+            .map(|(w, h)| w / h),
+        None => value.parse().ok(),
+    };
+    ratio
+        .filter(|r| r.is_finite() && *r > 0.0)
+        .ok_or_else(|| format!("--aspect expects a positive ratio, got `{value}`"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_args(args: &[&str]) -> Result<Command, String> {
+        parse(args.iter().map(|s| s.to_string()))
+    }
+
+    #[test]
+    fn test_defaults() {
+        assert_eq!(parse_args(&[]), Ok(Command::Render(RenderArgs::default())));
+        assert_eq!(
+            parse_args(&["render"]),
+            Ok(Command::Render(RenderArgs::default()))
+        );
+        assert_eq!(parse_args(&["scenes"]), Ok(Command::ListScenes));
+        assert_eq!(parse_args(&["--help"]), Ok(Command::Help));
+    }
+
+    #[test]
+    fn test_render_flags() {
+        let command = parse_args(&[
+            "render",
+            "-s",
+            "room.scene",
+            "--width=640",
+            "--aspect",
+            "16:9",
+            "-n",
+            "64",
+            "-d",
+            "8",
+            "-i",
+            "ao",
+            "--seed",
+            "7",
+            "-j",
+            "2",
+            "-o",
+            "out.ppm",
+            "--format",
+            "ppm",
+        ])
+        .unwrap();
+        assert_eq!(
+            command,
+            Command::Render(RenderArgs {
+                scene: SceneSource::File(PathBuf::from("room.scene")),
+                width: Some(640),
+                aspect: Some(16.0 / 9.0),
+                spp: Some(64),
+                depth: Some(8),
+                integrator: Some("ao".to_string()),
+                seed: Some(7),
+                threads: Some(2),
+                output: Some(PathBuf::from("out.ppm")),
+                format: Some("ppm".to_string()),
+            })
+        );
+    }
+
+    #[test]
+    fn test_usage() {
+        let usage = usage();
+        let words: Vec<&str> = usage
+            .split_whitespace()
+            .map(|word| word.trim_end_matches(','))
+            .collect();
+        for (name, _) in output::FORMATS {
+            assert!(words.contains(&name), "{name}");
+        }
+        assert!(usage.lines().all(|line| line.len() <= 78), "{usage}");
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(
+            parse_args(&["--width", "wide"]),
+            Err("--width expects a whole number, got `wide`".to_string())
+        );
+        assert_eq!(
+            parse_args(&["--spp", "0"]),
+            Err("--spp expects a positive whole number, got `0`".to_string())
+        );
+        assert_eq!(
+            parse_args(&["--spp"]),
+            Err("--spp needs a value".to_string())
+        );
+        assert_eq!(
+            parse_args(&["--format", "gif"]),
+            Err("unknown output format `gif`".to_string())
+        );
+        assert_eq!(
+            parse_args(&["--integrator=bdpt"]),
+            Err("unknown integrator `bdpt`".to_string())
+        );
+        assert_eq!(
+            parse_args(&["scenes", "extra"]),
+            Err("unexpected argument `extra`".to_string())
+        );
+    }
+}
@@ -1,11 +1,15 @@
 use std::{
-    io::Write,
+    io::{self, Write},
     ops::{Add, AddAssign, Mul},
 };
 
-use crate::{inretval::Interval, util::{random_f64, random_f64_range}, vec3::Vec3};
+use crate::{
+    inretval::Interval,
+    util::{Rng, random_f64, random_f64_range},
+    vec3::Vec3,
+};
 
-#[derive(Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
 pub struct Color {
     pub r: f64,
     pub g: f64,
@@ -16,14 +20,14 @@ impl Color {
     pub fn new(r: f64, g: f64, b: f64) -> Self {
         Color { r, g, b }
     }
-    pub fn random() -> Color {
-        Self::new(random_f64(), random_f64(), random_f64())
+    pub fn random(rng: &mut Rng) -> Color {
+        Self::new(random_f64(rng), random_f64(rng), random_f64(rng))
     }
-    pub fn random_range(min: f64, max: f64) -> Color {
+    pub fn random_range(rng: &mut Rng, min: f64, max: f64) -> Color {
         Self::new(
-            random_f64_range(min, max),
-            random_f64_range(min, max),
-            random_f64_range(min, max),
+            random_f64_range(rng, min, max),
+            random_f64_range(rng, min, max),
+            random_f64_range(rng, min, max),
         )
     }
 }
@@ -68,7 +72,7 @@ impl Mul for Color {
 
 const INTENCITY: Interval = Interval::new(0.0, 0.999);
 
-fn linear_to_gamma(linear_component: f64) -> f64 {
+pub fn linear_to_gamma(linear_component: f64) -> f64 {
     if linear_component > 0.0 {
         linear_component.sqrt()
     } else {
@@ -76,14 +80,25 @@ fn linear_to_gamma(linear_component: f64) -> f64 {
     }
 }
 
-pub fn write_color(mut out: impl Write, pixel_color: Color) {
-    let r = linear_to_gamma(pixel_color.r);
-    let g = linear_to_gamma(pixel_color.g);
-    let b = linear_to_gamma(pixel_color.b);
+/// Inverse of `linear_to_gamma`.
+pub fn gamma_to_linear(gamma_component: f64) -> f64 {
+    gamma_component * gamma_component
+}
+
+/// Gamma-encoded 8-bit value of a linear channel.
+pub fn channel_to_u8(linear_component: f64) -> u8 {
+    (256.0 * INTENCITY.clamp(linear_to_gamma(linear_component))) as u8
+}
+
+/// Gamma-encoded 16-bit value of a linear channel.
+pub fn channel_to_u16(linear_component: f64) -> u16 {
+    (65536.0 * linear_to_gamma(linear_component)).min(65535.0) as u16
+}
 
-    let rbyte = (256.0 * INTENCITY.clamp(r)) as usize;
-    let gbyte = (256.0 * INTENCITY.clamp(g)) as usize;
-    let bbyte = (256.0 * INTENCITY.clamp(b)) as usize;
+pub fn write_color(mut out: impl Write, pixel_color: Color) -> io::Result<()> {
+    let rbyte = channel_to_u8(pixel_color.r);
+    let gbyte = channel_to_u8(pixel_color.g);
+    let bbyte = channel_to_u8(pixel_color.b);
 
-    writeln!(out, "{rbyte} {gbyte} {bbyte}").unwrap();
+    writeln!(out, "{rbyte} {gbyte} {bbyte}")
 }
@@ -0,0 +1,803 @@
+//! DEFLATE (RFC 1951) compression wrapped in the zlib format (RFC 1950), as
+//! needed by the PNG and OpenEXR writers, and the matching decompression for
+//! the PNG reader.
+
+use std::io;
+
+const WINDOW_SIZE: usize = 1 << 15;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+const HASH_BITS: usize = 15;
+/// How many earlier positions with the same hash are tried per match search.
+const MAX_CHAIN: usize = 128;
+/// Symbols collected before a block is emitted with its own Huffman tables.
+const BLOCK_TOKENS: usize = 1 << 16;
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DIST_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+/// Order in which code length code lengths are stored in a dynamic block header.
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+/// Compresses `data` into a zlib stream.
+pub fn zlib_compress(data: &[u8]) -> Vec<u8> {
+    // CMF: deflate with a 32K window; FLG: default level, check bits.
+    let mut out = vec![0x78, 0x9c];
+    out.extend(deflate(data));
+    out.extend(adler32(data).to_be_bytes());
+    out
+}
+
+/// Compresses `data` into raw DEFLATE blocks.
+pub fn deflate(data: &[u8]) -> Vec<u8> {
+    let tokens = lz77(data);
+    let mut w = BitWriter::default();
+    if tokens.is_empty() {
+        write_block(&mut w, &[], &[], true);
+    }
+    let mut consumed = 0;
+    let chunks: Vec<&[Token]> = tokens.chunks(BLOCK_TOKENS).collect();
+    for (i, chunk) in chunks.iter().enumerate() {
+        let len: usize = chunk.iter().map(Token::len).sum();
+        write_block(
+            &mut w,
+            chunk,
+            &data[consumed..consumed + len],
+            i + 1 == chunks.len(),
+        );
+        consumed += len;
+    }
+    w.finish()
+}
+
+pub fn adler32(data: &[u8]) -> u32 {
+    const MOD: u32 = 65521;
+    let (mut a, mut b) = (1u32, 0u32);
+    // 5552 is the largest run that cannot overflow before reducing.
+    for chunk in data.chunks(5552) {
+        for &byte in chunk {
+            a += byte as u32;
+            b += a;
+        }
+        a %= MOD;
+        b %= MOD;
+    }
+    (b << 16) | a
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Token {
+    Literal(u8),
+    Match { len: u16, dist: u16 },
+}
+
+impl Token {
+    fn len(&self) -> usize {
+        match self {
+            Token::Literal(_) => 1,
+            Token::Match { len, .. } => *len as usize,
+        }
+    }
+}
+
+/// Greedy LZ77 parse using hash chains over the last 32K of input.
+fn lz77(data: &[u8]) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut head = vec![u32::MAX; 1 << HASH_BITS];
+    let mut prev = vec![u32::MAX; WINDOW_SIZE];
+    let hash = |i: usize| {
+        let v = (data[i] as u32) << 16 | (data[i + 1] as u32) << 8 | data[i + 2] as u32;
+        (v.wrapping_mul(2_654_435_761) >> (32 - HASH_BITS)) as usize
+    };
+    let insert = |i: usize, head: &mut [u32], prev: &mut [u32]| {
+        if i + MIN_MATCH <= data.len() {
+            let h = hash(i);
+            prev[i % WINDOW_SIZE] = head[h];
+            head[h] = i as u32;
+        }
+    };
+
+    let mut i = 0;
+    while i < data.len() {
+        let mut best_len = 0;
+        let mut best_dist = 0;
+        if i + MIN_MATCH <= data.len() {
+            let max_len = MAX_MATCH.min(data.len() - i);
+            let mut candidate = head[hash(i)];
+            let mut chain = 0;
+            while candidate != u32::MAX && chain < MAX_CHAIN {
+                let c = candidate as usize;
+                if i - c > WINDOW_SIZE - 1 {
+                    break;
+                }
+                if data[c + best_len.min(max_len - 1)] == data[i + best_len.min(max_len - 1)] {
+                    let len = data[c..c + max_len]
+                        .iter()
+                        .zip(&data[i..i + max_len])
+                        .take_while(|(a, b)| a == b)
+                        .count();
+                    if len > best_len {
+                        best_len = len;
+                        best_dist = i - c;
+                        if len == max_len {
+                            break;
+                        }
+                    }
+                }
+                let next = prev[c % WINDOW_SIZE];
+                if next == u32::MAX || next as usize >= c {
+                    break;
+                }
+                candidate = next;
+                chain += 1;
+            }
+        }
+
+        if best_len >= MIN_MATCH {
+            tokens.push(Token::Match {
+                len: best_len as u16,
+                dist: best_dist as u16,
+            });
+            for j in i..i + best_len {
+                insert(j, &mut head, &mut prev);
+            }
+            i += best_len;
+        } else {
+            tokens.push(Token::Literal(data[i]));
+            insert(i, &mut head, &mut prev);
+            i += 1;
+        }
+    }
+    tokens
+}
+
+fn length_code(len: u16) -> usize {
+    LENGTH_BASE.iter().rposition(|&base| base <= len).unwrap()
+}
+
+fn dist_code(dist: u16) -> usize {
+    DIST_BASE.iter().rposition(|&base| base <= dist).unwrap()
+}
+
+/// Writes one block, picking a dynamic Huffman or a stored block, whichever is smaller.
+fn write_block(w: &mut BitWriter, tokens: &[Token], raw: &[u8], last: bool) {
+    let mut lit_freq = [0u32; 286];
+    let mut dist_freq = [0u32; 30];
+    lit_freq[256] = 1;
+    for token in tokens {
+        match *token {
+            Token::Literal(byte) => lit_freq[byte as usize] += 1,
+            Token::Match { len, dist } => {
+                lit_freq[257 + length_code(len)] += 1;
+                dist_freq[dist_code(dist)] += 1;
+            }
+        }
+    }
+    let lit_lengths = code_lengths(&lit_freq, 15);
+    let dist_lengths = code_lengths(&dist_freq, 15);
+
+    let hlit = 257.max(lit_lengths.iter().rposition(|&l| l != 0).unwrap() + 1);
+    let hdist = 1.max(
+        dist_lengths
+            .iter()
+            .rposition(|&l| l != 0)
+            .map_or(0, |p| p + 1),
+    );
+    let mut all_lengths = lit_lengths[..hlit].to_vec();
+    all_lengths.extend_from_slice(&dist_lengths[..hdist]);
+    let cl_symbols = run_length_encode(&all_lengths);
+    let mut cl_freq = [0u32; 19];
+    for &(symbol, _) in &cl_symbols {
+        cl_freq[symbol as usize] += 1;
+    }
+    let cl_lengths = code_lengths(&cl_freq, 7);
+    let hclen = 4.max(
+        CODE_LENGTH_ORDER
+            .iter()
+            .rposition(|&s| cl_lengths[s] != 0)
+            .unwrap()
+            + 1,
+    );
+
+    let header_bits = 5 + 5 + 4 + 3 * hclen as u64;
+    let cl_bits: u64 = cl_symbols
+        .iter()
+        .map(|&(s, _)| (cl_lengths[s as usize] + repeat_extra_bits(s)) as u64)
+        .sum();
+    let data_bits: u64 = tokens
+        .iter()
+        .map(|token| match *token {
+            Token::Literal(byte) => lit_lengths[byte as usize] as u64,
+            Token::Match { len, dist } => {
+                let lc = length_code(len);
+                let dc = dist_code(dist);
+                (lit_lengths[257 + lc] + LENGTH_EXTRA[lc] + dist_lengths[dc] + DIST_EXTRA[dc])
+                    as u64
+            }
+        })
+        .sum::<u64>()
+        + lit_lengths[256] as u64;
+    let dynamic_bits = 3 + header_bits + cl_bits + data_bits;
+    let stored_bits = (raw.len() as u64 / 0xffff + 1) * (3 + 7 + 32) + raw.len() as u64 * 8;
+
+    if stored_bits < dynamic_bits {
+        write_stored(w, raw, last);
+        return;
+    }
+
+    w.write_bits(last as u32, 1);
+    w.write_bits(0b10, 2);
+    w.write_bits((hlit - 257) as u32, 5);
+    w.write_bits((hdist - 1) as u32, 5);
+    w.write_bits((hclen - 4) as u32, 4);
+    for &symbol in &CODE_LENGTH_ORDER[..hclen] {
+        w.write_bits(cl_lengths[symbol] as u32, 3);
+    }
+    let cl_codes = canonical_codes(&cl_lengths);
+    for &(symbol, extra) in &cl_symbols {
+        let s = symbol as usize;
+        w.write_code(cl_codes[s], cl_lengths[s]);
+        w.write_bits(extra as u32, repeat_extra_bits(symbol));
+    }
+
+    let lit_codes = canonical_codes(&lit_lengths);
+    let dist_codes = canonical_codes(&dist_lengths);
+    for token in tokens {
+        match *token {
+            Token::Literal(byte) => {
+                w.write_code(lit_codes[byte as usize], lit_lengths[byte as usize])
+            }
+            Token::Match { len, dist } => {
+                let lc = length_code(len);
+                w.write_code(lit_codes[257 + lc], lit_lengths[257 + lc]);
+                w.write_bits((len - LENGTH_BASE[lc]) as u32, LENGTH_EXTRA[lc]);
+                let dc = dist_code(dist);
+                w.write_code(dist_codes[dc], dist_lengths[dc]);
+                w.write_bits((dist - DIST_BASE[dc]) as u32, DIST_EXTRA[dc]);
+            }
+        }
+    }
+    w.write_code(lit_codes[256], lit_lengths[256]);
+}
+
+fn write_stored(w: &mut BitWriter, raw: &[u8], last: bool) {
+    let mut chunks: Vec<&[u8]> = raw.chunks(0xffff).collect();
+    if chunks.is_empty() {
+        chunks.push(&[]);
+    }
+    let count = chunks.len();
+    for (i, chunk) in chunks.into_iter().enumerate() {
+        w.write_bits((last && i + 1 == count) as u32, 1);
+        w.write_bits(0b00, 2);
+        w.align();
+        let len = chunk.len() as u16;
+        w.bytes.extend(len.to_le_bytes());
+        w.bytes.extend((!len).to_le_bytes());
+        w.bytes.extend_from_slice(chunk);
+    }
+}
+
+/// Encodes a sequence of code lengths with the repeat symbols 16, 17 and 18;
+/// returns (symbol, extra bits value) pairs.
+fn run_length_encode(lengths: &[u8]) -> Vec<(u8, u8)> {
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < lengths.len() {
+        let value = lengths[i];
+        let run = lengths[i..].iter().take_while(|&&l| l == value).count();
+        if value == 0 && run >= 3 {
+            let n = run.min(138);
+            if n <= 10 {
+                out.push((17, (n - 3) as u8));
+            } else {
+                out.push((18, (n - 11) as u8));
+            }
+            i += n;
+        } else if value != 0 && run >= 4 {
+            out.push((value, 0));
+            let n = (run - 1).min(6);
+            out.push((16, (n - 3) as u8));
+            i += 1 + n;
+        } else {
+            out.push((value, 0));
+            i += 1;
+        }
+    }
+    out
+}
+
+fn repeat_extra_bits(symbol: u8) -> u8 {
+    match symbol {
+        16 => 2,
+        17 => 3,
+        18 => 7,
+        _ => 0,
+    }
+}
+
+/// Optimal length-limited Huffman code lengths (package-merge).
+///
+/// Always yields a complete code: a lone used symbol is paired with a dummy one.
+fn code_lengths(freqs: &[u32], limit: u8) -> Vec<u8> {
+    let mut lengths = vec![0u8; freqs.len()];
+    let mut used: Vec<(u64, usize)> = freqs
+        .iter()
+        .enumerate()
+        .filter(|(_, f)| **f > 0)
+        .map(|(s, &f)| (f as u64, s))
+        .collect();
+    match used.len() {
+        0 => return lengths,
+        1 => {
+            let other = if used[0].1 == 0 { 1 } else { 0 };
+            lengths[used[0].1] = 1;
+            lengths[other] = 1;
+            return lengths;
+        }
+        _ => {}
+    }
+    used.sort();
+
+    let leaves: Vec<(u64, Vec<usize>)> = used.iter().map(|&(f, s)| (f, vec![s])).collect();
+    let mut current = leaves.clone();
+    for _ in 1..limit {
+        let packages = current
+            .chunks_exact(2)
+            .map(|pair| {
+                let mut symbols = pair[0].1.clone();
+                symbols.extend_from_slice(&pair[1].1);
+                (pair[0].0 + pair[1].0, symbols)
+            })
+            .collect::<Vec<_>>();
+        let mut merged = Vec::with_capacity(leaves.len() + packages.len());
+        let (mut a, mut b) = (leaves.iter().peekable(), packages.into_iter().peekable());
+        loop {
+            let take_leaf = match (a.peek(), b.peek()) {
+                (Some(l), Some(p)) => l.0 <= p.0,
+                (Some(_), None) => true,
+                (None, Some(_)) => false,
+                (None, None) => break,
+            };
+            if take_leaf {
+                merged.push(a.next().unwrap().clone());
+            } else {
+                merged.push(b.next().unwrap());
+            }
+        }
+        current = merged;
+    }
+
+    for (_, symbols) in current.iter().take(2 * used.len() - 2) {
+        for &s in symbols {
+            lengths[s] += 1;
+        }
+    }
+    lengths
+}
+
+/// Canonical Huffman codes for the given lengths (RFC 1951, section 3.2.2).
+fn canonical_codes(lengths: &[u8]) -> Vec<u16> {
+    let mut bl_count = [0u16; 16];
+    for &l in lengths {
+        if l > 0 {
+            bl_count[l as usize] += 1;
+        }
+    }
+    let mut next_code = [0u16; 16];
+    let mut code = 0u16;
+    for bits in 1..16 {
+        code = (code + bl_count[bits - 1]) << 1;
+        next_code[bits] = code;
+    }
+    lengths
+        .iter()
+        .map(|&l| {
+            if l == 0 {
+                return 0;
+            }
+            let c = next_code[l as usize];
+            next_code[l as usize] += 1;
+            c
+        })
+        .collect()
+}
+
+/// Packs bits least significant first, as DEFLATE requires.
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    buffer: u64,
+    count: u8,
+}
+
+impl BitWriter {
+    fn write_bits(&mut self, value: u32, bits: u8) {
+        self.buffer |= (value as u64) << self.count;
+        self.count += bits;
+        while self.count >= 8 {
+            self.bytes.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    /// Huffman codes are defined most significant bit first.
+    fn write_code(&mut self, code: u16, len: u8) {
+        let reversed = code.reverse_bits() >> (16 - len as u32);
+        self.write_bits(reversed as u32, len);
+    }
+
+    fn align(&mut self) {
+        if self.count > 0 {
+            self.write_bits(0, 8 - self.count);
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        self.align();
+        self.bytes
+    }
+}
+
+/// Decompresses a zlib stream, checking its header and Adler-32 trailer.
+/// Stops as soon as the output would grow past `limit` bytes.
+pub fn zlib_decompress(data: &[u8], limit: usize) -> io::Result<Vec<u8>> {
+    let [cmf, flg, ..] = *data else {
+        return Err(invalid("truncated zlib stream"));
+    };
+    if cmf & 0x0f != 8 || u16::from_be_bytes([cmf, flg]) % 31 != 0 {
+        return Err(invalid("invalid zlib header"));
+    }
+    if flg & 0x20 != 0 {
+        return Err(invalid("zlib preset dictionaries are not supported"));
+    }
+    let (out, used) = inflate_stream(&data[2..], limit)?;
+    let trailer = data
+        .get(2 + used..2 + used + 4)
+        .ok_or_else(|| invalid("truncated zlib stream"))?;
+    if u32::from_be_bytes(trailer.try_into().unwrap()) != adler32(&out) {
+        return Err(invalid("zlib checksum mismatch"));
+    }
+    Ok(out)
+}
+
+/// Decompresses raw DEFLATE blocks.
+pub fn inflate(data: &[u8]) -> io::Result<Vec<u8>> {
+    inflate_stream(data, usize::MAX).map(|(out, _)| out)
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn too_long() -> io::Error {
+    invalid("DEFLATE data is longer than expected")
+}
+
+/// Returns the decompressed data, at most `limit` bytes of it, and the
+/// number of input bytes used.
+fn inflate_stream(data: &[u8], limit: usize) -> io::Result<(Vec<u8>, usize)> {
+    let mut r = BitReader { data, pos: 0 };
+    let mut out = Vec::new();
+    loop {
+        let last = r.bits(1)? == 1;
+        match r.bits(2)? {
+            0b00 => {
+                r.align();
+                let len = r.bits(16)?;
+                if r.bits(16)? != !len & 0xffff {
+                    return Err(invalid("corrupt stored block length"));
+                }
+                if out.len() + len as usize > limit {
+                    return Err(too_long());
+                }
+                for _ in 0..len {
+                    out.push(r.bits(8)? as u8);
+                }
+            }
+            0b01 => {
+                let mut lengths = [8u8; 288];
+                lengths[144..256].fill(9);
+                lengths[256..280].fill(7);
+                let lit = Huffman::new(&lengths)?;
+                let dist = Huffman::new(&[5; 30])?;
+                inflate_block(&mut r, &mut out, limit, &lit, &dist)?;
+            }
+            0b10 => {
+                let (lit, dist) = dynamic_tables(&mut r)?;
+                inflate_block(&mut r, &mut out, limit, &lit, &dist)?;
+            }
+            _ => return Err(invalid("invalid DEFLATE block type")),
+        }
+        if last {
+            return Ok((out, r.pos.div_ceil(8)));
+        }
+    }
+}
+
+fn dynamic_tables(r: &mut BitReader) -> io::Result<(Huffman, Huffman)> {
+    let hlit = r.bits(5)? as usize + 257;
+    let hdist = r.bits(5)? as usize + 1;
+    let hclen = r.bits(4)? as usize + 4;
+    let mut cl_lengths = [0u8; 19];
+    for &symbol in &CODE_LENGTH_ORDER[..hclen] {
+        cl_lengths[symbol] = r.bits(3)? as u8;
+    }
+    let cl = Huffman::new(&cl_lengths)?;
+
+    let mut lengths = Vec::with_capacity(hlit + hdist);
+    while lengths.len() < hlit + hdist {
+        let (value, repeat) = match cl.decode(r)? {
+            symbol @ 0..16 => (symbol as u8, 1),
+            16 => {
+                let previous = *lengths
+                    .last()
+                    .ok_or_else(|| invalid("code length repeat without a previous length"))?;
+                (previous, 3 + r.bits(2)?)
+            }
+            17 => (0, 3 + r.bits(3)?),
+            _ => (0, 11 + r.bits(7)?),
+        };
+        lengths.extend(std::iter::repeat_n(value, repeat as usize));
+    }
+    if lengths.len() > hlit + hdist {
+        return Err(invalid("too many code lengths"));
+    }
+    if lengths[256] == 0 {
+        return Err(invalid("missing end of block code"));
+    }
+    Ok((
+        Huffman::new(&lengths[..hlit])?,
+        Huffman::new(&lengths[hlit..])?,
+    ))
+}
+
+fn inflate_block(
+    r: &mut BitReader,
+    out: &mut Vec<u8>,
+    limit: usize,
+    lit: &Huffman,
+    dist: &Huffman,
+) -> io::Result<()> {
+    loop {
+        let symbol = lit.decode(r)? as usize;
+        match symbol {
+            256 => return Ok(()),
+            _ if out.len() == limit => return Err(too_long()),
+            0..256 => out.push(symbol as u8),
+            _ => {
+                let code = symbol - 257;
+                if code >= LENGTH_BASE.len() {
+                    return Err(invalid("invalid length code"));
+                }
+                let len = LENGTH_BASE[code] as usize + r.bits(LENGTH_EXTRA[code])? as usize;
+                if out.len() + len > limit {
+                    return Err(too_long());
+                }
+                let code = dist.decode(r)? as usize;
+                if code >= DIST_BASE.len() {
+                    return Err(invalid("invalid distance code"));
+                }
+                let distance = DIST_BASE[code] as usize + r.bits(DIST_EXTRA[code])? as usize;
+                if distance > out.len() {
+                    return Err(invalid("distance reaches before the start of the data"));
+                }
+                // Byte by byte, since a match may overlap the bytes it produces.
+                let start = out.len() - distance;
+                for i in 0..len {
+                    out.push(out[start + i]);
+                }
+            }
+        }
+    }
+}
+
+/// Canonical Huffman decoding table: the number of codes of each length and
+/// the symbols ordered by code.
+struct Huffman {
+    counts: [u16; 16],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> io::Result<Self> {
+        let mut counts = [0u16; 16];
+        for &l in lengths {
+            counts[l as usize] += 1;
+        }
+        counts[0] = 0;
+        // Incomplete codes are allowed, e.g. a single distance code.
+        let mut left = 1i32;
+        for &count in &counts[1..] {
+            left = 2 * left - count as i32;
+            if left < 0 {
+                return Err(invalid("over-subscribed Huffman code"));
+            }
+        }
+        let mut offsets = [0u16; 16];
+        for len in 1..15 {
+            offsets[len + 1] = offsets[len] + counts[len];
+        }
+        let mut symbols = vec![0; lengths.len()];
+        for (symbol, &l) in lengths.iter().enumerate() {
+            if l > 0 {
+                symbols[offsets[l as usize] as usize] = symbol as u16;
+                offsets[l as usize] += 1;
+            }
+        }
+        Ok(Self { counts, symbols })
+    }
+
+    fn decode(&self, r: &mut BitReader) -> io::Result<u16> {
+        // Codes of each length are consecutive, starting at `first`.
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for &count in &self.counts[1..] {
+            code |= r.bits(1)? as i32;
+            let count = count as i32;
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(invalid("invalid Huffman code"))
+    }
+}
+
+/// Reads bits least significant first.
+struct BitReader<'a> {
+    data: &'a [u8],
+    /// Position in bits.
+    pos: usize,
+}
+
+impl BitReader<'_> {
+    fn bits(&mut self, count: u8) -> io::Result<u32> {
+        let mut value = 0;
+        for i in 0..count {
+            let byte = self
+                .data
+                .get(self.pos / 8)
+                .ok_or_else(|| invalid("truncated DEFLATE stream"))?;
+            value |= ((byte >> (self.pos % 8)) as u32 & 1) << i;
+            self.pos += 1;
+        }
+        Ok(value)
+    }
+
+    fn align(&mut self) {
+        self.pos = self.pos.div_ceil(8) * 8;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_adler32() {
+        assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
+        assert_eq!(adler32(b""), 1);
+    }
+
+    #[test]
+    fn test_code_lengths_are_limited_and_complete() {
+        // Fibonacci frequencies force a very deep unrestricted Huffman tree.
+        let mut freqs = vec![0u32; 30];
+        let (mut a, mut b) = (1u32, 1u32);
+        for f in freqs.iter_mut() {
+            *f = a;
+            (a, b) = (b, a + b);
+        }
+        let lengths = code_lengths(&freqs, 7);
+        assert!(lengths.iter().all(|&l| (1..=7).contains(&l)));
+        let kraft: f64 = lengths.iter().map(|&l| 0.5f64.powi(l as i32)).sum();
+        assert_eq!(kraft, 1.0);
+    }
+
+    #[test]
+    fn test_repetitive_data_compresses() {
+        let data: Vec<u8> = (0..100_000).map(|i| (i % 251) as u8).collect();
+        let compressed = zlib_compress(&data);
+        assert!(compressed.len() < data.len() / 50);
+        assert_eq!(compressed[..2], [0x78, 0x9c]);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut data: Vec<u8> = b"abracadabra ".repeat(500);
+        let mut state = 1u32;
+        for _ in 0..70_000 {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            data.push((state >> 24) as u8);
+        }
+        assert_eq!(
+            zlib_decompress(&zlib_compress(&data), usize::MAX).unwrap(),
+            data
+        );
+        assert_eq!(
+            zlib_decompress(&zlib_compress(&[]), usize::MAX).unwrap(),
+            b""
+        );
+    }
+
+    #[test]
+    fn test_zlib_reads_output() {
+        // Checked against miniz_oxide, so a mistake shared by `deflate` and
+        // `inflate` cannot cancel out.
+        let mut inputs = vec![
+            Vec::new(),
+            vec![7],
+            vec![0; 100_000],
+            b"abracadabra ".repeat(5000),
+            (0..200_000).map(|i| (i % 251) as u8).collect(),
+        ];
+        let mut state = 1u32;
+        for len in [1, 258, 259, 32_768, 65_536, 100_003] {
+            let random: Vec<u8> = (0..len)
+                .map(|_| {
+                    state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                    (state >> 24) as u8
+                })
+                .collect();
+            // Random bytes with the odd repeat, as in rendered images.
+            let mut mixed = random.clone();
+            mixed.extend_from_slice(&random[..len / 2]);
+            inputs.push(random);
+            inputs.push(mixed);
+        }
+        for data in inputs {
+            let zlib = miniz_oxide::inflate::decompress_to_vec_zlib(&zlib_compress(&data));
+            assert!(zlib.unwrap() == data, "{} bytes", data.len());
+            let raw = miniz_oxide::inflate::decompress_to_vec(&deflate(&data));
+            assert!(raw.unwrap() == data, "{} bytes", data.len());
+        }
+    }
+
+    #[test]
+    fn test_inflate_fixed_and_stored_blocks() {
+        // zlib.compress(b"hello hello hello hello"), fixed Huffman codes.
+        let fixed = [
+            0x78, 0x9c, 0xcb, 0x48, 0xcd, 0xc9, 0xc9, 0x57, 0xc8, 0x40, 0x27, 0x01, 0x68, 0x03,
+            0x08, 0xb1,
+        ];
+        assert_eq!(
+            zlib_decompress(&fixed, usize::MAX).unwrap(),
+            b"hello hello hello hello"
+        );
+        // A final stored block holding "abc".
+        let stored = [0x01, 0x03, 0x00, 0xfc, 0xff, b'a', b'b', b'c'];
+        assert_eq!(inflate(&stored).unwrap(), b"abc");
+        assert!(inflate(&stored[..6]).is_err());
+        assert!(zlib_decompress(&fixed, 23).is_ok());
+        for limit in [0, 5, 22] {
+            let err = zlib_decompress(&fixed, limit).unwrap_err();
+            assert_eq!(err.to_string(), "DEFLATE data is longer than expected");
+        }
+        assert!(
+            zlib_decompress(
+                &[0x78, 0x01, 0x01, 0x03, 0x00, 0xfc, 0xff, b'a', b'b', b'c'],
+                2
+            )
+            .is_err()
+        );
+        let mut corrupt = fixed;
+        corrupt[15] ^= 1;
+        assert!(zlib_decompress(&corrupt, usize::MAX).is_err());
+    }
+}
@@ -0,0 +1,264 @@
+//! Spatially varying density fields for heterogeneous media.
+
+use std::{
+    fs,
+    io::{self, Read},
+    path::Path,
+};
+
+use crate::{aabb::Aabb, perlin::Perlin, vec3::Point3};
+
+pub trait DensityField: Send + Sync {
+    /// Density at `p`, never negative.
+    fn density(&self, p: Point3) -> f64;
+    /// An upper bound on [`density`](Self::density) everywhere; the tighter it
+    /// is, the fewer steps tracking takes.
+    fn max_density(&self) -> f64;
+}
+
+/// Densities on a regular grid of voxels filling `bounds`, trilinearly
+/// interpolated between voxel centers and zero outside.
+pub struct VoxelGrid {
+    size: [usize; 3],
+    /// x varies fastest, then y, then z.
+    values: Vec<f32>,
+    bounds: Aabb,
+    max: f64,
+}
+
+impl VoxelGrid {
+    pub fn new(size: [usize; 3], values: Vec<f32>, bounds: Aabb) -> io::Result<Self> {
+        let count = size.iter().try_fold(1usize, |n, &s| n.checked_mul(s));
+        if size.contains(&0) || count != Some(values.len()) {
+            return Err(invalid_data(format!(
+                "voxel grid of {}x{}x{} needs as many values, found {}",
+                size[0],
+                size[1],
+                size[2],
+                values.len()
+            )));
+        }
+        if values.iter().any(|v| !v.is_finite() || *v < 0.0) {
+            return Err(invalid_data(
+                "voxel densities must be finite and non-negative",
+            ));
+        }
+        let max = values.iter().fold(0.0f32, |a, &b| a.max(b)) as f64;
+        Ok(Self {
+            size,
+            values,
+            bounds,
+            max,
+        })
+    }
+
+    /// Reads whitespace-separated text: the grid size `nx ny nz`, then the
+    /// values with x varying fastest. `#` starts a comment.
+    pub fn read_text(mut input: impl Read, bounds: Aabb) -> io::Result<Self> {
+        let mut text = String::new();
+        input.read_to_string(&mut text)?;
+        let mut words = text
+            .lines()
+            .map(|line| line.split('#').next().unwrap_or(""))
+            .flat_map(str::split_whitespace);
+        let mut size = [0; 3];
+        for n in &mut size {
+            let word = words
+                .next()
+                .ok_or_else(|| invalid_data("voxel grid is missing its size"))?;
+            *n = word
+                .parse()
+                .map_err(|_| invalid_data(format!("bad voxel grid size `{word}`")))?;
+        }
+        let values = words
+            .map(|word| {
+                word.parse()
+                    .map_err(|_| invalid_data(format!("bad voxel density `{word}`")))
+            })
+            .collect::<io::Result<_>>()?;
+        Self::new(size, values, bounds)
+    }
+
+    /// Reads little-endian 32-bit floats with x varying fastest.
+    pub fn read_raw(mut input: impl Read, size: [usize; 3], bounds: Aabb) -> io::Result<Self> {
+        let mut data = Vec::new();
+        input.read_to_end(&mut data)?;
+        if data.len() % 4 != 0 {
+            return Err(invalid_data(
+                "raw voxel data is not a whole number of floats",
+            ));
+        }
+        let values = data
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect();
+        Self::new(size, values, bounds)
+    }
+
+    /// Loads raw floats when `size` is given, text otherwise.
+    pub fn load(
+        path: impl AsRef<Path>,
+        size: Option<[usize; 3]>,
+        bounds: Aabb,
+    ) -> io::Result<Self> {
+        let data = fs::read(path)?;
+        match size {
+            Some(size) => Self::read_raw(&data[..], size, bounds),
+            None => Self::read_text(&data[..], bounds),
+        }
+    }
+
+    pub fn size(&self) -> [usize; 3] {
+        self.size
+    }
+
+    fn voxel(&self, x: usize, y: usize, z: usize) -> f64 {
+        let [nx, ny, _] = self.size;
+        self.values[(z * ny + y) * nx + x] as f64
+    }
+}
+
+impl DensityField for VoxelGrid {
+    fn density(&self, p: Point3) -> f64 {
+        let axes = [self.bounds.x, self.bounds.y, self.bounds.z];
+        if (0..3).any(|a| !axes[a].contains(p[a])) {
+            return 0.0;
+        }
+        // Lower corner voxel and the weight of the upper one on each axis.
+        let mut lower = [0; 3];
+        let mut upper = [0; 3];
+        let mut weight = [0.0; 3];
+        for a in 0..3 {
+            let n = self.size[a];
+            let f = (p[a] - axes[a].min) / axes[a].size() * n as f64 - 0.5;
+            let f = f.clamp(0.0, (n - 1) as f64);
+            lower[a] = f as usize;
+            upper[a] = (lower[a] + 1).min(n - 1);
+            weight[a] = f - lower[a] as f64;
+        }
+        let mut sum = 0.0;
+        for corner in 0..8 {
+            let pick = |a: usize| corner >> a & 1 == 1;
+            let w: f64 = (0..3)
+                .map(|a| if pick(a) { weight[a] } else { 1.0 - weight[a] })
+                .product();
+            let [x, y, z] = [0, 1, 2].map(|a| if pick(a) { upper[a] } else { lower[a] });
+            sum += w * self.voxel(x, y, z);
+        }
+        sum
+    }
+
+    fn max_density(&self) -> f64 {
+        self.max
+    }
+}
+
+/// Billowing, cloud-like density in `[0, 1]` from Perlin turbulence.
+pub struct NoiseDensity {
+    perlin: Perlin,
+    scale: f64,
+    pub octaves: usize,
+    /// Turbulence below this is clear air, which breaks the field into
+    /// separate puffs; the rest is stretched back to `[0, 1]`.
+    pub threshold: f64,
+}
+
+impl NoiseDensity {
+    pub fn new(scale: f64, seed: u64) -> Self {
+        Self {
+            perlin: Perlin::new(seed),
+            scale,
+            octaves: 5,
+            threshold: 0.0,
+        }
+    }
+}
+
+impl DensityField for NoiseDensity {
+    fn density(&self, p: Point3) -> f64 {
+        let t = self.perlin.turbulence(self.scale * p, self.octaves);
+        ((t - self.threshold) / (1.0 - self.threshold)).clamp(0.0, 1.0)
+    }
+
+    fn max_density(&self) -> f64 {
+        1.0
+    }
+}
+
+fn invalid_data(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::Rng;
+
+    fn unit_box() -> Aabb {
+        Aabb::from_points(Point3::origin(), Point3::new(1.0, 1.0, 1.0))
+    }
+
+    #[test]
+    fn test_voxel_grid_sampling() {
+        let grid = VoxelGrid::read_text(
+            "# two voxels along x\n2 1 1\n0.0 4.0 # trailing\n".as_bytes(),
+            unit_box(),
+        )
+        .unwrap();
+        assert_eq!(grid.size(), [2, 1, 1]);
+        assert_eq!(grid.max_density(), 4.0);
+        // Constant up to the first voxel center, linear between centers.
+        assert_eq!(grid.density(Point3::new(0.1, 0.5, 0.5)), 0.0);
+        assert_eq!(grid.density(Point3::new(0.5, 0.5, 0.5)), 2.0);
+        assert_eq!(grid.density(Point3::new(0.9, 0.2, 0.7)), 4.0);
+        assert_eq!(grid.density(Point3::new(1.5, 0.5, 0.5)), 0.0);
+
+        let raw: Vec<u8> = [1.0f32, 2.0, 3.0, 4.0]
+            .iter()
+            .flat_map(|v| v.to_le_bytes())
+            .collect();
+        let grid = VoxelGrid::read_raw(&raw[..], [1, 2, 2], unit_box()).unwrap();
+        assert_eq!(grid.density(Point3::new(0.5, 0.75, 0.25)), 2.0);
+        assert_eq!(grid.density(Point3::new(0.5, 0.25, 0.75)), 3.0);
+        assert_eq!(grid.density(Point3::new(0.5, 0.5, 0.5)), 2.5);
+    }
+
+    #[test]
+    fn test_voxel_grid_errors() {
+        let err = |text: &str| {
+            VoxelGrid::read_text(text.as_bytes(), unit_box())
+                .err()
+                .unwrap()
+                .to_string()
+        };
+        assert_eq!(
+            err("2 2 1\n1 2 3"),
+            "voxel grid of 2x2x1 needs as many values, found 3"
+        );
+        assert_eq!(err("2 x 1"), "bad voxel grid size `x`");
+        assert_eq!(
+            err("1 1 1 -1"),
+            "voxel densities must be finite and non-negative"
+        );
+        // 2^32 squared wraps to zero on 64-bit targets.
+        assert_eq!(
+            err("4294967296 4294967296 1"),
+            "voxel grid of 4294967296x4294967296x1 needs as many values, found 0"
+        );
+        assert!(VoxelGrid::read_raw(&[0u8; 5][..], [1, 1, 1], unit_box()).is_err());
+    }
+
+    #[test]
+    fn test_noise_density_range() {
+        let mut field = NoiseDensity::new(4.0, 9);
+        field.threshold = 0.3;
+        let mut rng = Rng::with_seed(1);
+        let mut clear = 0;
+        for _ in 0..500 {
+            let d = field.density(Point3::random_range(&mut rng, -3.0, 3.0));
+            assert!((0.0..=field.max_density()).contains(&d));
+            clear += (d == 0.0) as usize;
+        }
+        assert!(clear > 0 && clear < 500);
+    }
+}
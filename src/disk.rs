@@ -0,0 +1,125 @@
+use crate::{
+    aabb::Aabb,
+    hittable::{HitRecord, Hittable, area_to_solid_angle},
+    inretval::Interval,
+    material::Material,
+    ray::Ray,
+    util::Rng,
+    vec3::{Onb, Point3, Vec3},
+};
+
+/// Flat circle facing along `normal`.
+///
+/// (u, v) map the disk's bounding square onto [0, 1], so an image texture
+/// appears undistorted with the disk's center at (0.5, 0.5).
+pub struct Disk<'a> {
+    pub center: Point3,
+    pub normal: Vec3,
+    pub radius: f64,
+    pub mat: Box<dyn Material + 'a>,
+    /// u and v grow along the frame's in-plane axes.
+    frame: Onb,
+    bbox: Aabb,
+}
+
+impl<'a> Disk<'a> {
+    pub fn new(center: Point3, normal: Vec3, radius: f64, mat: impl Material + 'a) -> Self {
+        let frame = Onb::new(normal);
+        let normal = frame.w;
+        // The disk reaches radius * sin(angle to the axis) along each axis.
+        let extent = |n: f64| radius * (1.0 - n * n).max(0.0).sqrt();
+        let half = Vec3::new(extent(normal.x), extent(normal.y), extent(normal.z));
+        Self {
+            center,
+            normal,
+            radius,
+            mat: Box::new(mat),
+            frame,
+            bbox: Aabb::from_points(center - half, center + half),
+        }
+    }
+}
+
+impl<'a> Hittable for Disk<'a> {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        let denom = Vec3::dot(self.normal, r.direction);
+        if denom.abs() < 1e-8 {
+            return None;
+        }
+        let t = Vec3::dot(self.normal, self.center - r.origin) / denom;
+        if !ray_t.surrounds(t) {
+            return None;
+        }
+        let p = r.at(t);
+        let offset = p - self.center;
+        if offset.length_squared() > self.radius * self.radius {
+            return None;
+        }
+        let local = self.frame.to_local(offset);
+        let mut rec = HitRecord::new(t, p, self.normal, &*self.mat);
+        rec.u = 0.5 + local.x / (2.0 * self.radius);
+        rec.v = 0.5 + local.y / (2.0 * self.radius);
+        rec.set_face_normal(r);
+        Some(rec)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f64 {
+        match self.hit(
+            &Ray::new(origin, direction),
+            Interval::new(0.001, f64::INFINITY),
+        ) {
+            Some(rec) => {
+                let area = std::f64::consts::PI * self.radius * self.radius;
+                area_to_solid_angle(direction, rec.t, self.normal, area)
+            }
+            None => 0.0,
+        }
+    }
+
+    fn random(&self, origin: Point3, rng: &mut Rng) -> Vec3 {
+        let offset = self.radius * Vec3::random_in_unit_disk(rng);
+        self.center + self.frame.to_world(offset) - origin
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::gray;
+
+    #[test]
+    fn test_disk_hit() {
+        let disk = Disk::new(
+            Point3::new(0.0, 1.0, 0.0),
+            Vec3::new(0.0, 2.0, 0.0),
+            2.0,
+            gray(),
+        );
+        let ray_t = Interval::new(0.001, f64::INFINITY);
+
+        let r = Ray::new(Point3::new(0.0, 3.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        let rec = disk.hit(&r, ray_t).unwrap();
+        assert_eq!(rec.t, 2.0);
+        assert_eq!((rec.u, rec.v), (0.5, 0.5));
+        assert_eq!(rec.normal, Vec3::new(0.0, 1.0, 0.0));
+
+        let r = Ray::new(Point3::new(1.0, -1.0, 1.0), Vec3::new(0.0, 1.0, 0.0));
+        let rec = disk.hit(&r, ray_t).unwrap();
+        assert!(!rec.front_face);
+        assert_eq!(rec.normal, Vec3::new(0.0, -1.0, 0.0));
+        for coordinate in [rec.u, rec.v] {
+            assert!((coordinate - 0.25).abs() < 1e-12 || (coordinate - 0.75).abs() < 1e-12);
+        }
+
+        let r = Ray::new(Point3::new(1.5, -1.0, 1.5), Vec3::new(0.0, 1.0, 0.0));
+        assert!(disk.hit(&r, ray_t).is_none());
+
+        let bbox = disk.bounding_box();
+        assert_eq!((bbox.x.min, bbox.x.max), (-2.0, 2.0));
+        assert!(bbox.y.size() < 0.001);
+    }
+}
@@ -0,0 +1,67 @@
+use crate::{color::Color, vec3::Vec3};
+
+/// Radiance arriving from directions where a ray escapes the scene.
+pub enum Environment {
+    Solid(Color),
+    /// Vertical blend from `bottom` (looking straight down) to `top` (straight up).
+    Gradient {
+        bottom: Color,
+        top: Color,
+    },
+    Function(Box<dyn Fn(Vec3) -> Color + Send + Sync>),
+}
+
+impl Environment {
+    /// The blue-white daylight sky.
+    pub fn sky() -> Self {
+        Environment::Gradient {
+            bottom: Color::new(1.0, 1.0, 1.0),
+            top: Color::new(0.5, 0.7, 1.0),
+        }
+    }
+
+    pub fn value(&self, direction: Vec3) -> Color {
+        match self {
+            Environment::Solid(color) => *color,
+            Environment::Gradient { bottom, top } => {
+                let unit_direction = direction.unit_vector();
+                let a = 0.5 * (unit_direction.y + 1.0);
+                (1.0 - a) * *bottom + a * *top
+            }
+            Environment::Function(f) => f(direction),
+        }
+    }
+}
+
+impl Default for Environment {
+    fn default() -> Self {
+        Self::sky()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gradient_endpoints() {
+        let env = Environment::sky();
+        assert_eq!(
+            env.value(Vec3::new(0.0, 2.0, 0.0)),
+            Color::new(0.5, 0.7, 1.0)
+        );
+        assert_eq!(
+            env.value(Vec3::new(0.0, -1.0, 0.0)),
+            Color::new(1.0, 1.0, 1.0)
+        );
+    }
+
+    #[test]
+    fn test_function() {
+        let env = Environment::Function(Box::new(|d: Vec3| Color::from(d)));
+        assert_eq!(
+            env.value(Vec3::new(0.25, 0.5, 1.0)),
+            Color::new(0.25, 0.5, 1.0)
+        );
+    }
+}
@@ -0,0 +1,513 @@
+//! Scanline OpenEXR output with arbitrary named channels.
+
+use std::io::{self, Write};
+
+use crate::{deflate::zlib_compress, framebuffer::Framebuffer, output::ImageWriter};
+
+const MAGIC: [u8; 4] = [0x76, 0x2f, 0x31, 0x01];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SampleType {
+    /// 16-bit IEEE 754 half precision floats.
+    #[default]
+    Half,
+    /// 32-bit floats.
+    Float,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Compression {
+    None,
+    /// Run-length encoding, one scanline per block.
+    Rle,
+    /// Deflate, one scanline per block.
+    Zips,
+    /// Deflate, blocks of 16 scanlines.
+    #[default]
+    Zip,
+}
+
+impl Compression {
+    fn id(self) -> u8 {
+        match self {
+            Compression::None => 0,
+            Compression::Rle => 1,
+            Compression::Zips => 2,
+            Compression::Zip => 3,
+        }
+    }
+
+    fn lines_per_block(self) -> usize {
+        match self {
+            Compression::Zip => 16,
+            _ => 1,
+        }
+    }
+}
+
+/// Writes the framebuffer with its passes, see [`write_framebuffer_exr`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ExrOptions {
+    pub sample_type: SampleType,
+    pub compression: Compression,
+}
+
+impl ImageWriter for ExrOptions {
+    fn write(&self, out: &mut dyn Write, image: &Framebuffer) -> io::Result<()> {
+        write_framebuffer_exr(out, image, self.sample_type, self.compression)
+    }
+}
+
+/// A named image plane such as `R` or `N.X`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Channel {
+    pub name: String,
+    pub sample_type: SampleType,
+    /// One value per pixel, row by row starting from the top-left corner.
+    pub values: Vec<f32>,
+}
+
+impl Channel {
+    pub fn new(name: impl Into<String>, sample_type: SampleType, values: Vec<f32>) -> Self {
+        Self {
+            name: name.into(),
+            sample_type,
+            values,
+        }
+    }
+}
+
+/// The beauty pass as `R`, `G`, `B`, coverage as `A`, depth as `Z` and the
+/// shading normal as `N.X`, `N.Y`, `N.Z`.
+///
+/// OpenEXR colors are premultiplied by alpha, so the beauty pass is scaled
+/// by coverage: the background seen where nothing was hit drops out instead
+/// of glowing through in a compositor. Depth is always stored as `Float`,
+/// half precision is too coarse for it.
+pub fn framebuffer_channels(image: &Framebuffer, sample_type: SampleType) -> Vec<Channel> {
+    let colors = image.pixels();
+    let aovs = image.aovs();
+    let plane = |name: &str, sample_type, value: &dyn Fn(usize) -> f64| {
+        let values = (0..colors.len()).map(|i| value(i) as f32).collect();
+        Channel::new(name, sample_type, values)
+    };
+    vec![
+        plane("R", sample_type, &|i| aovs[i].alpha * colors[i].r),
+        plane("G", sample_type, &|i| aovs[i].alpha * colors[i].g),
+        plane("B", sample_type, &|i| aovs[i].alpha * colors[i].b),
+        plane("A", sample_type, &|i| aovs[i].alpha),
+        plane("Z", SampleType::Float, &|i| aovs[i].depth),
+        plane("N.X", sample_type, &|i| aovs[i].normal.x),
+        plane("N.Y", sample_type, &|i| aovs[i].normal.y),
+        plane("N.Z", sample_type, &|i| aovs[i].normal.z),
+    ]
+}
+
+/// Encodes the framebuffer and its passes, see [`framebuffer_channels`].
+pub fn write_framebuffer_exr(
+    out: impl Write,
+    image: &Framebuffer,
+    sample_type: SampleType,
+    compression: Compression,
+) -> io::Result<()> {
+    let channels = framebuffer_channels(image, sample_type);
+    write_exr(out, image.width(), image.height(), &channels, compression)
+}
+
+/// Encodes the channels as a single part scanline OpenEXR image.
+pub fn write_exr(
+    mut out: impl Write,
+    width: usize,
+    height: usize,
+    channels: &[Channel],
+    compression: Compression,
+) -> io::Result<()> {
+    let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidInput, message);
+    if width == 0 || height == 0 {
+        return Err(invalid("OpenEXR images cannot be empty".to_string()));
+    }
+    // Readers expect the channels, and their data, sorted by name.
+    let mut channels: Vec<&Channel> = channels.iter().collect();
+    channels.sort_by(|a, b| a.name.as_bytes().cmp(b.name.as_bytes()));
+    for (i, channel) in channels.iter().enumerate() {
+        if channel.name.is_empty() || channel.name.contains('\0') {
+            return Err(invalid(format!("invalid channel name {:?}", channel.name)));
+        }
+        if i > 0 && channels[i - 1].name == channel.name {
+            return Err(invalid(format!("duplicate channel `{}`", channel.name)));
+        }
+        if channel.values.len() != width * height {
+            return Err(invalid(format!(
+                "channel `{}` has {} values for {width}x{height} pixels",
+                channel.name,
+                channel.values.len()
+            )));
+        }
+    }
+
+    let mut header = Vec::new();
+    header.extend(MAGIC);
+    // Version 2, single part scanline file.
+    header.extend(2u32.to_le_bytes());
+    let mut chlist = Vec::new();
+    for channel in &channels {
+        chlist.extend(channel.name.as_bytes());
+        chlist.push(0);
+        let pixel_type: i32 = match channel.sample_type {
+            SampleType::Half => 1,
+            SampleType::Float => 2,
+        };
+        chlist.extend(pixel_type.to_le_bytes());
+        // pLinear and reserved bytes, then x and y sampling.
+        chlist.extend([0, 0, 0, 0]);
+        chlist.extend(1i32.to_le_bytes());
+        chlist.extend(1i32.to_le_bytes());
+    }
+    chlist.push(0);
+    let window: Vec<u8> = [0, 0, width as i32 - 1, height as i32 - 1]
+        .iter()
+        .flat_map(|v| v.to_le_bytes())
+        .collect();
+    attribute(&mut header, "channels", "chlist", &chlist);
+    attribute(
+        &mut header,
+        "compression",
+        "compression",
+        &[compression.id()],
+    );
+    attribute(&mut header, "dataWindow", "box2i", &window);
+    attribute(&mut header, "displayWindow", "box2i", &window);
+    // Increasing y.
+    attribute(&mut header, "lineOrder", "lineOrder", &[0]);
+    attribute(
+        &mut header,
+        "pixelAspectRatio",
+        "float",
+        &1f32.to_le_bytes(),
+    );
+    attribute(&mut header, "screenWindowCenter", "v2f", &[0; 8]);
+    attribute(
+        &mut header,
+        "screenWindowWidth",
+        "float",
+        &1f32.to_le_bytes(),
+    );
+    header.push(0);
+
+    let lines = compression.lines_per_block();
+    let blocks: Vec<Vec<u8>> = (0..height)
+        .step_by(lines)
+        .map(|y| {
+            let mut raw = Vec::new();
+            for row in y..(y + lines).min(height) {
+                for channel in &channels {
+                    let values = &channel.values[row * width..(row + 1) * width];
+                    for &value in values {
+                        match channel.sample_type {
+                            SampleType::Half => raw.extend(f32_to_half(value).to_le_bytes()),
+                            SampleType::Float => raw.extend(value.to_le_bytes()),
+                        }
+                    }
+                }
+            }
+            compress(raw, compression)
+        })
+        .collect();
+
+    out.write_all(&header)?;
+    let mut offset = (header.len() + 8 * blocks.len()) as u64;
+    for block in &blocks {
+        out.write_all(&offset.to_le_bytes())?;
+        offset += 8 + block.len() as u64;
+    }
+    for (i, block) in blocks.iter().enumerate() {
+        out.write_all(&((i * lines) as i32).to_le_bytes())?;
+        out.write_all(&(block.len() as i32).to_le_bytes())?;
+        out.write_all(block)?;
+    }
+    Ok(())
+}
+
+fn attribute(header: &mut Vec<u8>, name: &str, kind: &str, value: &[u8]) {
+    header.extend(name.as_bytes());
+    header.push(0);
+    header.extend(kind.as_bytes());
+    header.push(0);
+    header.extend((value.len() as i32).to_le_bytes());
+    header.extend(value);
+}
+
+/// A block that does not shrink is stored as is; readers recognise it by its
+/// size.
+fn compress(raw: Vec<u8>, compression: Compression) -> Vec<u8> {
+    let packed = match compression {
+        Compression::None => return raw,
+        Compression::Rle => rle(&predict(&raw)),
+        Compression::Zips | Compression::Zip => zlib_compress(&predict(&raw)),
+    };
+    if packed.len() < raw.len() {
+        packed
+    } else {
+        raw
+    }
+}
+
+/// Splits the even and odd bytes into two halves, then replaces every byte by
+/// its difference to the previous one. Both make deflate and RLE work better
+/// on little-endian floats.
+fn predict(raw: &[u8]) -> Vec<u8> {
+    let mut bytes: Vec<u8> = raw.iter().step_by(2).copied().collect();
+    bytes.extend(raw.iter().skip(1).step_by(2));
+    let mut previous = bytes.first().copied().unwrap_or(0);
+    for byte in bytes.iter_mut().skip(1) {
+        let current = *byte;
+        *byte = current.wrapping_sub(previous).wrapping_add(128);
+        previous = current;
+    }
+    bytes
+}
+
+/// Runs of identical bytes become `count - 1, value`; everything else is
+/// copied as `-count, bytes...`.
+fn rle(data: &[u8]) -> Vec<u8> {
+    const MIN_RUN: usize = 3;
+    let run_at = |i: usize, max: usize| {
+        data[i..]
+            .iter()
+            .take(max)
+            .take_while(|&&b| b == data[i])
+            .count()
+    };
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < data.len() {
+        let run = run_at(i, 128);
+        if run >= MIN_RUN {
+            out.extend([(run - 1) as u8, data[i]]);
+            i += run;
+            continue;
+        }
+        // Copy literally up to the next run worth encoding.
+        let mut end = i;
+        while end < data.len() && end - i < 127 && run_at(end, MIN_RUN) < MIN_RUN {
+            end += 1;
+        }
+        out.push(((end - i) as u8).wrapping_neg());
+        out.extend(&data[i..end]);
+        i = end;
+    }
+    out
+}
+
+/// Rounds to the nearest half precision float, ties to even. Values beyond
+/// the half range become infinite.
+pub fn f32_to_half(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x7f_ffff;
+    if exponent == 0xff {
+        // Keep NaNs quiet and NaN.
+        let nan = if mantissa != 0 { 0x200 } else { 0 };
+        return sign | 0x7c00 | nan;
+    }
+    let rebiased = exponent - 127 + 15;
+    if rebiased >= 0x1f {
+        return sign | 0x7c00;
+    }
+    let round = |value: u32, shift: u32| {
+        let halfway = 1 << (shift - 1);
+        let rest = value & ((1 << shift) - 1);
+        let truncated = value >> shift;
+        if rest > halfway || (rest == halfway && truncated & 1 == 1) {
+            truncated + 1
+        } else {
+            truncated
+        }
+    };
+    if rebiased <= 0 {
+        // Subnormal, or too small for even that.
+        if rebiased < -10 {
+            return sign;
+        }
+        let shift = (14 - rebiased) as u32;
+        return sign | round(mantissa | 0x80_0000, shift) as u16;
+    }
+    // A carry out of the mantissa correctly bumps the exponent.
+    sign | round(((rebiased as u32) << 23) | mantissa, 13) as u16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{color::Color, framebuffer::Aov, vec3::Vec3};
+
+    fn unpredict(mut bytes: Vec<u8>) -> Vec<u8> {
+        for i in 1..bytes.len() {
+            bytes[i] = bytes[i - 1].wrapping_add(bytes[i]).wrapping_sub(128);
+        }
+        let half = bytes.len().div_ceil(2);
+        let (even, odd) = bytes.split_at(half);
+        let mut raw = Vec::with_capacity(bytes.len());
+        for (i, &b) in even.iter().enumerate() {
+            raw.push(b);
+            raw.extend(odd.get(i));
+        }
+        raw
+    }
+
+    fn unrle(mut data: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        while !data.is_empty() {
+            let count = data[0] as i8;
+            if count < 0 {
+                let n = -(count as isize) as usize;
+                out.extend_from_slice(&data[1..1 + n]);
+                data = &data[1 + n..];
+            } else {
+                out.extend(std::iter::repeat_n(data[1], count as usize + 1));
+                data = &data[2..];
+            }
+        }
+        out
+    }
+
+    /// Returns the offset table and the end of the header.
+    fn read_offsets(file: &[u8], blocks: usize) -> (Vec<u64>, usize) {
+        let mut pos = 8;
+        while file[pos] != 0 {
+            let name_end = pos + file[pos..].iter().position(|&b| b == 0).unwrap();
+            let kind_end =
+                name_end + 1 + file[name_end + 1..].iter().position(|&b| b == 0).unwrap();
+            let size = i32::from_le_bytes(file[kind_end + 1..kind_end + 5].try_into().unwrap());
+            pos = kind_end + 5 + size as usize;
+        }
+        let table = pos + 1;
+        let offsets = (0..blocks)
+            .map(|i| u64::from_le_bytes(file[table + 8 * i..table + 8 * i + 8].try_into().unwrap()))
+            .collect();
+        (offsets, table + 8 * blocks)
+    }
+
+    #[test]
+    fn test_half() {
+        assert_eq!(f32_to_half(0.0), 0x0000);
+        assert_eq!(f32_to_half(-0.0), 0x8000);
+        assert_eq!(f32_to_half(1.0), 0x3c00);
+        assert_eq!(f32_to_half(-2.0), 0xc000);
+        assert_eq!(f32_to_half(0.1), 0x2e66);
+        assert_eq!(f32_to_half(65504.0), 0x7bff);
+        assert_eq!(f32_to_half(65520.0), 0x7c00);
+        assert_eq!(f32_to_half(f32::INFINITY), 0x7c00);
+        assert_eq!(f32_to_half(f32::NAN) & 0x7e00, 0x7e00);
+        // Subnormals round to the nearest multiple of 2^-24, ties to even.
+        assert_eq!(f32_to_half(2f32.powi(-24)), 0x0001);
+        assert_eq!(f32_to_half(2f32.powi(-25)), 0x0000);
+        assert_eq!(f32_to_half(1.5 * 2f32.powi(-25)), 0x0001);
+        assert_eq!(f32_to_half(2f32.powi(-14)), 0x0400);
+    }
+
+    #[test]
+    fn test_rle_round_trip() {
+        let mut data = vec![7u8; 300];
+        data.extend(0..=255u8);
+        data.extend([1, 1, 2, 2, 2, 3]);
+        assert_eq!(unrle(&rle(&data)), data);
+        assert_eq!(unpredict(predict(&data)), data);
+    }
+
+    #[test]
+    fn test_uncompressed_layout() {
+        let channels = [
+            Channel::new("G", SampleType::Float, vec![1.0, 2.0, 3.0, 4.0]),
+            Channel::new("B", SampleType::Half, vec![1.0, 0.5, 0.0, -2.0]),
+        ];
+        let mut out = Vec::new();
+        write_exr(&mut out, 2, 2, &channels, Compression::None).unwrap();
+
+        assert_eq!(out[..8], [0x76, 0x2f, 0x31, 0x01, 2, 0, 0, 0]);
+        let chlist = b"channels\0chlist\0";
+        assert_eq!(out[8..8 + chlist.len()], chlist[..]);
+        // Sorted, so `B` comes first.
+        assert_eq!(out[8 + chlist.len() + 4..8 + chlist.len() + 6], *b"B\0");
+
+        let (offsets, data) = read_offsets(&out, 2);
+        assert_eq!(offsets[0] as usize, data);
+        let line = 2 * 2 + 2 * 4;
+        assert_eq!(offsets[1] as usize, data + 8 + line);
+        let second = &out[offsets[1] as usize..];
+        assert_eq!(second[..8], [1, 0, 0, 0, line as u8, 0, 0, 0]);
+        assert_eq!(second[8..10], 0x0000u16.to_le_bytes());
+        assert_eq!(second[10..12], 0xc000u16.to_le_bytes());
+        assert_eq!(second[12..16], 3f32.to_le_bytes());
+        assert_eq!(out.len(), data + 2 * (8 + line));
+    }
+
+    #[test]
+    fn test_rle_blocks_decode() {
+        let mut image = Framebuffer::new(20, 3);
+        for x in 0..20 {
+            image.set(x, 1, Color::new(x as f64 / 20.0, 0.5, 8.0));
+            image.set_aov(
+                x,
+                1,
+                Aov {
+                    alpha: 0.5,
+                    depth: 3.25,
+                    normal: Vec3::new(0.0, 1.0, 0.0),
+                },
+            );
+        }
+        let mut out = Vec::new();
+        write_framebuffer_exr(&mut out, &image, SampleType::Half, Compression::Rle).unwrap();
+
+        let (offsets, _) = read_offsets(&out, 3);
+        let block = &out[offsets[1] as usize..offsets[2] as usize];
+        assert_eq!(block[..4], 1i32.to_le_bytes());
+        let size = i32::from_le_bytes(block[4..8].try_into().unwrap()) as usize;
+        assert_eq!(block.len(), 8 + size);
+        // Channels in name order: A B G N.X N.Y N.Z R Z.
+        let raw = unpredict(unrle(&block[8..]));
+        assert_eq!(raw.len(), 20 * (7 * 2 + 4));
+        let half = |channel: usize, x: usize| {
+            let i = channel * 40 + 2 * x;
+            u16::from_le_bytes([raw[i], raw[i + 1]])
+        };
+        // Colors are premultiplied by the coverage of one half.
+        assert_eq!(half(0, 3), f32_to_half(0.5));
+        assert_eq!(half(1, 3), f32_to_half(4.0));
+        assert_eq!(half(4, 3), f32_to_half(1.0));
+        assert_eq!(half(6, 10), f32_to_half(0.25));
+        assert_eq!(raw[7 * 40..7 * 40 + 4], 3.25f32.to_le_bytes());
+    }
+
+    #[test]
+    fn test_background_drops_out() {
+        // Sky behind nothing, and a white surface covering its pixel fully.
+        let mut image = Framebuffer::new(2, 1);
+        image.set(0, 0, Color::new(0.5, 0.7, 1.0));
+        image.set(1, 0, Color::new(1.0, 1.0, 1.0));
+        image.set_aov(
+            0,
+            0,
+            Aov {
+                alpha: 0.0,
+                ..Aov::default()
+            },
+        );
+        let channels = framebuffer_channels(&image, SampleType::Float);
+        let red = channels.iter().find(|c| c.name == "R").unwrap();
+        assert_eq!(red.values, [0.0, 1.0]);
+    }
+
+    #[test]
+    fn test_invalid_channels() {
+        let twice = [
+            Channel::new("R", SampleType::Half, vec![0.0]),
+            Channel::new("R", SampleType::Half, vec![0.0]),
+        ];
+        assert!(write_exr(Vec::new(), 1, 1, &twice, Compression::None).is_err());
+        let short = [Channel::new("R", SampleType::Half, vec![0.0])];
+        assert!(write_exr(Vec::new(), 2, 1, &short, Compression::None).is_err());
+    }
+}
@@ -0,0 +1,98 @@
+use crate::color::Color;
+use crate::vec3::Vec3;
+
+/// Rendered image held in memory as linear (not gamma corrected) colors.
+///
+/// Pixels are stored row by row starting from the top-left corner. Next to
+/// the beauty pass every pixel carries an [`Aov`] for compositing.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Framebuffer {
+    width: usize,
+    height: usize,
+    pixels: Vec<Color>,
+    aovs: Vec<Aov>,
+}
+
+/// Auxiliary passes of a pixel, taken from the first surface hit by each of
+/// its camera rays.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aov {
+    /// Fraction of the samples that hit geometry.
+    pub alpha: f64,
+    /// Mean distance from the camera to the first hit, infinite for none.
+    pub depth: f64,
+    /// Normalized mean of the world space shading normals at the first hit.
+    pub normal: Vec3,
+}
+
+impl Default for Aov {
+    fn default() -> Self {
+        Self {
+            alpha: 1.0,
+            depth: f64::INFINITY,
+            normal: Vec3::new(0.0, 0.0, 0.0),
+        }
+    }
+}
+
+impl Framebuffer {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![Color::new(0.0, 0.0, 0.0); width * height],
+            aovs: vec![Aov::default(); width * height],
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Color {
+        self.pixels[self.index(x, y)]
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, color: Color) {
+        let index = self.index(x, y);
+        self.pixels[index] = color;
+    }
+
+    pub fn aov(&self, x: usize, y: usize) -> Aov {
+        self.aovs[self.index(x, y)]
+    }
+
+    pub fn set_aov(&mut self, x: usize, y: usize, aov: Aov) {
+        let index = self.index(x, y);
+        self.aovs[index] = aov;
+    }
+
+    pub fn pixels(&self) -> &[Color] {
+        &self.pixels
+    }
+
+    pub fn pixels_mut(&mut self) -> &mut [Color] {
+        &mut self.pixels
+    }
+
+    pub fn aovs(&self) -> &[Aov] {
+        &self.aovs
+    }
+
+    pub fn row(&self, y: usize) -> &[Color] {
+        &self.pixels[y * self.width..(y + 1) * self.width]
+    }
+
+    pub fn aov_row(&self, y: usize) -> &[Aov] {
+        &self.aovs[y * self.width..(y + 1) * self.width]
+    }
+
+    fn index(&self, x: usize, y: usize) -> usize {
+        assert!(x < self.width && y < self.height, "pixel out of bounds");
+        y * self.width + x
+    }
+}
@@ -0,0 +1,219 @@
+//! Lossless floating point outputs written straight from the linear framebuffer.
+
+use std::io::{self, Write};
+
+use crate::{color::Color, framebuffer::Framebuffer, output::ImageWriter};
+
+/// Radiance RGBE, see [`write_hdr`].
+pub struct Hdr;
+
+/// Portable Float Map, see [`write_pfm`].
+pub struct Pfm;
+
+impl ImageWriter for Hdr {
+    fn write(&self, out: &mut dyn Write, image: &Framebuffer) -> io::Result<()> {
+        write_hdr(out, image)
+    }
+}
+
+impl ImageWriter for Pfm {
+    fn write(&self, out: &mut dyn Write, image: &Framebuffer) -> io::Result<()> {
+        write_pfm(out, image)
+    }
+}
+
+/// Encodes the framebuffer as a Radiance RGBE (`.hdr`) image with run-length
+/// encoded scanlines.
+pub fn write_hdr(mut out: impl Write, image: &Framebuffer) -> io::Result<()> {
+    write!(
+        out,
+        "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n",
+        image.height(),
+        image.width()
+    )?;
+    let width = image.width();
+    // The adaptive run-length scheme only exists for these widths.
+    let rle = (8..0x8000).contains(&width);
+    let mut channels: Vec<Vec<u8>> = (0..4).map(|_| Vec::with_capacity(width)).collect();
+    for y in 0..image.height() {
+        let row: Vec<[u8; 4]> = image.row(y).iter().map(|&c| to_rgbe(c)).collect();
+        if !rle {
+            out.write_all(row.as_flattened())?;
+            continue;
+        }
+        out.write_all(&[2, 2, (width >> 8) as u8, (width & 0xff) as u8])?;
+        for (i, channel) in channels.iter_mut().enumerate() {
+            channel.clear();
+            channel.extend(row.iter().map(|rgbe| rgbe[i]));
+            write_rle(&mut out, channel)?;
+        }
+    }
+    Ok(())
+}
+
+/// Shared-exponent encoding; negative and NaN components are clamped to
+/// zero, and values too bright for the exponent byte, infinity included, to
+/// the largest it holds.
+pub fn to_rgbe(color: Color) -> [u8; 4] {
+    let (r, g, b) = (color.r.max(0.0), color.g.max(0.0), color.b.max(0.0));
+    let v = r.max(g).max(b);
+    if v < 1e-32 {
+        return [0, 0, 0, 0];
+    }
+    if v >= 2f64.powi(127) {
+        let scale = 256.0 / 2f64.powi(127);
+        let [r, g, b] = [r, g, b].map(|c| (c * scale).min(255.0) as u8);
+        return [r, g, b, 255];
+    }
+    // v = m * 2^e with m in [0.5, 1), as C's frexp returns it.
+    let mut e = v.log2().floor() as i32 + 1;
+    if v / 2f64.powi(e) >= 1.0 {
+        e += 1;
+    }
+    let scale = 256.0 / 2f64.powi(e);
+    [
+        (r * scale) as u8,
+        (g * scale) as u8,
+        (b * scale) as u8,
+        (e + 128) as u8,
+    ]
+}
+
+/// Runs of identical bytes become `128 + count, value`; everything else is
+/// copied as `count, bytes...`.
+fn write_rle(mut out: impl Write, data: &[u8]) -> io::Result<()> {
+    const MIN_RUN: usize = 4;
+    let mut i = 0;
+    while i < data.len() {
+        let run = data[i..]
+            .iter()
+            .take(127)
+            .take_while(|&&b| b == data[i])
+            .count();
+        if run >= MIN_RUN {
+            out.write_all(&[128 + run as u8, data[i]])?;
+            i += run;
+            continue;
+        }
+        // Copy literally up to the next run worth encoding.
+        let mut end = i;
+        while end < data.len() && end - i < 128 {
+            let ahead = data[end..]
+                .iter()
+                .take(MIN_RUN)
+                .take_while(|&&b| b == data[end])
+                .count();
+            if ahead >= MIN_RUN {
+                break;
+            }
+            end += 1;
+        }
+        out.write_all(&[(end - i) as u8])?;
+        out.write_all(&data[i..end])?;
+        i = end;
+    }
+    Ok(())
+}
+
+/// Encodes the framebuffer as a little-endian Portable Float Map (`.pfm`).
+pub fn write_pfm(mut out: impl Write, image: &Framebuffer) -> io::Result<()> {
+    // A negative scale marks little-endian data; rows run bottom to top.
+    write!(out, "PF\n{} {}\n-1.0\n", image.width(), image.height())?;
+    for y in (0..image.height()).rev() {
+        for pixel in image.row(y) {
+            for channel in [pixel.r, pixel.g, pixel.b] {
+                out.write_all(&(channel as f32).to_le_bytes())?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn from_rgbe(rgbe: [u8; 4]) -> Color {
+        if rgbe[3] == 0 {
+            return Color::new(0.0, 0.0, 0.0);
+        }
+        let f = 2f64.powi(rgbe[3] as i32 - 128 - 8);
+        Color::new(rgbe[0] as f64 * f, rgbe[1] as f64 * f, rgbe[2] as f64 * f)
+    }
+
+    #[test]
+    fn test_rgbe() {
+        assert_eq!(to_rgbe(Color::new(1.0, 0.5, 0.0)), [128, 64, 0, 129]);
+        assert_eq!(to_rgbe(Color::new(0.0, -1.0, 0.0)), [0, 0, 0, 0]);
+        assert_eq!(to_rgbe(Color::new(f64::NAN, 1.0, 0.0)), [0, 128, 0, 129]);
+        // Out of range saturates rather than wrapping the exponent.
+        assert_eq!(
+            to_rgbe(Color::new(f64::INFINITY, 1e300, 1.0)),
+            [255, 255, 0, 255]
+        );
+        assert_eq!(
+            to_rgbe(Color::new(2f64.powi(127), 2f64.powi(126), 0.0)),
+            [255, 128, 0, 255]
+        );
+        assert_eq!(to_rgbe(Color::new(1.5 * 2f64.powi(126), 0.0, 0.0))[3], 255);
+        // Every channel shares the exponent of the brightest one.
+        let decoded = from_rgbe(to_rgbe(Color::new(1000.0, 300.0, 0.25)));
+        assert!((1000.0 - decoded.r) / 1000.0 < 1.0 / 128.0);
+        assert!((300.0 - decoded.g) < 4.0);
+        assert_eq!(decoded.b, 0.0);
+    }
+
+    #[test]
+    fn test_hdr_rle_round_trip() {
+        let mut image = Framebuffer::new(40, 2);
+        for x in 0..40 {
+            let value = if x < 20 { 2.0 } else { x as f64 / 40.0 };
+            image.set(x, 1, Color::new(value, 0.5, 0.125));
+        }
+        let mut out = Vec::new();
+        write_hdr(&mut out, &image).unwrap();
+
+        let header = b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y 2 +X 40\n";
+        assert_eq!(out[..header.len()], header[..]);
+        let mut data = &out[header.len()..];
+        for y in 0..2 {
+            assert_eq!(data[..4], [2, 2, 0, 40]);
+            data = &data[4..];
+            let mut channels = vec![Vec::new(); 4];
+            for channel in channels.iter_mut() {
+                while channel.len() < 40 {
+                    let count = data[0] as usize;
+                    if count > 128 {
+                        channel.extend(std::iter::repeat_n(data[1], count - 128));
+                        data = &data[2..];
+                    } else {
+                        channel.extend_from_slice(&data[1..1 + count]);
+                        data = &data[1 + count..];
+                    }
+                }
+                assert_eq!(channel.len(), 40);
+            }
+            for x in 0..40 {
+                let rgbe: Vec<u8> = channels.iter().map(|channel| channel[x]).collect();
+                assert_eq!(rgbe, to_rgbe(image.get(x, y)));
+            }
+        }
+        assert!(data.is_empty());
+    }
+
+    #[test]
+    fn test_pfm() {
+        let mut image = Framebuffer::new(2, 2);
+        image.set(1, 0, Color::new(4.5, 0.0, -1.0));
+        let mut out = Vec::new();
+        write_pfm(&mut out, &image).unwrap();
+
+        let header = b"PF\n2 2\n-1.0\n";
+        assert_eq!(out[..header.len()], header[..]);
+        assert_eq!(out.len(), header.len() + 2 * 2 * 3 * 4);
+        // The top row is stored last.
+        let top_right = &out[header.len() + 3 * 12..header.len() + 4 * 12];
+        assert_eq!(top_right[..4], 4.5f32.to_le_bytes());
+        assert_eq!(top_right[8..], (-1.0f32).to_le_bytes());
+    }
+}
@@ -1,15 +1,20 @@
-use std::rc::Rc;
+use std::sync::Arc;
 
+use crate::aabb::Aabb;
 use crate::inretval::Interval;
 use crate::material::Material;
+use crate::util::Rng;
 use crate::{
     ray::Ray,
     vec3::{Point3, Vec3},
 };
-pub struct HitRecord<'a>{
+pub struct HitRecord<'a> {
     pub t: f64,
     pub p: Point3,
     pub normal: Vec3,
+    /// Surface coordinates of the hit point, used for texturing and emission.
+    pub u: f64,
+    pub v: f64,
     pub front_face: bool,
     pub mat: &'a dyn Material,
 }
@@ -20,6 +25,8 @@ impl<'a> HitRecord<'a> {
             p,
             normal,
             t,
+            u: 0.0,
+            v: 0.0,
             front_face: true,
             mat,
         }
@@ -32,6 +39,174 @@ impl<'a> HitRecord<'a> {
         }
     }
 }
-pub trait Hittable {
-    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord>;
+pub trait Hittable: Send + Sync {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord<'_>>;
+    fn bounding_box(&self) -> Aabb;
+
+    /// Fraction of light that gets through along `r` within `ray_t`, as seen
+    /// by a shadow ray. Surfaces block it; participating media let part of
+    /// it through, so estimating it may draw from `rng`.
+    fn transmittance(&self, r: &Ray, ray_t: Interval, _rng: &mut Rng) -> f64 {
+        if self.hit(r, ray_t).is_some() {
+            0.0
+        } else {
+            1.0
+        }
+    }
+
+    /// Density, per unit solid angle as seen from `origin`, with which
+    /// [`random`](Self::random) picks `direction`. Zero for objects that
+    /// cannot be sampled, and wherever the object is not in the way.
+    fn pdf_value(&self, _origin: Point3, _direction: Vec3) -> f64 {
+        0.0
+    }
+
+    /// Direction from `origin` towards a random point of the object, used to
+    /// aim shadow rays at lights. Only meaningful where `pdf_value` is not
+    /// always zero.
+    fn random(&self, _origin: Point3, _rng: &mut Rng) -> Vec3 {
+        Vec3::new(1.0, 0.0, 0.0)
+    }
+}
+
+impl<H: Hittable + ?Sized> Hittable for Box<H> {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        (**self).hit(r, ray_t)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        (**self).bounding_box()
+    }
+
+    fn transmittance(&self, r: &Ray, ray_t: Interval, rng: &mut Rng) -> f64 {
+        (**self).transmittance(r, ray_t, rng)
+    }
+
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f64 {
+        (**self).pdf_value(origin, direction)
+    }
+
+    fn random(&self, origin: Point3, rng: &mut Rng) -> Vec3 {
+        (**self).random(origin, rng)
+    }
+}
+
+/// Lets one object be shared, e.g. placed several times through transforms.
+impl<H: Hittable + ?Sized> Hittable for Arc<H> {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        (**self).hit(r, ray_t)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        (**self).bounding_box()
+    }
+
+    fn transmittance(&self, r: &Ray, ray_t: Interval, rng: &mut Rng) -> f64 {
+        (**self).transmittance(r, ray_t, rng)
+    }
+
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f64 {
+        (**self).pdf_value(origin, direction)
+    }
+
+    fn random(&self, origin: Point3, rng: &mut Rng) -> Vec3 {
+        (**self).random(origin, rng)
+    }
+}
+
+/// Converts the density of a uniformly sampled point on a flat patch of
+/// `area` into density per solid angle at the ray origin, for the point hit
+/// at `t` along `direction` where the patch faces along `normal`.
+pub(crate) fn area_to_solid_angle(direction: Vec3, t: f64, normal: Vec3, area: f64) -> f64 {
+    let length = direction.length();
+    let distance_squared = t * t * direction.length_squared();
+    let cosine = (Vec3::dot(direction, normal) / (length * normal.length())).abs();
+    if cosine < 1e-8 {
+        return 0.0;
+    }
+    distance_squared / (cosine * area)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use super::*;
+    use crate::{
+        disk::Disk, hittable_list::HittableList, quad::Quad, sphere::Sphere, test_util::gray,
+        transform::Transform, triangle::Triangle, vec3::Mat4,
+    };
+
+    /// Checks that the density integrates to one over all directions and
+    /// that the samples cover the solid angle the shape fills.
+    fn check_sampling(name: &str, shape: &dyn Hittable, origin: Point3) {
+        let mut rng = Rng::with_seed(5);
+        let trials = 100_000;
+        let (mut total, mut covered) = (0.0, 0);
+        for _ in 0..trials {
+            let pdf = shape.pdf_value(origin, Vec3::random_unit_vector(&mut rng));
+            total += pdf;
+            covered += (pdf > 0.0) as usize;
+        }
+        let integral = 4.0 * PI * total / trials as f64;
+        assert!((integral - 1.0).abs() < 0.03, "{name}: {integral}");
+
+        let solid_angle = 4.0 * PI * covered as f64 / trials as f64;
+        let mut estimate = 0.0;
+        for _ in 0..trials {
+            let pdf = shape.pdf_value(origin, shape.random(origin, &mut rng));
+            assert!(pdf > 0.0, "{name}: sample misses the shape");
+            estimate += 1.0 / pdf;
+        }
+        let estimate = estimate / trials as f64;
+        assert!(
+            (estimate / solid_angle - 1.0).abs() < 0.03,
+            "{name}: {estimate} vs {solid_angle}"
+        );
+    }
+
+    #[test]
+    fn test_light_sampling() {
+        let origin = Point3::origin();
+        let sphere = Sphere::new(Point3::new(0.0, 0.0, -2.0), 1.0, gray());
+        check_sampling("sphere", &sphere, origin);
+        let inside = Sphere::new(Point3::new(0.0, 0.5, 0.0), 1.0, gray());
+        check_sampling("sphere from inside", &inside, origin);
+
+        let quad = || {
+            Quad::new(
+                Point3::new(-1.0, -0.5, -1.5),
+                Vec3::new(2.0, 0.0, 0.0),
+                Vec3::new(0.0, 1.0, 1.0),
+                gray(),
+            )
+        };
+        check_sampling("quad", &quad(), origin);
+        let triangle = Triangle::new(
+            Point3::new(-1.0, 0.0, -1.0),
+            Point3::new(1.0, 0.0, -1.0),
+            Point3::new(0.0, 2.0, -1.5),
+            gray(),
+        );
+        check_sampling("triangle", &triangle, origin);
+        let disk = Disk::new(
+            Point3::new(0.5, 1.0, -1.0),
+            Vec3::new(0.0, -1.0, 1.0),
+            1.0,
+            gray(),
+        );
+        check_sampling("disk", &disk, origin);
+
+        // Uneven scaling changes solid angles, which the transform accounts for.
+        let placed = Transform::new(
+            quad(),
+            Mat4::translation(Vec3::new(0.0, 0.5, 0.0)) * Mat4::scaling(Vec3::new(0.5, 2.0, 1.0)),
+        );
+        check_sampling("transformed quad", &placed, origin);
+
+        let mut lights = HittableList::new();
+        lights.add(sphere);
+        lights.add(disk);
+        check_sampling("list", &lights, origin);
+    }
 }
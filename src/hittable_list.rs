@@ -1,27 +1,55 @@
 use crate::{
+    aabb::Aabb,
     hittable::{HitRecord, Hittable},
     inretval::Interval,
     ray::Ray,
+    util::Rng,
+    vec3::{Point3, Vec3},
 };
 
 pub struct HittableList<'a> {
     objects: Vec<Box<dyn Hittable + 'a>>,
+    bbox: Aabb,
 }
 
 impl<'a> HittableList<'a> {
     pub fn new() -> HittableList<'a> {
         HittableList {
             objects: Vec::new(),
+            bbox: Aabb::EMPTY,
         }
     }
 
     pub fn add(&mut self, object: impl Hittable + 'a) {
-        self.objects.push(Box::new(object))
+        self.add_boxed(Box::new(object))
+    }
+
+    pub fn add_boxed(&mut self, object: Box<dyn Hittable + 'a>) {
+        self.bbox = Aabb::surrounding(self.bbox, object.bounding_box());
+        self.objects.push(object)
+    }
+
+    pub fn len(&self) -> usize {
+        self.objects.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
+
+    pub fn into_objects(self) -> Vec<Box<dyn Hittable + 'a>> {
+        self.objects
+    }
+}
+
+impl<'a> Default for HittableList<'a> {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
 impl<'a> Hittable for HittableList<'a> {
-    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord> {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
         let mut rec = None;
         let mut closets_so_far = ray_t.max;
         for object in self.objects.iter() {
@@ -32,4 +60,39 @@ impl<'a> Hittable for HittableList<'a> {
         }
         rec
     }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    fn transmittance(&self, r: &Ray, ray_t: Interval, rng: &mut Rng) -> f64 {
+        let mut transmittance = 1.0;
+        for object in &self.objects {
+            transmittance *= object.transmittance(r, ray_t, rng);
+            if transmittance == 0.0 {
+                break;
+            }
+        }
+        transmittance
+    }
+
+    /// Picks an object uniformly, so the density is the average of theirs.
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f64 {
+        if self.objects.is_empty() {
+            return 0.0;
+        }
+        let sum: f64 = self
+            .objects
+            .iter()
+            .map(|object| object.pdf_value(origin, direction))
+            .sum();
+        sum / self.objects.len() as f64
+    }
+
+    fn random(&self, origin: Point3, rng: &mut Rng) -> Vec3 {
+        if self.objects.is_empty() {
+            return Vec3::new(1.0, 0.0, 0.0);
+        }
+        self.objects[rng.usize(..self.objects.len())].random(origin, rng)
+    }
 }
@@ -1,13 +1,22 @@
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Interval {
     pub min: f64,
     pub max: f64,
 }
 
 impl Interval {
+    pub const EMPTY: Interval = Interval::new(f64::INFINITY, f64::NEG_INFINITY);
+    pub const UNIVERSE: Interval = Interval::new(f64::NEG_INFINITY, f64::INFINITY);
+
     pub const fn new(min: f64, max: f64) -> Self {
         Interval { min, max }
     }
 
+    /// Smallest interval containing both `a` and `b`.
+    pub fn union(a: Interval, b: Interval) -> Self {
+        Interval::new(a.min.min(b.min), a.max.max(b.max))
+    }
+
     pub fn size(&self) -> f64 {
         self.max - self.min
     }
@@ -27,4 +36,8 @@ impl Interval {
             x
         }
     }
+    pub fn expand(&self, delta: f64) -> Interval {
+        let padding = delta / 2.0;
+        Interval::new(self.min - padding, self.max + padding)
+    }
 }
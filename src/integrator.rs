@@ -0,0 +1,564 @@
+//! Rendering algorithms: how the light arriving along a camera ray is
+//! estimated.
+
+use crate::{
+    color::Color,
+    environment::Environment,
+    hittable::{HitRecord, Hittable},
+    hittable_list::HittableList,
+    inretval::Interval,
+    pdf::{CosinePdf, Heuristic, HittablePdf, Pdf},
+    ray::Ray,
+    util::{Rng, random_f64},
+    vec3::Vec3,
+};
+
+/// Names accepted by [`for_name`].
+pub const NAMES: [&str; 4] = ["path", "naive", "direct", "ao"];
+
+/// Looks up an integrator by name, ignoring case.
+pub fn for_name(name: &str) -> Option<Box<dyn Integrator>> {
+    let integrator: Box<dyn Integrator> = match name.to_ascii_lowercase().as_str() {
+        "path" => Box::new(PathTracer),
+        "naive" => Box::new(NaivePathTracer),
+        "direct" => Box::new(DirectLighting),
+        "ao" => Box::new(AmbientOcclusion::default()),
+        _ => return None,
+    };
+    Some(integrator)
+}
+
+/// What an integrator traces rays through, and the settings it shares with
+/// the camera.
+pub struct Context<'a> {
+    pub world: &'a dyn Hittable,
+    /// Emitters in `world` to aim shadow rays at.
+    pub lights: &'a HittableList<'a>,
+    /// Light from rays that leave the scene.
+    pub background: &'a Environment,
+    /// Most surfaces a path may meet, counting the first.
+    pub max_depth: usize,
+    /// Surfaces a path meets before Russian roulette may end it.
+    pub roulette_depth: usize,
+    /// How light found both by sampling lights and by bouncing is shared
+    /// between the two.
+    pub heuristic: Heuristic,
+}
+
+impl Context<'_> {
+    fn trace(&self, r: &Ray) -> Option<HitRecord<'_>> {
+        self.world.hit(r, Interval::new(0.001, f64::INFINITY))
+    }
+}
+
+pub trait Integrator: Send + Sync {
+    /// Light arriving back along the camera ray `r`. The camera has already
+    /// found its first hit, `rec`, which is `None` if the ray escapes.
+    fn radiance(&self, r: &Ray, rec: Option<&HitRecord>, ctx: &Context, rng: &mut Rng) -> Color;
+}
+
+/// Path tracing that only finds light by bouncing into it. Simple and
+/// unbiased, but noisy wherever lights are small.
+pub struct NaivePathTracer;
+
+impl Integrator for NaivePathTracer {
+    fn radiance(&self, r: &Ray, rec: Option<&HitRecord>, ctx: &Context, rng: &mut Rng) -> Color {
+        let Some(mut rec) = rec else {
+            return ctx.background.value(r.direction);
+        };
+        let mut ray = Ray::new(r.origin, r.direction);
+        let mut radiance = Color::new(0.0, 0.0, 0.0);
+        let mut throughput = Color::new(1.0, 1.0, 1.0);
+        let mut next;
+        for depth in 1.. {
+            radiance += throughput * rec.mat.emitted(rec.u, rec.v, rec.p);
+            let Some((attenuation, scattered)) = rec.mat.scatter(&ray, rec, rng) else {
+                break;
+            };
+            if depth >= ctx.max_depth {
+                break;
+            }
+            throughput = throughput * attenuation;
+            if !survives(&mut throughput, depth, ctx, rng) {
+                break;
+            }
+            next = match ctx.trace(&scattered) {
+                Some(next) => next,
+                None => {
+                    radiance += throughput * ctx.background.value(scattered.direction);
+                    break;
+                }
+            };
+            rec = &next;
+            ray = scattered;
+        }
+        radiance
+    }
+}
+
+/// Path tracing that also aims a shadow ray at the lights from every
+/// diffuse or glossy bounce, sharing light either way finds with multiple
+/// importance sampling. Far less noisy than bouncing alone.
+pub struct PathTracer;
+
+impl Integrator for PathTracer {
+    fn radiance(&self, r: &Ray, rec: Option<&HitRecord>, ctx: &Context, rng: &mut Rng) -> Color {
+        let Some(mut rec) = rec else {
+            return ctx.background.value(r.direction);
+        };
+        let mut ray = Ray::new(r.origin, r.direction);
+        let mut radiance = rec.mat.emitted(rec.u, rec.v, rec.p);
+        let mut throughput = Color::new(1.0, 1.0, 1.0);
+        let mut next;
+        for depth in 1.. {
+            let Some((attenuation, scattered)) = rec.mat.scatter(&ray, rec, rng) else {
+                break;
+            };
+            if depth >= ctx.max_depth {
+                break;
+            }
+            // Mirrors and glass can only find lights by bouncing into them.
+            let pdf = rec.mat.scattering_pdf(&ray, rec, &scattered);
+            let sampled = (pdf > 0.0 && !ctx.lights.is_empty()).then_some(pdf);
+            if sampled.is_some() {
+                radiance += throughput * attenuation * direct_light(&ray, rec, ctx, rng);
+            }
+            throughput = throughput * attenuation;
+            if !survives(&mut throughput, depth, ctx, rng) {
+                break;
+            }
+            next = match ctx.trace(&scattered) {
+                Some(next) => next,
+                None => {
+                    radiance += throughput * ctx.background.value(scattered.direction);
+                    break;
+                }
+            };
+            radiance += throughput * emission(&scattered, &next, ctx, sampled);
+            rec = &next;
+            ray = scattered;
+        }
+        radiance
+    }
+}
+
+/// Light that reaches the first diffuse or glossy surface straight from the
+/// lights or the background, seen directly or through mirrors and glass. No
+/// light bounces between surfaces, which makes shadows stand out.
+pub struct DirectLighting;
+
+impl Integrator for DirectLighting {
+    fn radiance(&self, r: &Ray, rec: Option<&HitRecord>, ctx: &Context, rng: &mut Rng) -> Color {
+        let Some(mut rec) = rec else {
+            return ctx.background.value(r.direction);
+        };
+        let mut ray = Ray::new(r.origin, r.direction);
+        let mut radiance = Color::new(0.0, 0.0, 0.0);
+        let mut throughput = Color::new(1.0, 1.0, 1.0);
+        let mut next;
+        for depth in 1.. {
+            radiance += throughput * rec.mat.emitted(rec.u, rec.v, rec.p);
+            let Some((attenuation, scattered)) = rec.mat.scatter(&ray, rec, rng) else {
+                break;
+            };
+            if depth >= ctx.max_depth {
+                break;
+            }
+            let pdf = rec.mat.scattering_pdf(&ray, rec, &scattered);
+            if pdf > 0.0 {
+                // Both the shadow ray and the bounce can find the light; the
+                // bounce ends wherever it lands.
+                let direct = direct_light(&ray, rec, ctx, rng);
+                let bounced = match ctx.trace(&scattered) {
+                    Some(next) => emission_along(&scattered, &next, ctx, pdf),
+                    None => ctx.background.value(scattered.direction),
+                };
+                radiance += throughput * attenuation * (direct + bounced);
+                break;
+            }
+            // Follow mirrors and glass to the surface they show.
+            throughput = throughput * attenuation;
+            if !survives(&mut throughput, depth, ctx, rng) {
+                break;
+            }
+            next = match ctx.trace(&scattered) {
+                Some(next) => next,
+                None => {
+                    radiance += throughput * ctx.background.value(scattered.direction);
+                    break;
+                }
+            };
+            rec = &next;
+            ray = scattered;
+        }
+        radiance
+    }
+}
+
+/// How open the sky is above each point: white where nothing lies within
+/// `distance`, darkening in creases and corners. Ignores materials and
+/// lights, which makes it a quick check of the geometry.
+#[derive(Default)]
+pub struct AmbientOcclusion {
+    /// How far away geometry still occludes; a tenth of the way across the
+    /// scene when not set.
+    pub distance: Option<f64>,
+}
+
+impl Integrator for AmbientOcclusion {
+    fn radiance(&self, r: &Ray, rec: Option<&HitRecord>, ctx: &Context, rng: &mut Rng) -> Color {
+        let Some(rec) = rec else {
+            return ctx.background.value(r.direction);
+        };
+        let distance = self.distance.unwrap_or_else(|| {
+            let bbox = ctx.world.bounding_box();
+            let diagonal = Vec3::new(bbox.x.size(), bbox.y.size(), bbox.z.size());
+            diagonal.length() / 10.0
+        });
+        // Cosine-weighted directions make the average visibility the
+        // fraction of light an overcast sky would give.
+        let direction = CosinePdf::new(rec.normal).generate(rng).unit_vector();
+        let probe = Ray::new(rec.p, direction);
+        let open = ctx
+            .world
+            .transmittance(&probe, Interval::new(0.001, distance), rng);
+        Color::new(open, open, open)
+    }
+}
+
+/// Russian roulette: once a path has met `ctx.roulette_depth` surfaces,
+/// ends it with a chance that grows as its `throughput` dims, and brightens
+/// the paths that go on to make up for the ones ended. Long paths cost
+/// little and the average is unchanged. Returns whether the path goes on.
+fn survives(throughput: &mut Color, depth: usize, ctx: &Context, rng: &mut Rng) -> bool {
+    if depth < ctx.roulette_depth {
+        return true;
+    }
+    let p = throughput.r.max(throughput.g).max(throughput.b).min(1.0);
+    if random_f64(rng) >= p {
+        return false;
+    }
+    *throughput = (1.0 / p) * *throughput;
+    true
+}
+
+/// Light `rec` gives off back along `r`. `scattering_pdf` is the density
+/// with which the bounce that sent `r` picked it, when that bounce also
+/// sampled the lights directly; the two then share the credit for it.
+fn emission(r: &Ray, rec: &HitRecord, ctx: &Context, scattering_pdf: Option<f64>) -> Color {
+    match scattering_pdf {
+        Some(pdf) => emission_along(r, rec, ctx, pdf),
+        None => rec.mat.emitted(rec.u, rec.v, rec.p),
+    }
+}
+
+/// The bounce's share of the light `rec` gives off back along `r`, for a
+/// bounce that picked `r` with density `scattering_pdf` and also sampled
+/// the lights directly.
+fn emission_along(r: &Ray, rec: &HitRecord, ctx: &Context, scattering_pdf: f64) -> Color {
+    let emitted = rec.mat.emitted(rec.u, rec.v, rec.p);
+    if !is_on_light(r, rec, ctx.lights) {
+        return emitted;
+    }
+    let light_pdf = ctx.lights.pdf_value(r.origin, r.direction);
+    ctx.heuristic.bounce_weight(scattering_pdf, light_pdf) * emitted
+}
+
+/// Light reaching `rec` straight from a random point on a random light,
+/// dimmed by whatever lies in between and weighted against finding it by
+/// bouncing, per unit of the material's attenuation.
+fn direct_light(r: &Ray, rec: &HitRecord, ctx: &Context, rng: &mut Rng) -> Color {
+    let black = Color::new(0.0, 0.0, 0.0);
+    let light_pdf = HittablePdf::new(ctx.lights, rec.p);
+    let direction = light_pdf.generate(rng).unit_vector();
+    let pdf = light_pdf.value(direction);
+    let shadow = Ray::new(rec.p, direction);
+    let scattering_pdf = rec.mat.scattering_pdf(r, rec, &shadow);
+    if pdf <= 0.0 || scattering_pdf <= 0.0 {
+        return black;
+    }
+    let Some(light) = ctx.lights.hit(&shadow, Interval::new(0.001, f64::INFINITY)) else {
+        return black;
+    };
+    // Stop short of the light so it does not shadow itself.
+    let visibility = ctx
+        .world
+        .transmittance(&shadow, Interval::new(0.001, light.t - 0.001), rng);
+    if visibility == 0.0 {
+        return black;
+    }
+    let weight = ctx.heuristic.light_weight(pdf, scattering_pdf);
+    (visibility * weight * scattering_pdf / pdf) * light.mat.emitted(light.u, light.v, light.p)
+}
+
+/// Whether the surface `rec` that `r` hit is one of `lights`.
+fn is_on_light(r: &Ray, rec: &HitRecord, lights: &HittableList) -> bool {
+    rec.mat.is_emissive()
+        && lights
+            .hit(r, Interval::new(0.001, f64::INFINITY))
+            .is_some_and(|light| light.t == rec.t)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{
+        camera::Camera,
+        material::{DiffuseLight, Lambertian, Material, Metal},
+        quad::Quad,
+        sphere::Sphere,
+        test_util::gray,
+        vec3::Point3,
+    };
+
+    struct Stats {
+        mean: f64,
+        /// Average difference between neighboring pixels.
+        noise: f64,
+        /// The brightest pixel, where fireflies show.
+        peak: f64,
+    }
+
+    fn render(
+        world: &HittableList,
+        lights: &HittableList,
+        integrator: impl Integrator + 'static,
+        heuristic: Heuristic,
+        max_depth: usize,
+        seed: u64,
+    ) -> Stats {
+        let mut cam = Camera::new();
+        cam.image_width = 24;
+        cam.aspect_ratio = 4.0 / 3.0;
+        cam.samples_per_pixel = 64;
+        cam.max_depth = max_depth;
+        cam.lookfrom = Point3::new(0.0, 0.5, 3.0);
+        cam.lookat = Point3::origin();
+        cam.vfov = 30.0;
+        cam.tile_size = 5;
+        cam.threads = 1;
+        cam.seed = seed;
+        cam.heuristic = heuristic;
+        cam.integrator = Box::new(integrator);
+        let image = cam.render(world, lights);
+        let luma: Vec<f64> = image.pixels().iter().map(|p| p.r + p.g + p.b).collect();
+        let n = luma.len() as f64;
+        Stats {
+            mean: luma.iter().sum::<f64>() / n,
+            noise: luma.windows(2).map(|w| (w[1] - w[0]).abs()).sum::<f64>() / n,
+            peak: luma.iter().fold(0.0, |a, &b| a.max(b)),
+        }
+    }
+
+    /// A small bright lamp, out of view, over a floor of `floor`.
+    fn lamp_scene(
+        floor: impl Material + 'static,
+    ) -> (HittableList<'static>, HittableList<'static>) {
+        let light = Arc::new(Sphere::new(
+            Point3::new(0.0, 1.5, 0.0),
+            0.2,
+            DiffuseLight::new(Color::new(40.0, 40.0, 40.0)),
+        ));
+        let mut world = HittableList::new();
+        world.add(Sphere::new(Point3::new(0.0, -100.0, 0.0), 100.0, floor));
+        world.add(light.clone());
+        let mut lights = HittableList::new();
+        lights.add(light);
+        (world, lights)
+    }
+
+    #[test]
+    fn test_shadow_rays_cut_noise() {
+        // The same brightness on average, far less noise.
+        let (world, lights) = lamp_scene(gray());
+        let naive = render(&world, &lights, NaivePathTracer, Heuristic::Power, 3, 13);
+        let path = render(&world, &lights, PathTracer, Heuristic::Power, 3, 13);
+        assert!(
+            (path.mean / naive.mean - 1.0).abs() < 0.1,
+            "{} vs {}",
+            path.mean,
+            naive.mean
+        );
+        assert!(path.noise < naive.noise / 5.0);
+    }
+
+    #[test]
+    fn test_mis_on_glossy_metal() {
+        // A big lamp over slightly fuzzy metal: light samples rarely match
+        // the narrow reflections, bounces find the lamp easily, and the
+        // heuristics lean on the bounces without changing the average.
+        let light = Arc::new(Quad::new(
+            Point3::new(-2.0, 2.0, -2.0),
+            Vec3::new(4.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, 4.0),
+            DiffuseLight::new(Color::new(4.0, 4.0, 4.0)),
+        ));
+        let mut world = HittableList::new();
+        let floor = Metal::new(Color::new(0.9, 0.9, 0.9), 0.05);
+        world.add(Sphere::new(Point3::new(0.0, -100.0, 0.0), 100.0, floor));
+        world.add(light.clone());
+        let mut lights = HittableList::new();
+        lights.add(light);
+
+        let naive = render(&world, &lights, NaivePathTracer, Heuristic::Power, 3, 21);
+        for heuristic in [Heuristic::Balance, Heuristic::Power] {
+            let path = render(&world, &lights, PathTracer, heuristic, 3, 21);
+            assert!(
+                (path.mean / naive.mean - 1.0).abs() < 0.02,
+                "{heuristic:?}: {} vs {}",
+                path.mean,
+                naive.mean
+            );
+            assert!(path.noise < 1.2 * naive.noise, "{heuristic:?}");
+        }
+
+        // Shadow rays alone occasionally land in a reflection and count it
+        // hugely; sharing with the bounces tames those fireflies.
+        let brightest = |heuristic| {
+            (1..5)
+                .map(|seed| render(&world, &lights, PathTracer, heuristic, 3, seed).peak)
+                .fold(0.0, f64::max)
+        };
+        let (off, power) = (brightest(Heuristic::Off), brightest(Heuristic::Power));
+        assert!(power < off / 2.0, "{power} vs {off}");
+    }
+
+    #[test]
+    fn test_direct_lighting_is_one_bounce() {
+        // Without light bouncing between surfaces, the floor gets what a
+        // path tracer limited to one bounce would give it.
+        let (world, lights) = lamp_scene(gray());
+        let direct = render(&world, &lights, DirectLighting, Heuristic::Power, 10, 3);
+        let path = render(&world, &lights, PathTracer, Heuristic::Power, 2, 3);
+        assert!(
+            (direct.mean / path.mean - 1.0).abs() < 0.05,
+            "{} vs {}",
+            direct.mean,
+            path.mean
+        );
+    }
+
+    /// Light reaching the origin from straight below inside a closed room
+    /// of `wall` lit by a small lamp, averaged over `trials` paths.
+    fn room_average(
+        integrator: &dyn Integrator,
+        wall: impl Material + 'static,
+        max_depth: usize,
+        roulette_depth: usize,
+        trials: usize,
+    ) -> f64 {
+        let light = Arc::new(Sphere::new(
+            Point3::new(0.0, 2.0, 0.0),
+            0.5,
+            DiffuseLight::new(Color::new(4.0, 4.0, 4.0)),
+        ));
+        let mut world = HittableList::new();
+        world.add(Sphere::new(Point3::origin(), 3.0, wall));
+        world.add(light.clone());
+        let mut lights = HittableList::new();
+        lights.add(light);
+        let ctx = Context {
+            world: &world,
+            lights: &lights,
+            background: &Environment::Solid(Color::new(0.0, 0.0, 0.0)),
+            max_depth,
+            roulette_depth,
+            heuristic: Heuristic::Power,
+        };
+        let r = Ray::new(Point3::origin(), Vec3::new(0.0, -1.0, 0.0));
+        let rec = ctx.trace(&r).unwrap();
+        let mut rng = Rng::with_seed(8);
+        let total: f64 = (0..trials)
+            .map(|_| integrator.radiance(&r, Some(&rec), &ctx, &mut rng).r)
+            .sum();
+        total / trials as f64
+    }
+
+    #[test]
+    fn test_russian_roulette_is_unbiased() {
+        // Most of the light on the walls has bounced many times; ending
+        // paths early by chance must not lose any of it.
+        let wall = || Lambertian::new(Color::new(0.7, 0.7, 0.7));
+        let integrators: [(&str, &dyn Integrator, usize); 2] = [
+            ("path", &PathTracer, 20_000),
+            ("naive", &NaivePathTracer, 100_000),
+        ];
+        for (name, integrator, trials) in integrators {
+            let full = room_average(integrator, wall(), 50, usize::MAX, trials);
+            let roulette = room_average(integrator, wall(), 50, 2, trials);
+            assert!(
+                (roulette / full - 1.0).abs() < 0.03,
+                "{name}: {roulette} vs {full}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_long_paths() {
+        // Inside a perfect mirror a path along a diameter bounces back and
+        // forth without ever dimming, all the way to `max_depth`, and the
+        // stack does not grow with it.
+        let mut world = HittableList::new();
+        let mirror = Metal::new(Color::new(1.0, 1.0, 1.0), 0.0);
+        world.add(Sphere::new(Point3::origin(), 3.0, mirror));
+        let lights = HittableList::new();
+        let ctx = Context {
+            world: &world,
+            lights: &lights,
+            background: &Environment::Solid(Color::new(1.0, 1.0, 1.0)),
+            max_depth: 200_000,
+            roulette_depth: 3,
+            heuristic: Heuristic::Power,
+        };
+        let r = Ray::new(Point3::origin(), Vec3::new(1.0, 0.0, 0.0));
+        let rec = ctx.trace(&r).unwrap();
+        let mut rng = Rng::with_seed(1);
+        let radiance = PathTracer.radiance(&r, Some(&rec), &ctx, &mut rng);
+        assert_eq!(radiance, Color::new(0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_ambient_occlusion() {
+        // A ball resting on a floor shades the floor beside it and nowhere
+        // else.
+        let mut world = HittableList::new();
+        world.add(Sphere::new(Point3::new(0.0, -100.0, 0.0), 100.0, gray()));
+        world.add(Sphere::new(Point3::new(0.0, 0.5, 0.0), 0.5, gray()));
+        let lights = HittableList::new();
+        let ctx = Context {
+            world: &world,
+            lights: &lights,
+            background: &Environment::Solid(Color::new(0.0, 0.0, 0.0)),
+            max_depth: 1,
+            roulette_depth: 1,
+            heuristic: Heuristic::Power,
+        };
+        let ao = AmbientOcclusion {
+            distance: Some(1.0),
+        };
+        let mut rng = Rng::with_seed(5);
+        let mut average = |x: f64| {
+            let r = Ray::new(Point3::new(x, 1.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+            let rec = ctx.trace(&r).unwrap();
+            let trials = 2000;
+            let total: f64 = (0..trials)
+                .map(|_| ao.radiance(&r, Some(&rec), &ctx, &mut rng).r)
+                .sum();
+            total / trials as f64
+        };
+        assert_eq!(average(3.0), 1.0);
+        let crease = average(0.55);
+        assert!(crease > 0.2 && crease < 0.9, "{crease}");
+    }
+
+    #[test]
+    fn test_for_name() {
+        for name in NAMES {
+            assert!(for_name(name).is_some(), "{name}");
+        }
+        assert!(for_name("AO").is_some());
+        assert!(for_name("bdpt").is_none());
+    }
+}
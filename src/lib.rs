@@ -0,0 +1,36 @@
+pub mod aabb;
+pub mod builtin;
+pub mod bvh;
+pub mod camera;
+pub mod color;
+pub mod deflate;
+pub mod density;
+pub mod disk;
+pub mod environment;
+pub mod exr;
+pub mod framebuffer;
+pub mod hdr;
+pub mod hittable;
+pub mod hittable_list;
+pub mod inretval;
+pub mod integrator;
+pub mod material;
+pub mod medium;
+pub mod mesh;
+pub mod obj;
+pub mod output;
+pub mod pdf;
+pub mod perlin;
+pub mod png;
+pub mod ppm;
+pub mod quad;
+pub mod ray;
+pub mod scene;
+pub mod sphere;
+#[cfg(test)]
+mod test_util;
+pub mod texture;
+pub mod transform;
+pub mod triangle;
+pub mod util;
+pub mod vec3;
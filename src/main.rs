@@ -1,131 +1,117 @@
-mod camera;
-mod color;
-mod hittable;
-mod hittable_list;
-mod inretval;
-mod material;
-mod ray;
-mod sphere;
-mod util;
-mod vec3;
-
-use vec3::{Point3, Vec3};
-
-use crate::{
-    camera::Camera,
-    color::Color,
-    hittable_list::HittableList,
-    material::{Dialectric, Lambertian, Metal},
-    sphere::Sphere, util::{random_f64, random_f64_range},
+mod cli;
+
+use std::{
+    env,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+    process,
 };
 
-fn oldmain() {
-    // World
-    let mut world = HittableList::new();
-
-    let material_groud = Lambertian::new(Color::new(0.8, 0.8, 0.0));
-    let material_center = Lambertian::new(Color::new(0.1, 0.2, 0.5));
-    let material_left = Dialectric::new(1.5);
-    let material_bubble = Dialectric::new(1.0 / 1.5);
-    // let material_left = Metal::new(Color::new(0.8, 0.6, 0.2),0.3);
-    let material_right = Metal::new(Color::new(0.8, 0.6, 0.2), 1.0);
-
-    world.add(Sphere::new(
-        Point3::new(0.0, -100.5, -1.0),
-        100.0,
-        material_groud,
-    ));
-    world.add(Sphere::new(
-        Point3::new(0.0, 0.0, -1.2),
-        0.5,
-        material_center,
-    ));
-    world.add(Sphere::new(
-        Point3::new(-1.0, 0.0, -1.0),
-        0.5,
-        material_left,
-    ));
-    world.add(Sphere::new(
-        Point3::new(-1.0, 0.0, -1.0),
-        0.4,
-        material_bubble,
-    ));
-    world.add(Sphere::new(
-        Point3::new(1.0, 0.0, -1.0),
-        0.5,
-        material_right,
-    ));
-
-    let mut cam = Camera::new();
-    cam.aspect_ratio = 16.0 / 9.0;
-    cam.image_width = 400;
-    cam.samples_per_pixel = 100;
-    cam.max_depth = 50;
-    cam.vfov = 20.0;
-
-    cam.lookfrom = Point3::new(-2.0, 2.0, 1.0);
-    cam.lookat = Point3::new(0.0, 0.0, -1.0);
-    cam.vup = Vec3::new(0.0, 1.0, 0.0);
-
-    cam.defocus_angle = 10.0;
-    cam.focus_dist = 3.4;
-
-    cam.render(&world);
-}
-
-
-
+use cli::{Command, RenderArgs, SceneSource};
+use ray_tracing::{
+    builtin,
+    bvh::BvhNode,
+    framebuffer::Framebuffer,
+    integrator,
+    output::{self, ImageWriter},
+    scene::{Scene, SceneError},
+};
 
+const DEFAULT_SEED: u64 = 42;
 
 fn main() {
-    let mut world = HittableList::new();
-    let ground_material = Lambertian::new(Color::new(0.5, 0.5, 0.5));
-    world.add(Sphere::new(Point3::new(0.0, -1000.0, 0.0), 1000.0, ground_material));
-    for a in -11..11 {
-        for b in -11..11{
-            let choose_mat = random_f64();
-            let center = Point3::new(a as f64 + 0.9 * random_f64() ,0.2, b as f64 + 0.9 * random_f64(),);
-            if (center - Point3::new(4.0, 0.2, 0.0)).length() > 0.9 {
-                if choose_mat < 0.8 {
-                    let albedo = Color::random() * Color::random();
-                    let sphere_material = Lambertian::new(albedo);
-                    world.add(Sphere::new(center, 0.2, sphere_material));
-                } else if choose_mat < 0.95 {
-                    let albedo = Color::random_range(0.5, 1.0);
-                    let fuzz = random_f64_range(0.0, 0.5);
-                    let sphere_material = Metal::new(albedo, fuzz);
-                    world.add(Sphere::new(center, 0.2, sphere_material));
-                } else {
-                    let sphere_material = Dialectric::new(1.5);
-                    world.add(Sphere::new(center, 0.2, sphere_material));
-
-                }
-                
+    let command = cli::parse(env::args().skip(1)).unwrap_or_else(|err| {
+        eprintln!("error: {err}\n\n{}", cli::usage());
+        process::exit(2);
+    });
+    let result = match command {
+        Command::Help => {
+            println!("{}", cli::usage());
+            Ok(())
+        }
+        Command::ListScenes => {
+            for name in builtin::NAMES {
+                println!("{name}");
             }
+            Ok(())
         }
+        Command::Render(args) => render(args),
+    };
+    if let Err(err) = result {
+        eprintln!("error: {err}");
+        process::exit(1);
     }
-    let material1 = Dialectric::new(1.5);
-    world.add(Sphere::new(Point3::new(0.0, 1.0, 0.0), 1.0, material1));
-
-    let material2 = Lambertian::new(Color::new(0.4, 0.2, 0.1));
-    world.add(Sphere::new(Point3::new(-4.0, 1.0, 0.0), 1.0, material2));
-
-    let material3 = Metal::new(Color::new(0.7, 0.6, 0.5), 0.0);
-    world.add(Sphere::new(Point3::new(4.0, 1.0, 0.0), 1.0, material3));
+}
 
-    let mut cam = Camera::new();
-    cam.aspect_ratio = 16.0 / 9.0;
-    cam.image_width = 400;
-    cam.samples_per_pixel = 100;
-    cam.max_depth = 20;
-    
-    cam.vfov = 20.0;
-    cam.lookfrom = Point3::new(13.0, 2.0, 3.0);
-    cam.lookat = Point3::new(0.0, 0.0, 0.0);
-    cam.vup = Vec3::new(0.0, 1.0, 0.0);
+fn render(args: RenderArgs) -> Result<(), String> {
+    let scene = match &args.scene {
+        SceneSource::File(path) => {
+            // I/O errors name the file that failed, which may be one the
+            // scene pulls in rather than the scene itself.
+            Scene::load(path).map_err(|err| match err {
+                SceneError::Io { .. } => err.to_string(),
+                SceneError::Parse { .. } => format!("{}: {err}", path.display()),
+            })?
+        }
+        SceneSource::Builtin(name) => builtin::load(name, args.seed.unwrap_or(DEFAULT_SEED))
+            .ok_or_else(|| format!("unknown built-in scene `{name}`"))?,
+    };
+    let writer = match (&args.format, &args.output) {
+        (Some(name), _) => output::writer_for_name(name),
+        (None, Some(path)) => output::writer_for_path(path),
+        (None, None) => output::writer_for_name("ppm"),
+    }
+    .ok_or_else(|| {
+        let path = args.output.as_deref().unwrap_or(Path::new("-"));
+        format!(
+            "cannot tell the output format of {}, pass --format",
+            path.display()
+        )
+    })?;
+
+    let Scene {
+        world,
+        lights,
+        mut camera,
+    } = scene;
+    if let Some(width) = args.width {
+        camera.image_width = width;
+    }
+    if let Some(aspect) = args.aspect {
+        camera.aspect_ratio = aspect;
+    }
+    if let Some(spp) = args.spp {
+        camera.samples_per_pixel = spp;
+    }
+    if let Some(depth) = args.depth {
+        camera.max_depth = depth;
+    }
+    if let Some(name) = &args.integrator {
+        camera.integrator = integrator::for_name(name).expect("integrator name was checked");
+    }
+    if let Some(seed) = args.seed {
+        camera.seed = seed;
+    }
+    if let Some(threads) = args.threads {
+        camera.threads = threads;
+    }
 
-    cam.defocus_angle = 0.6;
-    cam.focus_dist = 10.0;
+    let world = BvhNode::new(world);
+    let image = camera.render(&world, &lights);
+
+    let written = match &args.output {
+        Some(path) => File::create(path).and_then(|file| write_image(file, &image, &*writer)),
+        None => write_image(io::stdout().lock(), &image, &*writer),
+    };
+    written.map_err(|err| match &args.output {
+        Some(path) => format!("{}: {err}", path.display()),
+        None => err.to_string(),
+    })
+}
 
-    cam.render(&world);
-}
\ No newline at end of file
+fn write_image(out: impl Write, image: &Framebuffer, writer: &dyn ImageWriter) -> io::Result<()> {
+    let mut out = BufWriter::new(out);
+    writer.write(&mut out, image)?;
+    out.flush()
+}
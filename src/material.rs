@@ -1,57 +1,175 @@
-use crate::{color::Color, hittable::HitRecord, ray::Ray, util::random_f64, vec3::Vec3};
+use std::{f64::consts::PI, sync::Arc};
 
-pub trait Material {
-    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)>;
+use crate::{
+    color::Color,
+    hittable::HitRecord,
+    pdf::{CosinePdf, Pdf, SpherePdf},
+    ray::Ray,
+    texture::{SolidColor, Texture},
+    util::{Rng, random_f64},
+    vec3::{Point3, Vec3},
+};
+
+pub trait Material: Send + Sync {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, rng: &mut Rng) -> Option<(Color, Ray)>;
+
+    /// Light given off by the surface at the hit point; black for most materials.
+    fn emitted(&self, _u: f64, _v: f64, _p: Point3) -> Color {
+        Color::new(0.0, 0.0, 0.0)
+    }
+
+    /// Density per solid angle with which `scatter` sends light arriving
+    /// along `r_in` off towards `scattered`.
+    ///
+    /// Diffuse materials also scatter light in proportion to it, so
+    /// `attenuation * scattering_pdf` weighs light arriving from any chosen
+    /// direction. Zero for mirror-like materials, which no sampled light
+    /// direction can match.
+    fn scattering_pdf(&self, _r_in: &Ray, _rec: &HitRecord, _scattered: &Ray) -> f64 {
+        0.0
+    }
+
+    /// Whether objects made of this material give off light, and so are
+    /// worth aiming shadow rays at.
+    fn is_emissive(&self) -> bool {
+        false
+    }
+}
+
+/// Lets one material be shared by many objects.
+impl<M: Material + ?Sized> Material for Arc<M> {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, rng: &mut Rng) -> Option<(Color, Ray)> {
+        (**self).scatter(r_in, rec, rng)
+    }
+
+    fn emitted(&self, u: f64, v: f64, p: Point3) -> Color {
+        (**self).emitted(u, v, p)
+    }
+
+    fn scattering_pdf(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+        (**self).scattering_pdf(r_in, rec, scattered)
+    }
+
+    fn is_emissive(&self) -> bool {
+        (**self).is_emissive()
+    }
 }
 
 pub struct Lambertian {
-    albedo: Color,
+    tex: Arc<dyn Texture>,
 }
 
 impl Lambertian {
     pub fn new(albedo: Color) -> Self {
-        Self { albedo }
+        Self::from_texture(Arc::new(SolidColor::new(albedo)))
+    }
+
+    pub fn from_texture(tex: Arc<dyn Texture>) -> Self {
+        Self { tex }
     }
 }
 
 impl Material for Lambertian {
-    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
-        let mut scatter_direction = rec.normal + Vec3::random_unit_vector();
-        if scatter_direction.near_zero() {
-            scatter_direction = rec.normal;
-        }
-        let scattered = Ray::new(rec.p, scatter_direction);
-        let attentuation = self.albedo;
+    fn scatter(&self, _r_in: &Ray, rec: &HitRecord, rng: &mut Rng) -> Option<(Color, Ray)> {
+        let scattered = Ray::new(rec.p, CosinePdf::new(rec.normal).generate(rng));
+        let attentuation = self.tex.value(rec.u, rec.v, rec.p);
         Some((attentuation, scattered))
     }
+
+    fn scattering_pdf(&self, _r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+        CosinePdf::new(rec.normal).value(scattered.direction)
+    }
 }
 
 pub struct Metal {
-    albedo: Color,
+    tex: Arc<dyn Texture>,
     fuzz: f64,
 }
 
 impl Metal {
     pub fn new(albedo: Color, fuzz: f64) -> Self {
+        Self::from_texture(Arc::new(SolidColor::new(albedo)), fuzz)
+    }
+
+    pub fn from_texture(tex: Arc<dyn Texture>, fuzz: f64) -> Self {
         Self {
-            albedo,
+            tex,
             fuzz: if fuzz < 1.0 { fuzz } else { 1.0 },
         }
     }
 }
 
 impl Material for Metal {
-    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, rng: &mut Rng) -> Option<(Color, Ray)> {
         let mut reflected = Vec3::reflect(r_in.direction, rec.normal);
-        reflected = Vec3::unit_vector(reflected) + (self.fuzz * Vec3::random_unit_vector());
+        reflected = Vec3::unit_vector(reflected) + (self.fuzz * Vec3::random_unit_vector(rng));
         let scattered = Ray::new(rec.p, reflected);
-        let attentuation = self.albedo;
+        let attentuation = self.tex.value(rec.u, rec.v, rec.p);
         if Vec3::dot(scattered.direction, rec.normal) > 0.0 {
             Some((attentuation, scattered))
         } else {
             None
         }
     }
+
+    /// Fuzzed reflections spread over a cone around the mirror direction;
+    /// those that would go into the surface are absorbed.
+    fn scattering_pdf(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+        if self.fuzz <= 0.0 || Vec3::dot(scattered.direction, rec.normal) <= 0.0 {
+            return 0.0;
+        }
+        let mirror = Vec3::reflect(r_in.direction, rec.normal).unit_vector();
+        fuzz_pdf(mirror, self.fuzz, scattered.direction.unit_vector())
+    }
+}
+
+/// Density of the direction of `mirror + fuzz * v` for `v` uniform on the
+/// unit sphere, at the unit vector `direction`.
+///
+/// Each direction meets the fuzz sphere at up to two points `t * direction`.
+/// Moving from area on the sphere to solid angle scales the density of each
+/// by `t^2 / cos`, and the cosine to the sphere's normal works out to
+/// `sqrt(disc) / fuzz` at both.
+fn fuzz_pdf(mirror: Vec3, fuzz: f64, direction: Vec3) -> f64 {
+    let b = Vec3::dot(direction, mirror);
+    let disc = b * b - (1.0 - fuzz * fuzz);
+    if disc <= 0.0 {
+        return 0.0;
+    }
+    let root = disc.sqrt();
+    let t2: f64 = [b - root, b + root]
+        .iter()
+        .filter(|&&t| t > 0.0)
+        .map(|t| t * t)
+        .sum();
+    t2 / (4.0 * PI * fuzz * root)
+}
+
+/// Phase function of a participating medium: scatters equally in every
+/// direction.
+pub struct Isotropic {
+    tex: Arc<dyn Texture>,
+}
+
+impl Isotropic {
+    pub fn new(albedo: Color) -> Self {
+        Self::from_texture(Arc::new(SolidColor::new(albedo)))
+    }
+
+    pub fn from_texture(tex: Arc<dyn Texture>) -> Self {
+        Self { tex }
+    }
+}
+
+impl Material for Isotropic {
+    fn scatter(&self, _r_in: &Ray, rec: &HitRecord, rng: &mut Rng) -> Option<(Color, Ray)> {
+        let scattered = Ray::new(rec.p, SpherePdf.generate(rng));
+        Some((self.tex.value(rec.u, rec.v, rec.p), scattered))
+    }
+
+    fn scattering_pdf(&self, _r_in: &Ray, _rec: &HitRecord, scattered: &Ray) -> f64 {
+        SpherePdf.value(scattered.direction)
+    }
 }
 
 pub struct Dialectric {
@@ -70,7 +188,7 @@ impl Dialectric {
 }
 
 impl Material for Dialectric {
-    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, rng: &mut Rng) -> Option<(Color, Ray)> {
         let attentuation = Color::new(1.0, 1.0, 1.0);
         let ri = if rec.front_face {
             1.0 / self.refraction_index
@@ -82,7 +200,7 @@ impl Material for Dialectric {
         let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
         let cannot_refract = ri * sin_theta > 1.0;
 
-        let direction = if cannot_refract || self.reflectance(cos_theta) > random_f64() {
+        let direction = if cannot_refract || self.reflectance(cos_theta) > random_f64(rng) {
             Vec3::reflect(unit_direction, rec.normal)
         } else {
             Vec3::refract(unit_direction, rec.normal, ri)
@@ -92,3 +210,73 @@ impl Material for Dialectric {
         Some((attentuation, scattered))
     }
 }
+
+pub struct DiffuseLight {
+    tex: Arc<dyn Texture>,
+}
+
+impl DiffuseLight {
+    pub fn new(emit: Color) -> Self {
+        Self::from_texture(Arc::new(SolidColor::new(emit)))
+    }
+
+    pub fn from_texture(tex: Arc<dyn Texture>) -> Self {
+        Self { tex }
+    }
+}
+
+impl Material for DiffuseLight {
+    fn scatter(&self, _r_in: &Ray, _rec: &HitRecord, _rng: &mut Rng) -> Option<(Color, Ray)> {
+        None
+    }
+
+    fn emitted(&self, u: f64, v: f64, p: Point3) -> Color {
+        self.tex.value(u, v, p)
+    }
+
+    fn is_emissive(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn floor_hit(mat: &dyn Material) -> HitRecord<'_> {
+        HitRecord::new(0.0, Point3::origin(), Vec3::new(0.0, 1.0, 0.0), mat)
+    }
+
+    #[test]
+    fn test_metal_scattering_pdf() {
+        // Straight down onto the floor with fuzz 0.5, reflections fill the
+        // cone of half-angle 30 degrees around the normal.
+        let metal = Metal::new(Color::new(1.0, 1.0, 1.0), 0.5);
+        let rec = floor_hit(&metal);
+        let r_in = Ray::new(Point3::new(0.0, 1.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        let mut rng = Rng::with_seed(8);
+        let trials = 50_000;
+        let mut solid_angle = 0.0;
+        for _ in 0..trials {
+            let (_, scattered) = metal.scatter(&r_in, &rec, &mut rng).unwrap();
+            let pdf = metal.scattering_pdf(&r_in, &rec, &scattered);
+            assert!(pdf > 0.0);
+            solid_angle += 1.0 / pdf;
+        }
+        let solid_angle = solid_angle / trials as f64;
+        let expected = 2.0 * PI * (1.0 - 0.75f64.sqrt());
+        assert!((solid_angle / expected - 1.0).abs() < 0.02, "{solid_angle}");
+        let outside = Ray::new(Point3::origin(), Vec3::new(1.0, 1.0, 0.0));
+        assert_eq!(metal.scattering_pdf(&r_in, &rec, &outside), 0.0);
+
+        // Fully fuzzed, reflections are as diffuse as a Lambertian's.
+        let direction = Vec3::new(0.3, 0.8, -0.2).unit_vector();
+        let pdf = fuzz_pdf(Vec3::new(0.0, 1.0, 0.0), 1.0, direction);
+        assert!((pdf - direction.y / PI).abs() < 1e-12);
+
+        // A perfect mirror has no density to weigh light samples with.
+        let mirror = Metal::new(Color::new(1.0, 1.0, 1.0), 0.0);
+        let up = Ray::new(Point3::origin(), Vec3::new(0.0, 1.0, 0.0));
+        assert_eq!(mirror.scattering_pdf(&r_in, &floor_hit(&mirror), &up), 0.0);
+    }
+}
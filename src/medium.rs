@@ -0,0 +1,271 @@
+//! Participating media: volumes that scatter light throughout instead of at
+//! a surface.
+
+use crate::{
+    aabb::Aabb,
+    density::DensityField,
+    hittable::{HitRecord, Hittable},
+    inretval::Interval,
+    material::Material,
+    ray::Ray,
+    util::{Rng, hash_rng, random_f64},
+    vec3::Vec3,
+};
+
+/// Fog or smoke of uniform density filling a closed, convex `boundary`.
+///
+/// A ray travelling through it scatters after an exponentially distributed
+/// distance, and passes straight through if that lies beyond the far side.
+/// The returned hit carries `phase` as its material, typically
+/// [`Isotropic`](crate::material::Isotropic).
+pub struct ConstantMedium<'a> {
+    boundary: Box<dyn Hittable + 'a>,
+    density: f64,
+    phase: Box<dyn Material + 'a>,
+}
+
+impl<'a> ConstantMedium<'a> {
+    pub fn new(boundary: impl Hittable + 'a, density: f64, phase: impl Material + 'a) -> Self {
+        Self {
+            boundary: Box::new(boundary),
+            density,
+            phase: Box::new(phase),
+        }
+    }
+}
+
+impl Hittable for ConstantMedium<'_> {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        let (t_min, t_max) = inside(&*self.boundary, r, ray_t)?;
+        let mut rng = ray_rng(r);
+        let ray_length = r.direction.length();
+        let distance = free_flight(&mut rng, self.density);
+        if distance > (t_max - t_min) * ray_length {
+            return None;
+        }
+        Some(scatter_at(r, t_min + distance / ray_length, &*self.phase))
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.boundary.bounding_box()
+    }
+
+    /// Beer-Lambert: exact, no sampling needed.
+    fn transmittance(&self, r: &Ray, ray_t: Interval, _rng: &mut Rng) -> f64 {
+        match inside(&*self.boundary, r, ray_t) {
+            Some((t_min, t_max)) => (-self.density * (t_max - t_min) * r.direction.length()).exp(),
+            None => 1.0,
+        }
+    }
+}
+
+/// A medium whose density varies through a closed, convex `boundary`, like a
+/// cloud or an explosion.
+///
+/// Free flights are sampled with delta tracking and shadow rays estimate
+/// transmittance with ratio tracking. Both step through the volume with the
+/// field's maximum density and are unbiased for any field that respects it.
+pub struct HeterogeneousMedium<'a> {
+    boundary: Box<dyn Hittable + 'a>,
+    field: Box<dyn DensityField + 'a>,
+    /// Multiplies the field's values.
+    scale: f64,
+    majorant: f64,
+    phase: Box<dyn Material + 'a>,
+}
+
+impl<'a> HeterogeneousMedium<'a> {
+    pub fn new(
+        boundary: impl Hittable + 'a,
+        field: impl DensityField + 'a,
+        scale: f64,
+        phase: impl Material + 'a,
+    ) -> Self {
+        let majorant = scale * field.max_density();
+        Self {
+            boundary: Box::new(boundary),
+            field: Box::new(field),
+            scale,
+            majorant,
+            phase: Box::new(phase),
+        }
+    }
+
+    fn density(&self, r: &Ray, t: f64) -> f64 {
+        self.scale * self.field.density(r.at(t))
+    }
+}
+
+impl Hittable for HeterogeneousMedium<'_> {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        let (t_min, t_max) = inside(&*self.boundary, r, ray_t)?;
+        if self.majorant <= 0.0 {
+            return None;
+        }
+        let mut rng = ray_rng(r);
+        let ray_length = r.direction.length();
+        // Step as if the whole volume had the majorant's density, accepting
+        // a collision as real with probability density / majorant.
+        let mut t = t_min;
+        loop {
+            t += free_flight(&mut rng, self.majorant) / ray_length;
+            if t >= t_max {
+                return None;
+            }
+            if random_f64(&mut rng) * self.majorant < self.density(r, t) {
+                return Some(scatter_at(r, t, &*self.phase));
+            }
+        }
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.boundary.bounding_box()
+    }
+
+    fn transmittance(&self, r: &Ray, ray_t: Interval, rng: &mut Rng) -> f64 {
+        let Some((t_min, t_max)) = inside(&*self.boundary, r, ray_t) else {
+            return 1.0;
+        };
+        if self.majorant <= 0.0 {
+            return 1.0;
+        }
+        // Ratio tracking: the same steps as delta tracking, but weighting by
+        // the chance of a null collision instead of choosing one.
+        let ray_length = r.direction.length();
+        let mut transmittance = 1.0;
+        let mut t = t_min;
+        loop {
+            t += free_flight(rng, self.majorant) / ray_length;
+            if t >= t_max {
+                return transmittance;
+            }
+            transmittance *= 1.0 - self.density(r, t) / self.majorant;
+        }
+    }
+}
+
+/// Where the ray's line enters and leaves `boundary`, clipped to `ray_t`.
+fn inside(boundary: &dyn Hittable, r: &Ray, ray_t: Interval) -> Option<(f64, f64)> {
+    let enter = boundary.hit(r, Interval::UNIVERSE)?;
+    let exit = boundary.hit(r, Interval::new(enter.t + 0.0001, f64::INFINITY))?;
+    let t_min = enter.t.max(ray_t.min).max(0.0);
+    let t_max = exit.t.min(ray_t.max);
+    (t_min < t_max).then_some((t_min, t_max))
+}
+
+/// `hit` has no random stream, so media draw from one keyed by the ray; the
+/// same ray always scatters at the same point.
+fn ray_rng(r: &Ray) -> Rng {
+    hash_rng(&[
+        r.origin.x,
+        r.origin.y,
+        r.origin.z,
+        r.direction.x,
+        r.direction.y,
+        r.direction.z,
+    ])
+}
+
+/// Exponentially distributed distance to the next collision.
+fn free_flight(rng: &mut Rng, density: f64) -> f64 {
+    -(1.0 - random_f64(rng)).ln() / density
+}
+
+fn scatter_at<'a>(r: &Ray, t: f64, phase: &'a dyn Material) -> HitRecord<'a> {
+    // A point inside a volume has no surface normal; any will do.
+    HitRecord::new(t, r.at(t), Vec3::new(1.0, 0.0, 0.0), phase)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        color::Color, density::VoxelGrid, material::Isotropic, quad::BoxShape, sphere::Sphere,
+        vec3::Point3,
+    };
+
+    fn white() -> Isotropic {
+        Isotropic::new(Color::new(1.0, 1.0, 1.0))
+    }
+
+    #[test]
+    fn test_constant_medium_transmittance() {
+        // A slab of optical depth 1 lets through about e^-1 of the rays.
+        let density = 0.5;
+        let medium = ConstantMedium::new(
+            Sphere::new(
+                Point3::origin(),
+                1.0,
+                Isotropic::new(Color::new(1.0, 1.0, 1.0)),
+            ),
+            density,
+            Isotropic::new(Color::new(1.0, 1.0, 1.0)),
+        );
+        let mut rng = Rng::with_seed(3);
+        let trials = 20_000;
+        let mut passed = 0;
+        for _ in 0..trials {
+            // Through the center with slightly jittered origins, so each ray
+            // draws a different distance.
+            let origin = Point3::new(0.0, 0.0, 5.0 + random_f64(&mut rng));
+            let r = Ray::new(origin, Vec3::new(0.0, 0.0, -2.0));
+            match medium.hit(&r, Interval::new(0.001, f64::INFINITY)) {
+                Some(rec) => assert!(rec.p.z.abs() <= 1.0 + 1e-9),
+                None => passed += 1,
+            }
+        }
+        let fraction = passed as f64 / trials as f64;
+        assert!((fraction - (-1.0f64).exp()).abs() < 0.02, "{fraction}");
+
+        // Rays that start inside scatter before reaching the boundary, and
+        // the same ray always scatters at the same distance.
+        let r = Ray::new(Point3::origin(), Vec3::new(1.0, 0.0, 0.0));
+        let ray_t = Interval::new(0.001, f64::INFINITY);
+        let first = medium.hit(&r, ray_t).map(|rec| rec.t);
+        assert_eq!(first, medium.hit(&r, ray_t).map(|rec| rec.t));
+
+        let miss = Ray::new(Point3::new(0.0, 2.0, 5.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(medium.hit(&miss, ray_t).is_none());
+
+        let through = Ray::new(Point3::new(0.0, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0));
+        let t = medium.transmittance(&through, ray_t, &mut rng);
+        assert!((t - (-1.0f64).exp()).abs() < 1e-9);
+        // Only the part of the ray being asked about counts.
+        let t = medium.transmittance(&through, Interval::new(0.0, 5.0), &mut rng);
+        assert!((t - (-0.5f64).exp()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_heterogeneous_medium_tracking() {
+        // Density rises linearly from 0 to 1 between the voxel centers at
+        // z = 0.5 and 1.5 and is flat outside: an optical depth of 1 along z.
+        let boundary = Aabb::from_points(Point3::origin(), Point3::new(1.0, 1.0, 2.0));
+        let grid = VoxelGrid::new([1, 1, 2], vec![0.0, 1.0], boundary).unwrap();
+        let medium = HeterogeneousMedium::new(
+            BoxShape::new(Point3::origin(), Point3::new(1.0, 1.0, 2.0), white()),
+            grid,
+            1.0,
+            white(),
+        );
+        let ray_t = Interval::new(0.001, f64::INFINITY);
+        let expected = (-1.0f64).exp();
+
+        let mut rng = Rng::with_seed(11);
+        let trials = 20_000;
+        let (mut passed, mut ratio) = (0, 0.0);
+        for _ in 0..trials {
+            let origin = Point3::new(0.5, 0.5, -1.0 - random_f64(&mut rng));
+            let r = Ray::new(origin, Vec3::new(0.0, 0.0, 1.0));
+            match medium.hit(&r, ray_t) {
+                // Nothing collides where the density is zero.
+                Some(rec) => assert!(rec.p.z > 0.5 && rec.p.z < 2.0),
+                None => passed += 1,
+            }
+            ratio += medium.transmittance(&r, ray_t, &mut rng);
+        }
+        let fraction = passed as f64 / trials as f64;
+        assert!((fraction - expected).abs() < 0.02, "{fraction}");
+        let ratio = ratio / trials as f64;
+        assert!((ratio - expected).abs() < 0.01, "{ratio}");
+    }
+}
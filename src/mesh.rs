@@ -0,0 +1,107 @@
+use std::sync::Arc;
+
+use crate::{
+    aabb::Aabb,
+    bvh::BvhNode,
+    hittable::{HitRecord, Hittable},
+    inretval::Interval,
+    material::Material,
+    ray::Ray,
+    triangle::{intersect, surface_hit},
+    vec3::{Point3, Vec3},
+};
+
+/// Vertex buffers shared by every triangle of a mesh.
+#[derive(Debug, Default)]
+pub struct MeshData {
+    pub positions: Vec<Point3>,
+    pub normals: Vec<Vec3>,
+    pub uvs: Vec<(f64, f64)>,
+}
+
+/// Triangle given as indices into the [`MeshData`] buffers and the mesh materials.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MeshFace {
+    pub vertices: [usize; 3],
+    pub normals: Option<[usize; 3]>,
+    pub uvs: Option<[usize; 3]>,
+    pub material: usize,
+}
+
+/// Triangle mesh accelerated by its own bounding volume hierarchy.
+pub struct Mesh<'a> {
+    bvh: BvhNode<'a>,
+    triangle_count: usize,
+}
+
+impl<'a> Mesh<'a> {
+    pub fn new(
+        data: MeshData,
+        faces: Vec<MeshFace>,
+        materials: Vec<Arc<dyn Material + 'a>>,
+    ) -> Self {
+        let data = Arc::new(data);
+        let triangle_count = faces.len();
+        let triangles = faces
+            .into_iter()
+            .map(|face| {
+                let p = face.vertices.map(|i| data.positions[i]);
+                let bbox =
+                    Aabb::surrounding(Aabb::from_points(p[0], p[1]), Aabb::from_points(p[1], p[2]));
+                Box::new(MeshTriangle {
+                    data: Arc::clone(&data),
+                    face,
+                    mat: Arc::clone(&materials[face.material]),
+                    bbox,
+                }) as Box<dyn Hittable + 'a>
+            })
+            .collect();
+        Self {
+            bvh: BvhNode::from_objects(triangles),
+            triangle_count,
+        }
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.triangle_count
+    }
+}
+
+impl<'a> Hittable for Mesh<'a> {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        self.bvh.hit(r, ray_t)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bvh.bounding_box()
+    }
+}
+
+struct MeshTriangle<'a> {
+    data: Arc<MeshData>,
+    face: MeshFace,
+    mat: Arc<dyn Material + 'a>,
+    bbox: Aabb,
+}
+
+impl<'a> Hittable for MeshTriangle<'a> {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        let p = self.face.vertices.map(|i| self.data.positions[i]);
+        let (t, b) = intersect(r, ray_t, &p)?;
+        let normals = self.face.normals.map(|n| n.map(|i| self.data.normals[i]));
+        let uvs = self.face.uvs.map(|uv| uv.map(|i| self.data.uvs[i]));
+        Some(surface_hit(
+            r,
+            t,
+            b,
+            &p,
+            normals.as_ref(),
+            uvs.as_ref(),
+            &*self.mat,
+        ))
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+}
@@ -0,0 +1,447 @@
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use crate::{
+    color::Color,
+    material::{Dialectric, Lambertian, Material, Metal},
+    mesh::{Mesh, MeshData, MeshFace},
+    texture::{ImageTexture, Texture},
+    vec3::{Point3, Vec3},
+};
+
+#[derive(Debug)]
+pub enum ObjError {
+    Io {
+        path: PathBuf,
+        source: io::Error,
+    },
+    Parse {
+        file: PathBuf,
+        line: usize,
+        message: String,
+    },
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ObjError::Io { path, source } => write!(f, "{}: {source}", path.display()),
+            ObjError::Parse {
+                file,
+                line,
+                message,
+            } => write!(f, "{}:{line}: {message}", file.display()),
+        }
+    }
+}
+
+impl Error for ObjError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ObjError::Io { source, .. } => Some(source),
+            ObjError::Parse { .. } => None,
+        }
+    }
+}
+
+/// Loads a Wavefront OBJ file together with the `.mtl` libraries it references.
+///
+/// Faces without a `usemtl` (or naming an unknown material) use `default_material`.
+pub fn load_obj<'a>(
+    path: impl AsRef<Path>,
+    default_material: Arc<dyn Material + 'a>,
+) -> Result<Mesh<'a>, ObjError> {
+    let path = path.as_ref();
+    let source = read(path)?;
+    let base_dir = path.parent().unwrap_or(Path::new("."));
+    parse_obj(&source, path, base_dir, default_material)
+}
+
+/// Parses OBJ text; `file` is only used in error messages and `mtllib`
+/// paths are resolved against `base_dir`.
+pub fn parse_obj<'a>(
+    source: &str,
+    file: &Path,
+    base_dir: &Path,
+    default_material: Arc<dyn Material + 'a>,
+) -> Result<Mesh<'a>, ObjError> {
+    let mut data = MeshData::default();
+    let mut faces = Vec::new();
+    let mut materials = vec![default_material];
+    let mut material_ids: HashMap<String, usize> = HashMap::new();
+    let mut library: HashMap<String, MtlEntry> = HashMap::new();
+    let mut current_material = 0;
+
+    for (index, line) in source.lines().enumerate() {
+        let mut p = LineParser::new(line, file, index + 1);
+        let Some(keyword) = p.next() else {
+            continue;
+        };
+        match keyword {
+            "v" => data.positions.push(p.point()?),
+            "vn" => data.normals.push(p.vector()?),
+            "vt" => {
+                let u = p.number()?;
+                let v = p.optional_number()?.unwrap_or(0.0);
+                data.uvs.push((u, v));
+            }
+            "f" => {
+                let tokens: Vec<&str> = p.by_ref().collect();
+                let mut corners = Vec::with_capacity(tokens.len());
+                for token in tokens {
+                    corners.push(parse_corner(token, &data).map_err(|m| p.error(m))?);
+                }
+                if corners.len() < 3 {
+                    return Err(p.error("face needs at least three vertices".to_string()));
+                }
+                // Triangulate polygons as a fan around the first corner.
+                for i in 1..corners.len() - 1 {
+                    let tri = [corners[0], corners[i], corners[i + 1]];
+                    faces.push(MeshFace {
+                        vertices: tri.map(|c| c.0),
+                        uvs: all_some(tri.map(|c| c.1)),
+                        normals: all_some(tri.map(|c| c.2)),
+                        material: current_material,
+                    });
+                }
+            }
+            "mtllib" => {
+                for name in p.by_ref() {
+                    library.extend(load_mtl(&base_dir.join(name))?);
+                }
+            }
+            "usemtl" => {
+                let name = p.rest();
+                current_material = match material_ids.get(name) {
+                    Some(&id) => id,
+                    None => match library.get(name) {
+                        Some(entry) => {
+                            materials.push(entry.to_material());
+                            material_ids.insert(name.to_string(), materials.len() - 1);
+                            materials.len() - 1
+                        }
+                        None => 0,
+                    },
+                };
+            }
+            // Grouping and smoothing statements do not affect rendering.
+            "o" | "g" | "s" | "l" | "p" => {}
+            other => return Err(p.error(format!("unknown statement `{other}`"))),
+        }
+    }
+
+    Ok(Mesh::new(data, faces, materials))
+}
+
+type Corner = (usize, Option<usize>, Option<usize>);
+
+/// Parses `v`, `v/vt`, `v//vn` or `v/vt/vn`; negative indices count back
+/// from the most recently defined element.
+fn parse_corner(token: &str, data: &MeshData) -> Result<Corner, String> {
+    let mut parts = token.split('/');
+    let vertex = resolve_index(parts.next(), data.positions.len(), "vertex")?
+        .ok_or_else(|| format!("missing vertex index in `{token}`"))?;
+    let uv = resolve_index(parts.next(), data.uvs.len(), "texture coordinate")?;
+    let normal = resolve_index(parts.next(), data.normals.len(), "normal")?;
+    Ok((vertex, uv, normal))
+}
+
+fn resolve_index(part: Option<&str>, len: usize, what: &str) -> Result<Option<usize>, String> {
+    let Some(part) = part.filter(|s| !s.is_empty()) else {
+        return Ok(None);
+    };
+    let index: i64 = part
+        .parse()
+        .map_err(|_| format!("invalid {what} index `{part}`"))?;
+    let resolved = if index > 0 {
+        index - 1
+    } else {
+        len as i64 + index
+    };
+    if index == 0 || resolved < 0 || resolved >= len as i64 {
+        return Err(format!("{what} index {index} out of range"));
+    }
+    Ok(Some(resolved as usize))
+}
+
+fn all_some(values: [Option<usize>; 3]) -> Option<[usize; 3]> {
+    Some([values[0]?, values[1]?, values[2]?])
+}
+
+/// Subset of the MTL illumination model that maps onto our materials.
+struct MtlEntry {
+    diffuse: Color,
+    /// Used in place of `diffuse` when present.
+    diffuse_map: Option<Arc<dyn Texture>>,
+    specular: Color,
+    shininess: f64,
+    refraction_index: f64,
+    dissolve: f64,
+    illum: u32,
+}
+
+impl Default for MtlEntry {
+    fn default() -> Self {
+        Self {
+            diffuse: Color::new(0.8, 0.8, 0.8),
+            diffuse_map: None,
+            specular: Color::new(0.0, 0.0, 0.0),
+            shininess: 0.0,
+            refraction_index: 1.0,
+            dissolve: 1.0,
+            illum: 2,
+        }
+    }
+}
+
+impl MtlEntry {
+    fn to_material<'a>(&self) -> Arc<dyn Material + 'a> {
+        let transparent = self.dissolve < 1.0 || matches!(self.illum, 4 | 6 | 7 | 9);
+        if transparent {
+            Arc::new(Dialectric::new(self.refraction_index))
+        } else if self.illum == 3 || self.illum == 5 {
+            // Map the Phong exponent onto a roughness-like fuzz factor.
+            let fuzz = (2.0 / (self.shininess + 2.0)).sqrt();
+            Arc::new(Metal::new(self.specular, fuzz))
+        } else if let Some(tex) = &self.diffuse_map {
+            Arc::new(Lambertian::from_texture(tex.clone()))
+        } else {
+            Arc::new(Lambertian::new(self.diffuse))
+        }
+    }
+}
+
+fn load_mtl(path: &Path) -> Result<HashMap<String, MtlEntry>, ObjError> {
+    let source = read(path)?;
+    let mut entries = HashMap::new();
+    let mut current: Option<(String, MtlEntry)> = None;
+    // Maps shared between materials are loaded once.
+    let mut maps: HashMap<PathBuf, Arc<dyn Texture>> = HashMap::new();
+
+    for (index, line) in source.lines().enumerate() {
+        let mut p = LineParser::new(line, path, index + 1);
+        let Some(keyword) = p.next() else {
+            continue;
+        };
+        if keyword == "newmtl" {
+            if let Some((name, entry)) = current.take() {
+                entries.insert(name, entry);
+            }
+            current = Some((p.rest().to_string(), MtlEntry::default()));
+            continue;
+        }
+        let Some((_, entry)) = current.as_mut() else {
+            return Err(p.error(format!("`{keyword}` before any `newmtl`")));
+        };
+        match keyword {
+            "Kd" => entry.diffuse = p.color()?,
+            "Ks" => entry.specular = p.color()?,
+            "Ns" => entry.shininess = p.number()?,
+            "Ni" => entry.refraction_index = p.number()?,
+            "d" => entry.dissolve = p.number()?,
+            "Tr" => entry.dissolve = 1.0 - p.number()?,
+            "illum" => entry.illum = p.number()? as u32,
+            "map_Kd" => {
+                // Map options come first, the file name last.
+                let Some(name) = p.rest().split_whitespace().last() else {
+                    return Err(p.error("`map_Kd` needs a file name".to_string()));
+                };
+                let map_path = path.parent().unwrap_or(Path::new("")).join(name);
+                let tex = match maps.get(&map_path) {
+                    Some(tex) => tex.clone(),
+                    None => {
+                        let tex: Arc<dyn Texture> =
+                            Arc::new(ImageTexture::load(&map_path).map_err(|source| {
+                                ObjError::Io {
+                                    path: map_path.clone(),
+                                    source,
+                                }
+                            })?);
+                        maps.insert(map_path, tex.clone());
+                        tex
+                    }
+                };
+                entry.diffuse_map = Some(tex);
+            }
+            // Remaining statements (ambient, emission, other maps) are ignored.
+            _ => {}
+        }
+    }
+    if let Some((name, entry)) = current {
+        entries.insert(name, entry);
+    }
+    Ok(entries)
+}
+
+fn read(path: &Path) -> Result<String, ObjError> {
+    fs::read_to_string(path).map_err(|source| ObjError::Io {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+/// Whitespace tokenizer for a single OBJ/MTL line with comments stripped.
+struct LineParser<'s> {
+    content: &'s str,
+    tokens: std::str::SplitWhitespace<'s>,
+    file: &'s Path,
+    line: usize,
+}
+
+impl<'s> LineParser<'s> {
+    fn new(line: &'s str, file: &'s Path, number: usize) -> Self {
+        let content = line.split('#').next().unwrap_or("").trim();
+        Self {
+            content,
+            tokens: content.split_whitespace(),
+            file,
+            line: number,
+        }
+    }
+
+    fn error(&self, message: String) -> ObjError {
+        ObjError::Parse {
+            file: self.file.to_path_buf(),
+            line: self.line,
+            message,
+        }
+    }
+
+    /// Everything after the keyword, for names that may contain spaces.
+    fn rest(&self) -> &'s str {
+        self.content
+            .split_once(char::is_whitespace)
+            .map_or("", |(_, rest)| rest.trim())
+    }
+
+    fn optional_number(&mut self) -> Result<Option<f64>, ObjError> {
+        match self.tokens.next() {
+            None => Ok(None),
+            Some(token) => token
+                .parse()
+                .map(Some)
+                .map_err(|_| self.error(format!("invalid number `{token}`"))),
+        }
+    }
+
+    fn number(&mut self) -> Result<f64, ObjError> {
+        self.optional_number()?
+            .ok_or_else(|| self.error("expected a number".to_string()))
+    }
+
+    fn point(&mut self) -> Result<Point3, ObjError> {
+        Ok(Point3::from(self.vector()?))
+    }
+
+    fn vector(&mut self) -> Result<Vec3, ObjError> {
+        Ok(Vec3::new(self.number()?, self.number()?, self.number()?))
+    }
+
+    fn color(&mut self) -> Result<Color, ObjError> {
+        Ok(Color::from(self.vector()?))
+    }
+}
+
+impl<'s> Iterator for LineParser<'s> {
+    type Item = &'s str;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.tokens.next()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{hittable::Hittable, inretval::Interval, ray::Ray, test_util::gray, util::Rng};
+
+    fn parse(source: &str) -> Result<Mesh<'static>, ObjError> {
+        parse_obj(
+            source,
+            Path::new("test.obj"),
+            Path::new("."),
+            Arc::new(gray()),
+        )
+    }
+
+    #[test]
+    fn test_polygon_is_triangulated() {
+        let mesh = parse(
+            "# unit square\n\
+             v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\n\
+             vt 0 0\nvt 1 0\nvt 1 1\nvt 0 1\n\
+             vn 0 0 1\n\
+             f 1/1/1 2/2/1 3/3/1 -1/-1/-1\n",
+        )
+        .unwrap();
+        assert_eq!(mesh.triangle_count(), 2);
+
+        let r = Ray::new(Point3::new(0.25, 0.75, 1.0), Vec3::new(0.0, 0.0, -1.0));
+        let rec = mesh.hit(&r, Interval::new(0.001, f64::INFINITY)).unwrap();
+        assert_eq!(rec.t, 1.0);
+        assert!((rec.u - 0.25).abs() < 1e-12 && (rec.v - 0.75).abs() < 1e-12);
+        assert_eq!(rec.normal, Vec3::new(0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn test_parse_error_reports_line() {
+        let err = match parse("v 0 0 0\nv 1 0 0\nf 1 2 3\n") {
+            Err(err) => err,
+            Ok(_) => panic!("expected an error"),
+        };
+        assert_eq!(err.to_string(), "test.obj:3: vertex index 3 out of range");
+    }
+
+    #[test]
+    fn test_mtl_materials() {
+        let dir = std::env::temp_dir().join(format!("rusty_rays_obj_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("scene.mtl"),
+            "newmtl glass\nNi 1.5\nd 0.1\n\nnewmtl red plastic\nKd 1 0 0\n\
+             newmtl wood\nmap_Kd -s 2 2 1 wood.ppm\n",
+        )
+        .unwrap();
+        fs::write(dir.join("wood.ppm"), "P3 1 1 255 200 120 40\n").unwrap();
+        fs::write(
+            dir.join("scene.obj"),
+            "mtllib scene.mtl\n\
+             v 0 0 0\nv 1 0 0\nv 0 1 0\nv 2 0 0\nv 3 0 0\nv 2 1 0\n\
+             v 4 0 0\nv 5 0 0\nv 4 1 0\nv 6 0 0\nv 7 0 0\nv 6 1 0\n\
+             usemtl red plastic\nf 1 2 3\nusemtl glass\nf 4 5 6\n\
+             usemtl missing\nf 7 8 9\nusemtl wood\nf 10 11 12\n",
+        )
+        .unwrap();
+        let mesh = load_obj(dir.join("scene.obj"), Arc::new(gray())).unwrap();
+        assert_eq!(mesh.triangle_count(), 4);
+
+        // What each face scatters with, and whether it is a perfect
+        // reflector or refractor.
+        let mut rng = Rng::with_seed(1);
+        let mut face = |x: f64| {
+            let r = Ray::new(Point3::new(x + 0.25, 0.25, 1.0), Vec3::new(0.0, 0.0, -1.0));
+            let rec = mesh.hit(&r, Interval::new(0.001, f64::INFINITY)).unwrap();
+            let (attenuation, scattered) = rec.mat.scatter(&r, &rec, &mut rng).unwrap();
+            let specular = rec.mat.scattering_pdf(&r, &rec, &scattered) == 0.0;
+            (attenuation, specular)
+        };
+        assert_eq!(face(0.0), (Color::new(1.0, 0.0, 0.0), false));
+        assert_eq!(face(2.0), (Color::new(1.0, 1.0, 1.0), true));
+        // Unknown materials fall back to the default one.
+        assert_eq!(face(4.0), (Color::new(0.5, 0.5, 0.5), false));
+        let (wood, specular) = face(6.0);
+        assert!(!specular && wood.r > wood.g && wood.g > wood.b, "{wood:?}");
+
+        fs::remove_file(dir.join("wood.ppm")).unwrap();
+        match load_obj(dir.join("scene.obj"), Arc::new(gray())) {
+            Err(ObjError::Io { path, .. }) => assert_eq!(path, dir.join("wood.ppm")),
+            _ => panic!("expected a missing texture error"),
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,87 @@
+//! Image file formats behind a common [`ImageWriter`] interface.
+
+use std::{
+    io::{self, Write},
+    path::Path,
+};
+
+use crate::{
+    exr::{Compression, ExrOptions, SampleType},
+    framebuffer::Framebuffer,
+    hdr::{Hdr, Pfm},
+    png::{BitDepth, PngOptions},
+    ppm::{GrayPass, P3, P6, Pgm},
+};
+
+/// Encodes a rendered framebuffer in one file format.
+pub trait ImageWriter {
+    fn write(&self, out: &mut dyn Write, image: &Framebuffer) -> io::Result<()>;
+}
+
+/// Builds the writer for one format.
+pub type NewWriter = fn() -> Box<dyn ImageWriter>;
+
+/// Every format [`writer_for_name`] accepts, with how to build its writer.
+pub const FORMATS: [(&str, NewWriter); 12] = [
+    ("ppm", || Box::new(P6)),
+    ("p6", || Box::new(P6)),
+    ("p3", || Box::new(P3)),
+    ("pgm", || Box::new(Pgm(GrayPass::Luminance))),
+    ("pgm-alpha", || Box::new(Pgm(GrayPass::Alpha))),
+    ("pgm-depth", || Box::new(Pgm(GrayPass::Depth))),
+    ("png", || Box::new(PngOptions::default())),
+    ("png16", || {
+        Box::new(PngOptions {
+            bit_depth: BitDepth::Sixteen,
+            alpha: false,
+        })
+    }),
+    ("hdr", || Box::new(Hdr)),
+    ("pfm", || Box::new(Pfm)),
+    ("exr", || Box::new(ExrOptions::default())),
+    ("exr32", || {
+        Box::new(ExrOptions {
+            sample_type: SampleType::Float,
+            compression: Compression::Zip,
+        })
+    }),
+];
+
+/// Looks up a format by name, ignoring case.
+pub fn writer_for_name(name: &str) -> Option<Box<dyn ImageWriter>> {
+    FORMATS
+        .iter()
+        .find(|(format, _)| format.eq_ignore_ascii_case(name))
+        .map(|(_, writer)| writer())
+}
+
+/// Picks the format from the file extension, e.g. `ppm` for `out.ppm`.
+pub fn writer_for_path(path: &Path) -> Option<Box<dyn ImageWriter>> {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .and_then(writer_for_name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lookup() {
+        for (name, _) in FORMATS {
+            assert!(writer_for_name(name).is_some(), "{name}");
+        }
+        assert!(writer_for_name("PNG").is_some());
+        assert!(writer_for_name("gif").is_none());
+        assert!(writer_for_path(Path::new("out/depth.pgm")).is_some());
+        assert!(writer_for_path(Path::new("image")).is_none());
+
+        let mut out = Vec::new();
+        let image = Framebuffer::new(1, 1);
+        writer_for_path(Path::new("a.PPM"))
+            .unwrap()
+            .write(&mut out, &image)
+            .unwrap();
+        assert_eq!(out, b"P6\n1 1\n255\n\0\0\0");
+    }
+}
@@ -0,0 +1,239 @@
+//! Probability densities over directions, for importance sampling.
+//!
+//! Every density is per unit solid angle, and `generate` need not return
+//! unit vectors.
+
+use std::f64::consts::PI;
+
+use crate::{
+    hittable::Hittable,
+    util::{Rng, random_f64},
+    vec3::{Point3, Vec3},
+};
+
+pub trait Pdf {
+    /// Density of `generate` picking `direction`.
+    fn value(&self, direction: Vec3) -> f64;
+    fn generate(&self, rng: &mut Rng) -> Vec3;
+}
+
+/// Proportional to the cosine of the angle to `normal` over its hemisphere,
+/// the way a Lambertian surface scatters.
+pub struct CosinePdf {
+    normal: Vec3,
+}
+
+impl CosinePdf {
+    pub fn new(normal: Vec3) -> Self {
+        Self {
+            normal: normal.unit_vector(),
+        }
+    }
+}
+
+impl Pdf for CosinePdf {
+    fn value(&self, direction: Vec3) -> f64 {
+        let cos_theta = Vec3::dot(self.normal, direction.unit_vector());
+        cos_theta.max(0.0) / PI
+    }
+
+    fn generate(&self, rng: &mut Rng) -> Vec3 {
+        // Points on the unit sphere resting on the surface are spread over
+        // directions just like this.
+        let direction = self.normal + Vec3::random_unit_vector(rng);
+        if direction.near_zero() {
+            self.normal
+        } else {
+            direction
+        }
+    }
+}
+
+/// The same density in every direction: 1 / 4pi per steradian.
+pub struct SpherePdf;
+
+impl Pdf for SpherePdf {
+    fn value(&self, _direction: Vec3) -> f64 {
+        1.0 / (4.0 * PI)
+    }
+
+    fn generate(&self, rng: &mut Rng) -> Vec3 {
+        Vec3::random_unit_vector(rng)
+    }
+}
+
+/// Directions from `origin` towards points on `objects`, typically the
+/// scene's lights.
+pub struct HittablePdf<'a> {
+    objects: &'a dyn Hittable,
+    origin: Point3,
+}
+
+impl<'a> HittablePdf<'a> {
+    pub fn new(objects: &'a dyn Hittable, origin: Point3) -> Self {
+        Self { objects, origin }
+    }
+}
+
+impl Pdf for HittablePdf<'_> {
+    fn value(&self, direction: Vec3) -> f64 {
+        self.objects.pdf_value(self.origin, direction)
+    }
+
+    fn generate(&self, rng: &mut Rng) -> Vec3 {
+        self.objects.random(self.origin, rng)
+    }
+}
+
+/// Draws from either of two densities with equal chance.
+pub struct MixturePdf<'a> {
+    pdfs: [&'a dyn Pdf; 2],
+}
+
+impl<'a> MixturePdf<'a> {
+    pub fn new(a: &'a dyn Pdf, b: &'a dyn Pdf) -> Self {
+        Self { pdfs: [a, b] }
+    }
+}
+
+impl Pdf for MixturePdf<'_> {
+    fn value(&self, direction: Vec3) -> f64 {
+        0.5 * self.pdfs[0].value(direction) + 0.5 * self.pdfs[1].value(direction)
+    }
+
+    fn generate(&self, rng: &mut Rng) -> Vec3 {
+        if random_f64(rng) < 0.5 {
+            self.pdfs[0].generate(rng)
+        } else {
+            self.pdfs[1].generate(rng)
+        }
+    }
+}
+
+/// How multiple importance sampling shares out a direction that two
+/// strategies, such as sampling the material and sampling the lights, could
+/// both have picked.
+///
+/// Each strategy's sample is weighted so the weights of the two sum to one:
+/// every direction is still counted once in all, but mostly by the strategy
+/// more likely to find it, which keeps rare, huge samples from either one
+/// from turning into fireflies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Heuristic {
+    /// No sharing: shadow rays alone count the lights, and bounces that
+    /// reach one ignore it. Noisy where materials are glossy; for comparison.
+    Off,
+    /// In proportion to the densities.
+    Balance,
+    /// In proportion to the squared densities, which favors the better
+    /// strategy more strongly and is usually a little less noisy.
+    #[default]
+    Power,
+}
+
+impl Heuristic {
+    /// Weight of a shadow ray towards a light, drawn with density
+    /// `light_pdf`, where the bounce would have picked it with density
+    /// `scattering_pdf`.
+    pub fn light_weight(self, light_pdf: f64, scattering_pdf: f64) -> f64 {
+        match self {
+            Heuristic::Off => 1.0,
+            _ => self.weight(light_pdf, scattering_pdf),
+        }
+    }
+
+    /// Weight of a bounce that reached a light, the other way round.
+    pub fn bounce_weight(self, scattering_pdf: f64, light_pdf: f64) -> f64 {
+        match self {
+            Heuristic::Off => 0.0,
+            _ => self.weight(scattering_pdf, light_pdf),
+        }
+    }
+
+    /// Weight of a sample drawn with density `pdf` by one strategy, where
+    /// the other would have drawn it with density `other`.
+    fn weight(self, pdf: f64, other: f64) -> f64 {
+        let (f, g) = match self {
+            Heuristic::Off | Heuristic::Balance => (pdf, other),
+            Heuristic::Power => (pdf * pdf, other * other),
+        };
+        if f + g > 0.0 { f / (f + g) } else { 0.0 }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{color::Color, material::Lambertian, quad::Quad};
+
+    /// Monte Carlo estimate of the density's integral over all directions.
+    fn integral(pdf: &dyn Pdf, rng: &mut Rng) -> f64 {
+        let trials = 50_000;
+        let total: f64 = (0..trials)
+            .map(|_| pdf.value(Vec3::random_unit_vector(rng)))
+            .sum();
+        4.0 * PI * total / trials as f64
+    }
+
+    #[test]
+    fn test_densities_integrate_to_one() {
+        let mut rng = Rng::with_seed(2);
+        let cosine = CosinePdf::new(Vec3::new(0.0, 2.0, 1.0));
+        let light = Quad::new(
+            Point3::new(-1.0, 1.0, -1.0),
+            Vec3::new(2.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, 2.0),
+            Lambertian::new(Color::new(1.0, 1.0, 1.0)),
+        );
+        let hittable = HittablePdf::new(&light, Point3::origin());
+        let mixture = MixturePdf::new(&cosine, &hittable);
+        let pdfs: [(&str, &dyn Pdf); 4] = [
+            ("cosine", &cosine),
+            ("sphere", &SpherePdf),
+            ("hittable", &hittable),
+            ("mixture", &mixture),
+        ];
+        for (name, pdf) in pdfs {
+            let integral = integral(pdf, &mut rng);
+            assert!((integral - 1.0).abs() < 0.03, "{name}: {integral}");
+            // Generated directions are ones the density allows.
+            for _ in 0..100 {
+                assert!(pdf.value(pdf.generate(&mut rng)) > 0.0, "{name}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_cosine_pdf_mean() {
+        // The mean cosine to the normal of cosine-weighted directions is 2/3.
+        let mut rng = Rng::with_seed(4);
+        let normal = Vec3::new(1.0, 1.0, 0.0).unit_vector();
+        let pdf = CosinePdf::new(normal);
+        let trials = 20_000;
+        let mean: f64 = (0..trials)
+            .map(|_| Vec3::dot(normal, pdf.generate(&mut rng).unit_vector()))
+            .sum::<f64>()
+            / trials as f64;
+        assert!((mean - 2.0 / 3.0).abs() < 0.01, "{mean}");
+    }
+
+    #[test]
+    fn test_heuristics() {
+        assert_eq!(Heuristic::Balance.weight(3.0, 1.0), 0.75);
+        assert_eq!(Heuristic::Power.weight(3.0, 1.0), 0.9);
+        for heuristic in [Heuristic::Balance, Heuristic::Power] {
+            let sum = heuristic.weight(0.2, 5.0) + heuristic.weight(5.0, 0.2);
+            assert!((sum - 1.0).abs() < 1e-12);
+            // Only one strategy could have picked it.
+            assert_eq!(heuristic.weight(2.0, 0.0), 1.0);
+            assert_eq!(heuristic.weight(0.0, 0.0), 0.0);
+            assert_eq!(
+                heuristic.light_weight(3.0, 1.0) + heuristic.bounce_weight(1.0, 3.0),
+                1.0
+            );
+        }
+        // Without sharing the shadow ray takes all the credit.
+        assert_eq!(Heuristic::Off.light_weight(0.1, 9.0), 1.0);
+        assert_eq!(Heuristic::Off.bounce_weight(9.0, 0.1), 0.0);
+    }
+}
@@ -0,0 +1,118 @@
+//! Gradient (Perlin) noise.
+
+use crate::{
+    util::Rng,
+    vec3::{Point3, Vec3},
+};
+
+const POINT_COUNT: usize = 256;
+
+/// Lattice of random unit gradients, hashed by three permutation tables.
+pub struct Perlin {
+    gradients: Vec<Vec3>,
+    perm_x: Vec<usize>,
+    perm_y: Vec<usize>,
+    perm_z: Vec<usize>,
+}
+
+impl Perlin {
+    /// Equal seeds give identical noise.
+    pub fn new(seed: u64) -> Self {
+        let mut rng = Rng::with_seed(seed);
+        let gradients = (0..POINT_COUNT)
+            .map(|_| Vec3::random_range(&mut rng, -1.0, 1.0).unit_vector())
+            .collect();
+        let mut permutation = || {
+            let mut perm: Vec<usize> = (0..POINT_COUNT).collect();
+            rng.shuffle(&mut perm);
+            perm
+        };
+        let (perm_x, perm_y, perm_z) = (permutation(), permutation(), permutation());
+        Self {
+            gradients,
+            perm_x,
+            perm_y,
+            perm_z,
+        }
+    }
+
+    /// Smooth noise in about `[-1, 1]`, zero at every lattice point.
+    pub fn noise(&self, p: Point3) -> f64 {
+        let (u, v, w) = (p.x - p.x.floor(), p.y - p.y.floor(), p.z - p.z.floor());
+        let (i, j, k) = (p.x.floor() as i64, p.y.floor() as i64, p.z.floor() as i64);
+
+        let mut corners = [[[Vec3::zero(); 2]; 2]; 2];
+        for (di, plane) in corners.iter_mut().enumerate() {
+            for (dj, row) in plane.iter_mut().enumerate() {
+                for (dk, corner) in row.iter_mut().enumerate() {
+                    let index = self.perm_x[((i + di as i64) & 255) as usize]
+                        ^ self.perm_y[((j + dj as i64) & 255) as usize]
+                        ^ self.perm_z[((k + dk as i64) & 255) as usize];
+                    *corner = self.gradients[index];
+                }
+            }
+        }
+        interpolate(&corners, u, v, w)
+    }
+
+    /// Sum of `octaves` layers of noise, each at twice the frequency and half
+    /// the amplitude of the previous one; always non-negative.
+    pub fn turbulence(&self, p: Point3, octaves: usize) -> f64 {
+        let mut accum = 0.0;
+        let mut p = p;
+        let mut weight = 1.0;
+        for _ in 0..octaves {
+            accum += weight * self.noise(p);
+            weight *= 0.5;
+            p = 2.0 * p;
+        }
+        accum.abs()
+    }
+}
+
+/// Trilinear blend of the corner gradients' contributions, Hermite smoothed.
+fn interpolate(corners: &[[[Vec3; 2]; 2]; 2], u: f64, v: f64, w: f64) -> f64 {
+    let (uu, vv, ww) = (
+        u * u * (3.0 - 2.0 * u),
+        v * v * (3.0 - 2.0 * v),
+        w * w * (3.0 - 2.0 * w),
+    );
+    let mut accum = 0.0;
+    for (i, plane) in corners.iter().enumerate() {
+        for (j, row) in plane.iter().enumerate() {
+            for (k, &gradient) in row.iter().enumerate() {
+                let (fi, fj, fk) = (i as f64, j as f64, k as f64);
+                let weight = Vec3::new(u - fi, v - fj, w - fk);
+                accum += (fi * uu + (1.0 - fi) * (1.0 - uu))
+                    * (fj * vv + (1.0 - fj) * (1.0 - vv))
+                    * (fk * ww + (1.0 - fk) * (1.0 - ww))
+                    * Vec3::dot(gradient, weight);
+            }
+        }
+    }
+    accum
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_noise() {
+        let perlin = Perlin::new(1);
+        assert_eq!(perlin.noise(Point3::new(3.0, -2.0, 7.0)), 0.0);
+        let p = Point3::new(0.3, 1.7, -4.2);
+        assert_eq!(perlin.noise(p), Perlin::new(1).noise(p));
+        assert_ne!(perlin.noise(p), Perlin::new(2).noise(p));
+
+        let mut rng = Rng::with_seed(5);
+        for _ in 0..1000 {
+            let p = Point3::random_range(&mut rng, -50.0, 50.0);
+            assert!(perlin.noise(p).abs() <= 1.0);
+            assert!(perlin.turbulence(p, 7) >= 0.0);
+            // Continuous: nearby points give nearby values.
+            let q = p + Vec3::new(1e-6, 0.0, 0.0);
+            assert!((perlin.noise(p) - perlin.noise(q)).abs() < 1e-5);
+        }
+    }
+}
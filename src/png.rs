@@ -0,0 +1,611 @@
+use std::io::{self, Read, Write};
+
+use crate::{
+    color::{Color, channel_to_u8, channel_to_u16},
+    deflate::{zlib_compress, zlib_decompress},
+    framebuffer::{Aov, Framebuffer},
+    output::ImageWriter,
+};
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BitDepth {
+    #[default]
+    Eight,
+    Sixteen,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PngOptions {
+    pub bit_depth: BitDepth,
+    /// Adds an alpha channel holding the coverage of each pixel.
+    pub alpha: bool,
+}
+
+impl ImageWriter for PngOptions {
+    fn write(&self, out: &mut dyn Write, image: &Framebuffer) -> io::Result<()> {
+        write_png(out, image, *self)
+    }
+}
+
+/// Encodes the framebuffer as a PNG image, gamma encoded like `write_color`.
+pub fn write_png(mut out: impl Write, image: &Framebuffer, options: PngOptions) -> io::Result<()> {
+    let channels = if options.alpha { 4 } else { 3 };
+    let (depth, bytes_per_channel) = match options.bit_depth {
+        BitDepth::Eight => (8, 1),
+        BitDepth::Sixteen => (16, 2),
+    };
+    let color_type = if options.alpha { 6 } else { 2 };
+
+    out.write_all(&SIGNATURE)?;
+
+    let mut ihdr = Vec::with_capacity(13);
+    ihdr.extend((image.width() as u32).to_be_bytes());
+    ihdr.extend((image.height() as u32).to_be_bytes());
+    // Bit depth, color type, deflate compression, adaptive filtering, no interlace.
+    ihdr.extend([depth, color_type, 0, 0, 0]);
+    write_chunk(&mut out, b"IHDR", &ihdr)?;
+
+    // Samples are encoded with gamma 1/2, see `linear_to_gamma`.
+    write_chunk(&mut out, b"gAMA", &50_000u32.to_be_bytes())?;
+
+    let bpp = channels * bytes_per_channel;
+    let stride = image.width() * bpp;
+    let mut filtered = Vec::with_capacity((stride + 1) * image.height());
+    let mut previous = vec![0u8; stride];
+    let mut current = Vec::with_capacity(stride);
+    for y in 0..image.height() {
+        current.clear();
+        for (&pixel, aov) in image.row(y).iter().zip(image.aov_row(y)) {
+            encode_pixel(&mut current, pixel, aov.alpha, options);
+        }
+        filter_row(&mut filtered, &current, &previous, bpp);
+        std::mem::swap(&mut current, &mut previous);
+    }
+    write_chunk(&mut out, b"IDAT", &zlib_compress(&filtered))?;
+    write_chunk(&mut out, b"IEND", &[])
+}
+
+/// Alpha is linear coverage, so unlike the colors it is not gamma encoded.
+fn encode_pixel(row: &mut Vec<u8>, pixel: Color, alpha: f64, options: PngOptions) {
+    match options.bit_depth {
+        BitDepth::Eight => {
+            row.extend([pixel.r, pixel.g, pixel.b].map(channel_to_u8));
+            if options.alpha {
+                row.push((alpha.clamp(0.0, 1.0) * 255.0).round() as u8);
+            }
+        }
+        BitDepth::Sixteen => {
+            for channel in [pixel.r, pixel.g, pixel.b] {
+                row.extend(channel_to_u16(channel).to_be_bytes());
+            }
+            if options.alpha {
+                row.extend(((alpha.clamp(0.0, 1.0) * 65535.0).round() as u16).to_be_bytes());
+            }
+        }
+    }
+}
+
+/// Appends the row with the filter type that minimises the sum of absolute
+/// differences, the heuristic recommended by the PNG specification.
+fn filter_row(out: &mut Vec<u8>, row: &[u8], previous: &[u8], bpp: usize) {
+    let mut best: Option<(u64, u8, Vec<u8>)> = None;
+    for filter in 0..5u8 {
+        let candidate: Vec<u8> = (0..row.len())
+            .map(|i| {
+                let a = if i >= bpp { row[i - bpp] } else { 0 };
+                let b = previous[i];
+                let c = if i >= bpp { previous[i - bpp] } else { 0 };
+                let predictor = match filter {
+                    0 => 0,
+                    1 => a,
+                    2 => b,
+                    3 => ((a as u16 + b as u16) / 2) as u8,
+                    _ => paeth(a, b, c),
+                };
+                row[i].wrapping_sub(predictor)
+            })
+            .collect();
+        let cost = candidate
+            .iter()
+            .map(|&v| (v as i8).unsigned_abs() as u64)
+            .sum();
+        if best
+            .as_ref()
+            .is_none_or(|(best_cost, _, _)| cost < *best_cost)
+        {
+            best = Some((cost, filter, candidate));
+        }
+    }
+    let (_, filter, bytes) = best.unwrap();
+    out.push(filter);
+    out.extend(bytes);
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let pa = (p - a as i16).abs();
+    let pb = (p - b as i16).abs();
+    let pc = (p - c as i16).abs();
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+fn write_chunk(mut out: impl Write, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    out.write_all(&(data.len() as u32).to_be_bytes())?;
+    out.write_all(kind)?;
+    out.write_all(data)?;
+    let mut crc = Crc32::new();
+    crc.update(kind);
+    crc.update(data);
+    out.write_all(&crc.finish().to_be_bytes())
+}
+
+/// Starting column and row, then column and row steps of the seven Adam7
+/// passes.
+const ADAM7: [(usize, usize, usize, usize); 7] = [
+    (0, 0, 8, 8),
+    (4, 0, 8, 8),
+    (0, 4, 4, 8),
+    (2, 0, 4, 4),
+    (0, 2, 2, 4),
+    (1, 0, 2, 2),
+    (0, 1, 1, 2),
+];
+
+/// Decodes a PNG image into linear colors, its alpha becoming the coverage
+/// pass.
+///
+/// Samples are linearized with the gamma of the `gAMA` chunk, or 2.2 when
+/// there is none. Every bit depth, color type and Adam7 interlacing are
+/// supported.
+pub fn read_png(mut input: impl Read) -> io::Result<Framebuffer> {
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message);
+    let mut data = Vec::new();
+    input.read_to_end(&mut data)?;
+    if !data.starts_with(&SIGNATURE) {
+        return Err(invalid("not a PNG file"));
+    }
+
+    let mut header = None;
+    let mut palette: &[u8] = &[];
+    let mut transparency: &[u8] = &[];
+    let mut exponent = 2.2;
+    let mut compressed = Vec::new();
+    let mut rest = &data[8..];
+    loop {
+        if rest.len() < 12 {
+            return Err(invalid("truncated PNG file"));
+        }
+        let len = u32::from_be_bytes(rest[..4].try_into().unwrap()) as usize;
+        let kind: [u8; 4] = rest[4..8].try_into().unwrap();
+        let body = rest
+            .get(8..8 + len)
+            .ok_or_else(|| invalid("truncated PNG chunk"))?;
+        let crc = &rest[8 + len..];
+        let crc = crc.get(..4).ok_or_else(|| invalid("truncated PNG chunk"))?;
+        if u32::from_be_bytes(crc.try_into().unwrap()) != crc32(&rest[4..8 + len]) {
+            return Err(invalid("PNG chunk checksum mismatch"));
+        }
+        rest = &rest[12 + len..];
+        match &kind {
+            b"IHDR" => header = Some(PngHeader::parse(body)?),
+            b"PLTE" => palette = body,
+            b"tRNS" => transparency = body,
+            b"gAMA" if len == 4 => {
+                let gamma = u32::from_be_bytes(body.try_into().unwrap());
+                if gamma > 0 {
+                    exponent = 100_000.0 / gamma as f64;
+                }
+            }
+            b"IDAT" => compressed.extend_from_slice(body),
+            b"IEND" => break,
+            // Ancillary chunks have a lowercase first letter and may be skipped.
+            _ if kind[0].is_ascii_uppercase() => {
+                return Err(invalid("unsupported critical PNG chunk"));
+            }
+            _ => {}
+        }
+    }
+    let header = header.ok_or_else(|| invalid("PNG file without an IHDR chunk"))?;
+    // Inflating stops at the size the header gives, so neither a huge
+    // claimed image nor a small file expanding to gigabytes can exhaust
+    // memory.
+    let size = header
+        .data_len()
+        .ok_or_else(|| invalid("PNG image is too large"))?;
+    let raw = zlib_decompress(&compressed, size)?;
+    if raw.len() != size {
+        return Err(invalid("PNG image data does not match its size"));
+    }
+
+    let mut image = Framebuffer::new(header.width, header.height);
+    let bpp = header.bits_per_pixel().div_ceil(8);
+    let mut raw = &raw[..];
+    for (x0, y0, dx, dy, columns, rows) in header.passes() {
+        let stride = (columns * header.bits_per_pixel()).div_ceil(8);
+        let mut previous = vec![0u8; stride];
+        for row in 0..rows {
+            let (line, remaining) = raw
+                .split_at_checked(stride + 1)
+                .ok_or_else(|| invalid("truncated PNG image data"))?;
+            raw = remaining;
+            let mut current = line[1..].to_vec();
+            unfilter(line[0], &mut current, &previous, bpp)?;
+            for column in 0..columns {
+                let (color, alpha) =
+                    header.pixel(&current, column, palette, transparency, exponent)?;
+                let (x, y) = (x0 + column * dx, y0 + row * dy);
+                image.set(x, y, color);
+                image.set_aov(
+                    x,
+                    y,
+                    Aov {
+                        alpha,
+                        ..Aov::default()
+                    },
+                );
+            }
+            previous = current;
+        }
+    }
+    Ok(image)
+}
+
+struct PngHeader {
+    width: usize,
+    height: usize,
+    bit_depth: u8,
+    color_type: u8,
+    interlaced: bool,
+}
+
+impl PngHeader {
+    fn parse(body: &[u8]) -> io::Result<Self> {
+        let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message);
+        let body: &[u8; 13] = body.try_into().map_err(|_| invalid("invalid IHDR chunk"))?;
+        let width = u32::from_be_bytes(body[..4].try_into().unwrap()) as usize;
+        let height = u32::from_be_bytes(body[4..8].try_into().unwrap()) as usize;
+        let [bit_depth, color_type, compression, filter, interlace] = body[8..].try_into().unwrap();
+        let depths: &[u8] = match color_type {
+            0 => &[1, 2, 4, 8, 16],
+            3 => &[1, 2, 4, 8],
+            2 | 4 | 6 => &[8, 16],
+            _ => return Err(invalid("invalid PNG color type")),
+        };
+        if !depths.contains(&bit_depth) {
+            return Err(invalid("invalid PNG bit depth for its color type"));
+        }
+        if width == 0 || height == 0 || compression != 0 || filter != 0 || interlace > 1 {
+            return Err(invalid("invalid IHDR chunk"));
+        }
+        Ok(Self {
+            width,
+            height,
+            bit_depth,
+            color_type,
+            interlaced: interlace == 1,
+        })
+    }
+
+    fn bits_per_pixel(&self) -> usize {
+        self.channels() * self.bit_depth as usize
+    }
+
+    /// Each non-empty pass with its columns and rows.
+    fn passes(&self) -> impl Iterator<Item = (usize, usize, usize, usize, usize, usize)> {
+        let passes: &[_] = if self.interlaced {
+            &ADAM7
+        } else {
+            &[(0, 0, 1, 1)]
+        };
+        let (width, height) = (self.width, self.height);
+        passes.iter().filter_map(move |&(x0, y0, dx, dy)| {
+            let columns = (width + dx - 1 - x0) / dx;
+            let rows = (height + dy - 1 - y0) / dy;
+            (columns > 0 && rows > 0).then_some((x0, y0, dx, dy, columns, rows))
+        })
+    }
+
+    /// Bytes of filtered scanlines the image holds, `None` if too many to
+    /// count.
+    fn data_len(&self) -> Option<usize> {
+        self.passes()
+            .try_fold(0usize, |total, (_, _, _, _, columns, rows)| {
+                let stride = columns.checked_mul(self.bits_per_pixel())?.div_ceil(8);
+                total.checked_add(stride.checked_add(1)?.checked_mul(rows)?)
+            })
+    }
+
+    fn channels(&self) -> usize {
+        match self.color_type {
+            2 => 3,
+            4 => 2,
+            6 => 4,
+            _ => 1,
+        }
+    }
+
+    /// Sample `index` of an unfiltered row.
+    fn sample(&self, row: &[u8], index: usize) -> u16 {
+        match self.bit_depth {
+            16 => u16::from_be_bytes([row[2 * index], row[2 * index + 1]]),
+            8 => row[index] as u16,
+            depth => {
+                let bit = index * depth as usize;
+                let shift = 8 - depth as usize - bit % 8;
+                ((row[bit / 8] >> shift) & ((1 << depth) - 1)) as u16
+            }
+        }
+    }
+
+    fn pixel(
+        &self,
+        row: &[u8],
+        column: usize,
+        palette: &[u8],
+        transparency: &[u8],
+        exponent: f64,
+    ) -> io::Result<(Color, f64)> {
+        let channels = self.channels();
+        let samples: Vec<u16> = (0..channels)
+            .map(|c| self.sample(row, column * channels + c))
+            .collect();
+        let max = ((1u32 << self.bit_depth) - 1) as f64;
+        let linear = |v: u16, max: f64| (v as f64 / max).powf(exponent);
+        // A tRNS chunk names one fully transparent gray or RGB value.
+        let keyed = |samples: &[u16]| {
+            let key: Vec<u16> = transparency
+                .chunks_exact(2)
+                .map(|b| u16::from_be_bytes([b[0], b[1]]))
+                .collect();
+            if key == samples { 0.0 } else { 1.0 }
+        };
+        Ok(match self.color_type {
+            0 => {
+                let g = linear(samples[0], max);
+                (Color::new(g, g, g), keyed(&samples))
+            }
+            2 => {
+                let [r, g, b] = [0, 1, 2].map(|c| linear(samples[c], max));
+                (Color::new(r, g, b), keyed(&samples))
+            }
+            3 => {
+                let index = samples[0] as usize;
+                let rgb = palette.get(3 * index..3 * index + 3).ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::InvalidData, "bad palette index")
+                })?;
+                let [r, g, b] = [0, 1, 2].map(|c| linear(rgb[c] as u16, 255.0));
+                let alpha = transparency.get(index).map_or(1.0, |&a| a as f64 / 255.0);
+                (Color::new(r, g, b), alpha)
+            }
+            4 => {
+                let g = linear(samples[0], max);
+                (Color::new(g, g, g), samples[1] as f64 / max)
+            }
+            _ => {
+                let [r, g, b] = [0, 1, 2].map(|c| linear(samples[c], max));
+                (Color::new(r, g, b), samples[3] as f64 / max)
+            }
+        })
+    }
+}
+
+/// Undoes the per-row filter in place, see `filter_row`.
+fn unfilter(filter: u8, row: &mut [u8], previous: &[u8], bpp: usize) -> io::Result<()> {
+    if filter > 4 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "invalid PNG filter type",
+        ));
+    }
+    for i in 0..row.len() {
+        let a = if i >= bpp { row[i - bpp] } else { 0 };
+        let b = previous[i];
+        let c = if i >= bpp { previous[i - bpp] } else { 0 };
+        let predictor = match filter {
+            0 => 0,
+            1 => a,
+            2 => b,
+            3 => ((a as u16 + b as u16) / 2) as u8,
+            _ => paeth(a, b, c),
+        };
+        row[i] = row[i].wrapping_add(predictor);
+    }
+    Ok(())
+}
+
+/// CRC-32 as used by PNG chunks (ISO 3309, reflected polynomial 0xEDB88320).
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = Crc32::new();
+    crc.update(data);
+    crc.finish()
+}
+
+struct Crc32 {
+    table: [u32; 256],
+    value: u32,
+}
+
+impl Crc32 {
+    fn new() -> Self {
+        let mut table = [0u32; 256];
+        for (n, entry) in table.iter_mut().enumerate() {
+            let mut c = n as u32;
+            for _ in 0..8 {
+                c = if c & 1 != 0 {
+                    0xedb8_8320 ^ (c >> 1)
+                } else {
+                    c >> 1
+                };
+            }
+            *entry = c;
+        }
+        Self {
+            table,
+            value: 0xffff_ffff,
+        }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        for &byte in data {
+            self.value =
+                self.table[((self.value ^ byte as u32) & 0xff) as usize] ^ (self.value >> 8);
+        }
+    }
+
+    fn finish(&self) -> u32 {
+        self.value ^ 0xffff_ffff
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+        assert_eq!(crc32(b"IEND"), 0xae42_6082);
+    }
+
+    #[test]
+    fn test_png_layout() {
+        let mut image = Framebuffer::new(3, 2);
+        image.set(1, 1, Color::new(1.0, 0.25, 0.0));
+        let mut out = Vec::new();
+        let options = PngOptions {
+            bit_depth: BitDepth::Sixteen,
+            alpha: true,
+        };
+        write_png(&mut out, &image, options).unwrap();
+
+        assert_eq!(out[..8], SIGNATURE);
+        assert_eq!(out[12..16], *b"IHDR");
+        assert_eq!(out[16..20], 3u32.to_be_bytes());
+        assert_eq!(out[20..24], 2u32.to_be_bytes());
+        assert_eq!(out[24..26], [16, 6]);
+        assert_eq!(
+            out[out.len() - 12..],
+            [0, 0, 0, 0, b'I', b'E', b'N', b'D', 0xae, 0x42, 0x60, 0x82]
+        );
+    }
+
+    fn png_file(ihdr: [u8; 13], chunks: &[(&[u8; 4], &[u8])], raw: &[u8]) -> Vec<u8> {
+        let mut out = SIGNATURE.to_vec();
+        write_chunk(&mut out, b"IHDR", &ihdr).unwrap();
+        for (kind, data) in chunks {
+            write_chunk(&mut out, kind, data).unwrap();
+        }
+        write_chunk(&mut out, b"IDAT", &zlib_compress(raw)).unwrap();
+        write_chunk(&mut out, b"IEND", &[]).unwrap();
+        out
+    }
+
+    #[test]
+    fn test_png_round_trip() {
+        let mut image = Framebuffer::new(5, 4);
+        for y in 0..4 {
+            for x in 0..5 {
+                let value = (x + 5 * y) as f64 / 20.0;
+                image.set(x, y, Color::new(value, 1.0 - value, 0.5));
+                image.set_aov(
+                    x,
+                    y,
+                    Aov {
+                        alpha: value,
+                        ..Aov::default()
+                    },
+                );
+            }
+        }
+        for bit_depth in [BitDepth::Eight, BitDepth::Sixteen] {
+            let mut out = Vec::new();
+            let options = PngOptions {
+                bit_depth,
+                alpha: true,
+            };
+            write_png(&mut out, &image, options).unwrap();
+            let decoded = read_png(&out[..]).unwrap();
+            for (a, b) in image.pixels().iter().zip(decoded.pixels()) {
+                assert!((a.r - b.r).abs() < 0.01 && (a.g - b.g).abs() < 0.01);
+            }
+            for (a, b) in image.aovs().iter().zip(decoded.aovs()) {
+                assert!((a.alpha - b.alpha).abs() < 0.003);
+            }
+        }
+    }
+
+    #[test]
+    fn test_read_palette_and_interlaced() {
+        // 3x1, 2-bit palette indices 2, 0, 1 with the first entry transparent.
+        let ihdr = [0, 0, 0, 3, 0, 0, 0, 1, 2, 3, 0, 0, 0];
+        let palette = [255, 0, 0, 0, 255, 0, 0, 0, 255];
+        let file = png_file(
+            ihdr,
+            &[
+                (b"PLTE", &palette),
+                (b"tRNS", &[0]),
+                (b"gAMA", &[0, 0, 0xc3, 0x50]),
+            ],
+            &[0, 0b10_00_01_00],
+        );
+        let image = read_png(&file[..]).unwrap();
+        assert_eq!(image.get(0, 0), Color::new(0.0, 0.0, 1.0));
+        assert_eq!(image.get(1, 0), Color::new(1.0, 0.0, 0.0));
+        assert_eq!(image.aov(1, 0).alpha, 0.0);
+        assert_eq!(image.aov(2, 0).alpha, 1.0);
+
+        // 3x3 8-bit gray, Adam7: passes 2 and 3 are empty.
+        let ihdr = [0, 0, 0, 3, 0, 0, 0, 3, 8, 0, 0, 0, 1];
+        let raw = [
+            0, 0, // pass 1: (0, 0)
+            0, 20, // pass 4: (2, 0)
+            0, 40, 60, // pass 5: (0, 2), (2, 2)
+            0, 80, // pass 6: (1, 0)
+            0, 100, // pass 6: (1, 2)
+            0, 120, 140, 160, // pass 7: row 1
+        ];
+        let file = png_file(ihdr, &[(b"gAMA", &[0, 1, 0x86, 0xa0])], &raw);
+        let image = read_png(&file[..]).unwrap();
+        let gray = |x, y| (image.get(x, y).r * 255.0).round() as u8;
+        assert_eq!(
+            [0, 1, 2].map(|y| [0, 1, 2].map(|x| gray(x, y))),
+            [[0, 80, 20], [120, 140, 160], [40, 100, 60]]
+        );
+
+        let mut corrupt = file.clone();
+        corrupt[20] ^= 1;
+        assert!(read_png(&corrupt[..]).is_err());
+    }
+
+    #[test]
+    fn test_read_size_mismatch() {
+        // A tiny file claiming 100000x100000 pixels is refused before the
+        // image is allocated, as is data longer than the image.
+        let huge = [0, 1, 0x86, 0xa0, 0, 1, 0x86, 0xa0, 8, 2, 0, 0, 0];
+        let err = read_png(&png_file(huge, &[], &[0, 1, 2, 3])[..]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(err.to_string(), "PNG image data does not match its size");
+        let gray = [0, 0, 0, 1, 0, 0, 0, 1, 8, 0, 0, 0, 0];
+        assert!(read_png(&png_file(gray, &[], &[0, 7])[..]).is_ok());
+        assert!(read_png(&png_file(gray, &[], &[0, 7, 0])[..]).is_err());
+    }
+
+    #[test]
+    fn test_read_stops_inflating_at_image_size() {
+        // A few kilobytes of IDAT that would inflate to 10 MB, for one pixel.
+        let gray = [0, 0, 0, 1, 0, 0, 0, 1, 8, 0, 0, 0, 0];
+        let bomb = png_file(gray, &[], &vec![0; 10_000_000]);
+        assert!(bomb.len() < 100_000);
+        let err = read_png(&bomb[..]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(err.to_string(), "DEFLATE data is longer than expected");
+    }
+}
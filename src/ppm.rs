@@ -0,0 +1,292 @@
+use std::io::{self, Read, Write};
+
+use crate::{
+    color::{Color, channel_to_u8, gamma_to_linear, write_color},
+    framebuffer::Framebuffer,
+    output::ImageWriter,
+};
+
+/// ASCII PPM, see [`write_p3`].
+pub struct P3;
+
+/// Binary PPM, see [`write_p6`].
+pub struct P6;
+
+/// Binary PGM of one pass, see [`write_pgm`].
+pub struct Pgm(pub GrayPass);
+
+/// Single channel views of a framebuffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GrayPass {
+    /// Rec. 709 luminance of the beauty pass, gamma encoded like the colors.
+    Luminance,
+    /// Pixel coverage.
+    Alpha,
+    /// Distance to the camera as 16-bit samples, white for the nearest hit
+    /// and black where nothing was hit.
+    Depth,
+}
+
+impl ImageWriter for P3 {
+    fn write(&self, out: &mut dyn Write, image: &Framebuffer) -> io::Result<()> {
+        write_p3(out, image)
+    }
+}
+
+impl ImageWriter for P6 {
+    fn write(&self, out: &mut dyn Write, image: &Framebuffer) -> io::Result<()> {
+        write_p6(out, image)
+    }
+}
+
+impl ImageWriter for Pgm {
+    fn write(&self, out: &mut dyn Write, image: &Framebuffer) -> io::Result<()> {
+        write_pgm(out, image, self.0)
+    }
+}
+
+/// Encodes the framebuffer as an ASCII (P3) PPM image.
+pub fn write_p3(mut out: impl Write, image: &Framebuffer) -> io::Result<()> {
+    writeln!(out, "P3\n{} {}\n255", image.width(), image.height())?;
+    for &pixel in image.pixels() {
+        write_color(&mut out, pixel)?;
+    }
+    Ok(())
+}
+
+/// Encodes the framebuffer as a binary (P6) PPM image.
+pub fn write_p6(mut out: impl Write, image: &Framebuffer) -> io::Result<()> {
+    writeln!(out, "P6\n{} {}\n255", image.width(), image.height())?;
+    let bytes: Vec<u8> = image
+        .pixels()
+        .iter()
+        .flat_map(|pixel| [pixel.r, pixel.g, pixel.b].map(channel_to_u8))
+        .collect();
+    out.write_all(&bytes)
+}
+
+/// Encodes one pass of the framebuffer as a binary (P5) PGM image.
+pub fn write_pgm(mut out: impl Write, image: &Framebuffer, pass: GrayPass) -> io::Result<()> {
+    let max = if pass == GrayPass::Depth { 65535 } else { 255 };
+    writeln!(out, "P5\n{} {}\n{max}", image.width(), image.height())?;
+    let bytes: Vec<u8> = match pass {
+        GrayPass::Luminance => image
+            .pixels()
+            .iter()
+            .map(|p| channel_to_u8(0.2126 * p.r + 0.7152 * p.g + 0.0722 * p.b))
+            .collect(),
+        GrayPass::Alpha => image
+            .aovs()
+            .iter()
+            .map(|aov| (aov.alpha.clamp(0.0, 1.0) * 255.0).round() as u8)
+            .collect(),
+        GrayPass::Depth => {
+            let depths = image.aovs().iter().map(|aov| aov.depth);
+            let finite = depths.clone().filter(|d| d.is_finite());
+            let near = finite.clone().fold(f64::INFINITY, f64::min);
+            let far = finite.fold(f64::NEG_INFINITY, f64::max);
+            // Zero is kept for the background.
+            let scale = if far > near {
+                65534.0 / (far - near)
+            } else {
+                0.0
+            };
+            depths
+                .map(|d| match d.is_finite() {
+                    true => 65535 - ((d - near) * scale).round() as u16,
+                    false => 0,
+                })
+                .flat_map(u16::to_be_bytes)
+                .collect()
+        }
+    };
+    out.write_all(&bytes)
+}
+
+/// Decodes a PPM or PGM image, ASCII (P2, P3) or binary (P5, P6), into
+/// linear colors. Samples are assumed gamma encoded like `write_p3` does.
+pub fn read_ppm(mut input: impl Read) -> io::Result<Framebuffer> {
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message);
+    let mut data = Vec::new();
+    input.read_to_end(&mut data)?;
+
+    let mut pos = 0;
+    // Whitespace separated header fields, `#` comments running to the end of the line.
+    let field = |pos: &mut usize| -> io::Result<&[u8]> {
+        loop {
+            match data.get(*pos) {
+                Some(b'#') => {
+                    while data.get(*pos).is_some_and(|&b| b != b'\n') {
+                        *pos += 1;
+                    }
+                }
+                Some(b) if b.is_ascii_whitespace() => *pos += 1,
+                Some(_) => break,
+                None => return Err(invalid("truncated PPM file")),
+            }
+        }
+        let start = *pos;
+        while data.get(*pos).is_some_and(|b| !b.is_ascii_whitespace()) {
+            *pos += 1;
+        }
+        Ok(&data[start..*pos])
+    };
+    let magic = field(&mut pos)?;
+    let (channels, binary) = match magic {
+        b"P2" => (1, false),
+        b"P3" => (3, false),
+        b"P5" => (1, true),
+        b"P6" => (3, true),
+        _ => return Err(invalid("not a PPM or PGM file")),
+    };
+    let number = |pos: &mut usize| -> io::Result<usize> {
+        std::str::from_utf8(field(pos)?)
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .ok_or_else(|| invalid("invalid number in PPM file"))
+    };
+    let width = number(&mut pos)?;
+    let height = number(&mut pos)?;
+    let max = number(&mut pos)?;
+    if !(1..=65535).contains(&max) {
+        return Err(invalid("PPM maximum value out of range"));
+    }
+
+    let count = width
+        .checked_mul(height)
+        .filter(|&pixels| pixels > 0)
+        .and_then(|pixels| pixels.checked_mul(channels))
+        .ok_or_else(|| invalid("invalid PPM image size"))?;
+    let samples: Vec<usize> = if binary {
+        // Exactly one whitespace byte separates the header from the data.
+        let bytes = data
+            .get(pos + 1..)
+            .ok_or_else(|| invalid("truncated PPM file"))?;
+        let size = if max > 255 { 2 } else { 1 };
+        if count.checked_mul(size).is_none_or(|len| bytes.len() < len) {
+            return Err(invalid("truncated PPM file"));
+        }
+        bytes[..count * size]
+            .chunks(size)
+            .map(|b| b.iter().fold(0, |v, &b| v << 8 | b as usize))
+            .collect()
+    } else {
+        (0..count)
+            .map(|_| number(&mut pos))
+            .collect::<io::Result<_>>()?
+    };
+    if samples.iter().any(|&s| s > max) {
+        return Err(invalid("PPM sample above the maximum value"));
+    }
+
+    let mut image = Framebuffer::new(width, height);
+    let linear = |s: usize| gamma_to_linear(s as f64 / max as f64);
+    for (pixel, rgb) in image.pixels_mut().iter_mut().zip(samples.chunks(channels)) {
+        *pixel = match *rgb {
+            [r, g, b] => Color::new(linear(r), linear(g), linear(b)),
+            _ => Color::new(linear(rgb[0]), linear(rgb[0]), linear(rgb[0])),
+        };
+    }
+    Ok(image)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{color::Color, framebuffer::Aov};
+
+    #[test]
+    fn test_write_p3() {
+        let mut image = Framebuffer::new(2, 1);
+        image.set(0, 0, Color::new(1.0, 0.25, 0.0));
+        let mut out = Vec::new();
+        write_p3(&mut out, &image).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "P3\n2 1\n255\n255 128 0\n0 0 0\n"
+        );
+    }
+
+    #[test]
+    fn test_write_p6() {
+        let mut image = Framebuffer::new(2, 1);
+        image.set(0, 0, Color::new(1.0, 0.25, 0.0));
+        let mut out = Vec::new();
+        write_p6(&mut out, &image).unwrap();
+        assert_eq!(out, b"P6\n2 1\n255\n\xff\x80\0\0\0\0");
+    }
+
+    #[test]
+    fn test_write_pgm() {
+        let mut image = Framebuffer::new(3, 1);
+        image.set(0, 0, Color::new(1.0, 1.0, 1.0));
+        let depth = |depth| Aov {
+            depth,
+            ..Aov::default()
+        };
+        image.set_aov(0, 0, depth(2.0));
+        image.set_aov(1, 0, depth(4.0));
+        image.set_aov(
+            2,
+            0,
+            Aov {
+                alpha: 0.0,
+                ..Aov::default()
+            },
+        );
+
+        let mut out = Vec::new();
+        write_pgm(&mut out, &image, GrayPass::Luminance).unwrap();
+        assert_eq!(out, b"P5\n3 1\n255\n\xff\0\0");
+        out.clear();
+        write_pgm(&mut out, &image, GrayPass::Alpha).unwrap();
+        assert_eq!(out, b"P5\n3 1\n255\n\xff\xff\0");
+        out.clear();
+        write_pgm(&mut out, &image, GrayPass::Depth).unwrap();
+        assert_eq!(out, b"P5\n3 1\n65535\n\xff\xff\0\x01\0\0");
+    }
+
+    #[test]
+    fn test_read_ppm() {
+        let mut image = Framebuffer::new(2, 1);
+        image.set(0, 0, Color::new(1.0, 0.25, 0.0));
+        let writers: [&dyn ImageWriter; 2] = [&P3, &P6];
+        for writer in writers {
+            let mut out = Vec::new();
+            writer.write(&mut out, &image).unwrap();
+            let decoded = read_ppm(&out[..]).unwrap();
+            assert_eq!(decoded.width(), 2);
+            assert_eq!(
+                decoded.get(0, 0),
+                Color::new(1.0, gamma_to_linear(128.0 / 255.0), 0.0)
+            );
+        }
+
+        let gray = b"P2 # comment\n2 1\n# another\n4\n4 2\n";
+        let decoded = read_ppm(&gray[..]).unwrap();
+        assert_eq!(decoded.get(1, 0), Color::new(0.25, 0.25, 0.25));
+        let wide = b"P5 1 1 65535\n\x80\x00";
+        let decoded = read_ppm(&wide[..]).unwrap();
+        assert!((decoded.get(0, 0).r - 0.25).abs() < 1e-4);
+
+        assert!(read_ppm(&b"P6 2 2 255\n\0\0"[..]).is_err());
+        assert!(read_ppm(&b"P3 1 1 255 0 0 256"[..]).is_err());
+    }
+
+    #[test]
+    fn test_read_ppm_size() {
+        // 2^32 squared wraps to zero pixels on 64-bit targets, and a third
+        // of 2^64 pixels to a handful of samples.
+        let err = |file: &[u8]| read_ppm(file).unwrap_err().to_string();
+        assert_eq!(
+            err(b"P6 4294967296 4294967296 255\n"),
+            "invalid PPM image size"
+        );
+        assert_eq!(err(b"P5 0 3 255\n"), "invalid PPM image size");
+        assert_eq!(
+            err(b"P6 6148914691236517206 1 255\n"),
+            "invalid PPM image size"
+        );
+        assert_eq!(err(b"P5 100000 100000 255\n\0"), "truncated PPM file");
+    }
+}
@@ -0,0 +1,197 @@
+use std::sync::Arc;
+
+use crate::{
+    aabb::Aabb,
+    hittable::{HitRecord, Hittable, area_to_solid_angle},
+    hittable_list::HittableList,
+    inretval::Interval,
+    material::Material,
+    ray::Ray,
+    util::{Rng, random_f64},
+    vec3::{Point3, Vec3},
+};
+
+/// Parallelogram spanned by the edges `u` and `v` from the corner `q`.
+///
+/// The front face is the side `u x v` points to; (u, v) run from 0 to 1
+/// along the two edges.
+pub struct Quad<'a> {
+    pub q: Point3,
+    pub u: Vec3,
+    pub v: Vec3,
+    pub mat: Box<dyn Material + 'a>,
+    normal: Vec3,
+    /// Plane offset, `dot(normal, p) == d` for points on the plane.
+    d: f64,
+    /// `n / |n|^2` for `n = u x v`, turns plane points into edge coordinates.
+    w: Vec3,
+    area: f64,
+    bbox: Aabb,
+}
+
+impl<'a> Quad<'a> {
+    pub fn new(q: Point3, u: Vec3, v: Vec3, mat: impl Material + 'a) -> Self {
+        let n = Vec3::cross(u, v);
+        let normal = n.unit_vector();
+        let bbox = Aabb::surrounding(
+            Aabb::from_points(q, q + u + v),
+            Aabb::from_points(q + u, q + v),
+        );
+        Self {
+            q,
+            u,
+            v,
+            mat: Box::new(mat),
+            normal,
+            d: Vec3::dot(normal, Vec3::from(q)),
+            w: n / Vec3::dot(n, n),
+            area: n.length(),
+            bbox,
+        }
+    }
+}
+
+impl<'a> Hittable for Quad<'a> {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        let denom = Vec3::dot(self.normal, r.direction);
+        // Parallel to the plane.
+        if denom.abs() < 1e-8 {
+            return None;
+        }
+        let t = (self.d - Vec3::dot(self.normal, Vec3::from(r.origin))) / denom;
+        if !ray_t.surrounds(t) {
+            return None;
+        }
+        let p = r.at(t);
+        let planar = p - self.q;
+        let alpha = Vec3::dot(self.w, Vec3::cross(planar, self.v));
+        let beta = Vec3::dot(self.w, Vec3::cross(self.u, planar));
+        let unit = Interval::new(0.0, 1.0);
+        if !unit.contains(alpha) || !unit.contains(beta) {
+            return None;
+        }
+        let mut rec = HitRecord::new(t, p, self.normal, &*self.mat);
+        (rec.u, rec.v) = (alpha, beta);
+        rec.set_face_normal(r);
+        Some(rec)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f64 {
+        match self.hit(
+            &Ray::new(origin, direction),
+            Interval::new(0.001, f64::INFINITY),
+        ) {
+            Some(rec) => area_to_solid_angle(direction, rec.t, self.normal, self.area),
+            None => 0.0,
+        }
+    }
+
+    fn random(&self, origin: Point3, rng: &mut Rng) -> Vec3 {
+        let p = self.q + random_f64(rng) * self.u + random_f64(rng) * self.v;
+        p - origin
+    }
+}
+
+/// Axis-aligned box made of six outward facing quads.
+pub struct BoxShape<'a> {
+    sides: HittableList<'a>,
+}
+
+impl<'a> BoxShape<'a> {
+    /// The box with opposite corners `a` and `b`.
+    pub fn new(a: Point3, b: Point3, mat: impl Material + 'a) -> Self {
+        let mat = Arc::new(mat);
+        let min = Point3::new(a.x.min(b.x), a.y.min(b.y), a.z.min(b.z));
+        let max = Point3::new(a.x.max(b.x), a.y.max(b.y), a.z.max(b.z));
+        let dx = Vec3::new(max.x - min.x, 0.0, 0.0);
+        let dy = Vec3::new(0.0, max.y - min.y, 0.0);
+        let dz = Vec3::new(0.0, 0.0, max.z - min.z);
+
+        let mut sides = HittableList::new();
+        let mut side = |q, u, v| sides.add(Quad::new(q, u, v, mat.clone()));
+        side(Point3::new(min.x, min.y, max.z), dx, dy); // front
+        side(Point3::new(max.x, min.y, max.z), -dz, dy); // right
+        side(Point3::new(max.x, min.y, min.z), -dx, dy); // back
+        side(Point3::new(min.x, min.y, min.z), dz, dy); // left
+        side(Point3::new(min.x, max.y, max.z), dx, -dz); // top
+        side(Point3::new(min.x, min.y, min.z), dx, dz); // bottom
+        Self { sides }
+    }
+}
+
+impl<'a> Hittable for BoxShape<'a> {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        self.sides.hit(r, ray_t)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.sides.bounding_box()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::gray;
+
+    #[test]
+    fn test_quad_hit() {
+        let quad = Quad::new(
+            Point3::new(-1.0, -1.0, -2.0),
+            Vec3::new(2.0, 0.0, 0.0),
+            Vec3::new(0.0, 4.0, 0.0),
+            gray(),
+        );
+        let ray_t = Interval::new(0.001, f64::INFINITY);
+        let r = Ray::new(Point3::origin(), Vec3::new(0.5, 1.0, -2.0));
+        let rec = quad.hit(&r, ray_t).unwrap();
+        assert_eq!(rec.t, 1.0);
+        assert_eq!((rec.u, rec.v), (0.75, 0.5));
+        assert_eq!(rec.normal, Vec3::new(0.0, 0.0, 1.0));
+        assert!(rec.front_face);
+
+        // From behind the normal is flipped towards the ray.
+        let r = Ray::new(Point3::new(0.0, 0.0, -4.0), Vec3::new(0.0, 0.0, 1.0));
+        let rec = quad.hit(&r, ray_t).unwrap();
+        assert_eq!(rec.normal, Vec3::new(0.0, 0.0, -1.0));
+        assert!(!rec.front_face);
+
+        let outside = Ray::new(Point3::origin(), Vec3::new(0.6, 0.0, -1.0));
+        assert!(quad.hit(&outside, ray_t).is_none());
+        let parallel = Ray::new(Point3::origin(), Vec3::new(1.0, 0.0, 0.0));
+        assert!(quad.hit(&parallel, ray_t).is_none());
+    }
+
+    #[test]
+    fn test_box_normals_face_outward() {
+        let shape = BoxShape::new(
+            Point3::new(1.0, 1.0, 1.0),
+            Point3::new(-1.0, -2.0, -1.0),
+            gray(),
+        );
+        let bbox = shape.bounding_box();
+        // Flat sides are padded slightly.
+        assert!((bbox.y.min + 2.0).abs() < 1e-3 && (bbox.y.max - 1.0).abs() < 1e-3);
+        let ray_t = Interval::new(0.001, f64::INFINITY);
+        for axis in 0..3 {
+            for sign in [-1.0, 1.0] {
+                let mut dir = [0.0; 3];
+                dir[axis] = sign;
+                let dir = Vec3::new(dir[0], dir[1], dir[2]);
+                let center = Point3::new(0.0, -0.5, 0.0);
+                let r = Ray::new(center + 5.0 * dir, -dir);
+                let rec = shape.hit(&r, ray_t).unwrap();
+                assert_eq!(rec.normal, dir);
+                assert!(rec.front_face);
+                // Leaving from the inside hits the same side's back.
+                let rec = shape.hit(&Ray::new(center, dir), ray_t).unwrap();
+                assert_eq!(rec.normal, -dir);
+                assert!(!rec.front_face);
+            }
+        }
+    }
+}
@@ -0,0 +1,1423 @@
+//! Text scene description format.
+//!
+//! A scene file is a sequence of statements, each a keyword followed by a
+//! `{ ... }` block of properties. Whitespace is insignificant and `#` starts a
+//! comment that runs to the end of the line:
+//!
+//! ```text
+//! render { width 400 aspect 1.7778 samples 100 depth 50 seed 42 }
+//! camera { lookfrom 13 2 3 lookat 0 0 0 vup 0 1 0 vfov 20 }
+//! environment gradient { bottom 1 1 1 top 0.5 0.7 1 }
+//!
+//! texture earth image { file "earth.png" filter bilinear address wrap }
+//! texture tiles checker { scale 0.5 even 0.2 0.3 0.1 odd 0.9 0.9 0.9 space uv }
+//! texture stone marble { scale 4 light 0.9 0.9 0.85 dark 0.2 0.2 0.25 seed 7 }
+//!
+//! material ground lambertian { albedo 0.5 0.5 0.5 }
+//! material globe lambertian { texture earth }
+//! material glass dielectric { ior 1.5 }
+//!
+//! sphere { center 0 -1000 0 radius 1000 material ground }
+//! quad { corner -1 0 -1 u 2 0 0 v 0 0 2 material ground }
+//! disk { center 0 2 0 normal 0 -1 0 radius 0.5 material glass }
+//! box { min -1 0 -1 max 1 1 1 material ground }
+//! mesh { file "teapot.obj" material glass scale 2 2 2 rotate 0 1 0 30 }
+//!
+//! material smoke isotropic { albedo 0.2 0.2 0.2 }
+//! medium { boundary sphere { center 0 1 0 radius 1 } density 0.5 material smoke }
+//! medium { boundary box { min 0 0 0 max 2 1 2 } density 4 material smoke
+//!          grid { file "cloud.raw" size 64 32 64 } }
+//! ```
+//!
+//! Textures and materials are declared with a name and a type and referenced
+//! by name from statements after them. Image and mesh paths are relative to
+//! the scene file.
+//!
+//! Every object also takes placement properties, applied in the order
+//! written: `translate x y z`, `rotate x y z degrees` about an axis through
+//! the origin, `euler x y z` for degrees about x, then y, then z, and
+//! `scale x y z`. A mesh used several times with the same material is loaded
+//! once and shared.
+//!
+//! Spheres, triangles, quads and disks made of a `light` material become the
+//! scene's lights, which every diffuse or glossy bounce aims a shadow ray
+//! at. Light from other glowing objects is only found by bouncing into it.
+//! Light that both a shadow ray and a bounce can find is shared between them
+//! with the power heuristic, or the balance heuristic after
+//! `render { mis balance }`; `mis off` leaves it all to the shadow rays.
+//! `render { integrator naive }` picks another rendering algorithm, one of
+//! [`integrator::NAMES`]. Paths that reach `render { roulette 3 }` surfaces,
+//! the default, go on by chance, more likely the brighter they still are,
+//! until `depth` cuts them off.
+//!
+//! A medium is uniform unless given a density field, which `density` then
+//! scales: `grid` fills the boundary's bounding box with voxels read from
+//! text (`nx ny nz` then the values, x fastest) or, when `size` is given, raw
+//! little-endian floats; `noise { scale seed octaves threshold }` makes
+//! billowing cloud.
+
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use crate::{
+    camera::Camera,
+    color::Color,
+    density::{NoiseDensity, VoxelGrid},
+    disk::Disk,
+    environment::Environment,
+    hittable::Hittable,
+    hittable_list::HittableList,
+    integrator,
+    material::{Dialectric, DiffuseLight, Isotropic, Lambertian, Material, Metal},
+    medium::{ConstantMedium, HeterogeneousMedium},
+    mesh::Mesh,
+    obj::load_obj,
+    pdf::Heuristic,
+    quad::{BoxShape, Quad},
+    sphere::Sphere,
+    texture::{
+        AddressMode, CheckerSpace, CheckerTexture, Filter, ImageTexture, MarbleTexture,
+        NoiseTexture, SolidColor, Texture, TurbulenceTexture, WoodTexture,
+    },
+    transform::Transform,
+    triangle::Triangle,
+    vec3::{Mat4, Point3, Vec3},
+};
+
+/// Everything needed to render: the objects and a configured camera.
+pub struct Scene {
+    pub world: HittableList<'static>,
+    /// Emitters in `world` that shadow rays are aimed at.
+    pub lights: HittableList<'static>,
+    pub camera: Camera,
+}
+
+#[derive(Debug)]
+pub enum SceneError {
+    Io {
+        path: PathBuf,
+        source: io::Error,
+    },
+    Parse {
+        line: usize,
+        column: usize,
+        message: String,
+    },
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SceneError::Io { path, source } => write!(f, "{}: {source}", path.display()),
+            SceneError::Parse {
+                line,
+                column,
+                message,
+            } => write!(f, "{line}:{column}: {message}"),
+        }
+    }
+}
+
+impl Error for SceneError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SceneError::Io { source, .. } => Some(source),
+            SceneError::Parse { .. } => None,
+        }
+    }
+}
+
+impl Scene {
+    pub fn load(path: impl AsRef<Path>) -> Result<Scene, SceneError> {
+        let path = path.as_ref();
+        let source = fs::read_to_string(path).map_err(|source| SceneError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        Self::parse(&source, path.parent().unwrap_or(Path::new(".")))
+    }
+
+    /// Parses scene text; relative file references are resolved against `base_dir`.
+    pub fn parse(source: &str, base_dir: &Path) -> Result<Scene, SceneError> {
+        let tokens = tokenize(source)?;
+        Parser {
+            tokens,
+            pos: 0,
+            base_dir,
+            textures: HashMap::new(),
+            materials: HashMap::new(),
+            meshes: HashMap::new(),
+            boundary_material: None,
+            lights: HittableList::new(),
+        }
+        .scene()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum TokenKind {
+    Ident(String),
+    Number(f64),
+    Str(String),
+    LBrace,
+    RBrace,
+    Eof,
+}
+
+impl fmt::Display for TokenKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TokenKind::Ident(name) => write!(f, "`{name}`"),
+            TokenKind::Number(n) => write!(f, "number {n}"),
+            TokenKind::Str(s) => write!(f, "string {s:?}"),
+            TokenKind::LBrace => write!(f, "`{{`"),
+            TokenKind::RBrace => write!(f, "`}}`"),
+            TokenKind::Eof => write!(f, "end of file"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    kind: TokenKind,
+    line: usize,
+    column: usize,
+}
+
+impl Token {
+    fn error(&self, message: impl Into<String>) -> SceneError {
+        SceneError::Parse {
+            line: self.line,
+            column: self.column,
+            message: message.into(),
+        }
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, SceneError> {
+    let mut tokens = Vec::new();
+    for (line_index, line) in source.lines().enumerate() {
+        let chars: Vec<char> = line.chars().collect();
+        let mut i = 0;
+        while i < chars.len() {
+            let c = chars[i];
+            let start = i;
+            let at = |kind| Token {
+                kind,
+                line: line_index + 1,
+                column: start + 1,
+            };
+            let error = |message: String| SceneError::Parse {
+                line: line_index + 1,
+                column: start + 1,
+                message,
+            };
+            if c == '#' {
+                break;
+            } else if c.is_whitespace() {
+                i += 1;
+            } else if c == '{' {
+                tokens.push(at(TokenKind::LBrace));
+                i += 1;
+            } else if c == '}' {
+                tokens.push(at(TokenKind::RBrace));
+                i += 1;
+            } else if c == '"' {
+                i += 1;
+                while i < chars.len() && chars[i] != '"' {
+                    i += 1;
+                }
+                if i == chars.len() {
+                    return Err(error("unterminated string".to_string()));
+                }
+                let text = chars[start + 1..i].iter().collect();
+                tokens.push(at(TokenKind::Str(text)));
+                i += 1;
+            } else if c.is_ascii_digit() || matches!(c, '-' | '+' | '.') {
+                while i < chars.len() && is_word_char(chars[i]) {
+                    i += 1;
+                }
+                let text: String = chars[start..i].iter().collect();
+                let value = text
+                    .parse()
+                    .map_err(|_| error(format!("invalid number `{text}`")))?;
+                tokens.push(at(TokenKind::Number(value)));
+            } else if c.is_alphabetic() || c == '_' {
+                while i < chars.len() && is_word_char(chars[i]) {
+                    i += 1;
+                }
+                tokens.push(at(TokenKind::Ident(chars[start..i].iter().collect())));
+            } else {
+                return Err(error(format!("unexpected character `{c}`")));
+            }
+        }
+    }
+    let line = source.lines().count().max(1);
+    let column = source.lines().last().map_or(0, |l| l.chars().count()) + 1;
+    tokens.push(Token {
+        kind: TokenKind::Eof,
+        line,
+        column,
+    });
+    Ok(tokens)
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '_' | '-' | '+' | '.')
+}
+
+struct Parser<'p> {
+    tokens: Vec<Token>,
+    pos: usize,
+    base_dir: &'p Path,
+    textures: HashMap<String, Arc<dyn Texture>>,
+    materials: HashMap<String, Arc<dyn Material>>,
+    /// Loaded meshes by file and material name.
+    meshes: HashMap<(PathBuf, String), Arc<Mesh<'static>>>,
+    /// Stands in for the material of a medium's boundary while it is parsed.
+    boundary_material: Option<Arc<dyn Material>>,
+    lights: HittableList<'static>,
+}
+
+impl<'p> Parser<'p> {
+    fn scene(mut self) -> Result<Scene, SceneError> {
+        let mut world = HittableList::new();
+        let mut camera = Camera::new();
+
+        loop {
+            let token = self.next();
+            let keyword = match &token.kind {
+                TokenKind::Eof => break,
+                TokenKind::Ident(keyword) => keyword.clone(),
+                other => return Err(token.error(format!("expected a statement, found {other}"))),
+            };
+            match keyword.as_str() {
+                "render" => self.render(&mut camera)?,
+                "camera" => self.camera(&mut camera)?,
+                "environment" => camera.background = self.environment()?,
+                "texture" => self.texture()?,
+                "material" => self.material()?,
+                _ => match self.object(&token)? {
+                    Some(object) => world.add_boxed(object),
+                    None => return Err(token.error(format!("unknown statement `{keyword}`"))),
+                },
+            }
+        }
+
+        Ok(Scene {
+            world,
+            lights: self.lights,
+            camera,
+        })
+    }
+
+    fn render(&mut self, camera: &mut Camera) -> Result<(), SceneError> {
+        self.block(|p, key| {
+            match key.as_str() {
+                "width" => camera.image_width = p.positive_count()?,
+                "aspect" => camera.aspect_ratio = p.positive()?,
+                "samples" => camera.samples_per_pixel = p.positive_count()?,
+                "depth" => camera.max_depth = p.count()?,
+                "roulette" => camera.roulette_depth = p.count()?,
+                "seed" => camera.seed = p.count()? as u64,
+                "threads" => camera.threads = p.count()?,
+                "tile_size" => camera.tile_size = p.count()?,
+                "integrator" => {
+                    camera.integrator = p.keyword("integrator", integrator::for_name)?
+                }
+                "mis" => {
+                    camera.heuristic = p.keyword("heuristic", |value| match value {
+                        "off" => Some(Heuristic::Off),
+                        "balance" => Some(Heuristic::Balance),
+                        "power" => Some(Heuristic::Power),
+                        _ => None,
+                    })?
+                }
+                _ => return Ok(false),
+            }
+            Ok(true)
+        })
+    }
+
+    fn camera(&mut self, camera: &mut Camera) -> Result<(), SceneError> {
+        self.block(|p, key| {
+            match key.as_str() {
+                "lookfrom" => camera.lookfrom = p.point()?,
+                "lookat" => camera.lookat = p.point()?,
+                "vup" => camera.vup = p.vec3()?,
+                "vfov" => camera.vfov = p.positive()?,
+                "defocus_angle" => camera.defocus_angle = p.number()?,
+                "focus_dist" => camera.focus_dist = p.positive()?,
+                _ => return Ok(false),
+            }
+            Ok(true)
+        })
+    }
+
+    fn environment(&mut self) -> Result<Environment, SceneError> {
+        let (kind, token) = self.ident()?;
+        match kind.as_str() {
+            "solid" => {
+                let mut color = Color::new(0.0, 0.0, 0.0);
+                self.block(|p, key| {
+                    match key.as_str() {
+                        "color" => color = p.color()?,
+                        _ => return Ok(false),
+                    }
+                    Ok(true)
+                })?;
+                Ok(Environment::Solid(color))
+            }
+            "gradient" => {
+                let mut bottom = Color::new(1.0, 1.0, 1.0);
+                let mut top = Color::new(0.5, 0.7, 1.0);
+                self.block(|p, key| {
+                    match key.as_str() {
+                        "bottom" => bottom = p.color()?,
+                        "top" => top = p.color()?,
+                        _ => return Ok(false),
+                    }
+                    Ok(true)
+                })?;
+                Ok(Environment::Gradient { bottom, top })
+            }
+            _ => Err(token.error(format!("unknown environment type `{kind}`"))),
+        }
+    }
+
+    fn texture(&mut self) -> Result<(), SceneError> {
+        let (name, name_token) = self.ident()?;
+        if self.textures.contains_key(&name) {
+            return Err(name_token.error(format!("texture `{name}` is already defined")));
+        }
+        let (kind, kind_token) = self.ident()?;
+
+        let mut color = None;
+        let mut scale = 1.0;
+        let mut even = Color::new(0.2, 0.3, 0.1);
+        let mut odd = Color::new(0.9, 0.9, 0.9);
+        let mut space = CheckerSpace::default();
+        let mut light = Color::new(1.0, 1.0, 1.0);
+        let mut dark = Color::new(0.0, 0.0, 0.0);
+        let (mut octaves, mut distortion, mut seed) = (None, None, 0);
+        let mut file = None;
+        let mut filter = Filter::default();
+        let mut address = AddressMode::default();
+        let allowed: &[&str] = match kind.as_str() {
+            "solid" => &["color"],
+            "checker" => &["scale", "even", "odd", "space"],
+            "noise" => &["scale", "color", "seed"],
+            "turbulence" => &["scale", "color", "octaves", "seed"],
+            "marble" => &["scale", "light", "dark", "octaves", "distortion", "seed"],
+            "wood" => &["scale", "light", "dark", "distortion", "seed"],
+            "image" => &["file", "filter", "address"],
+            _ => return Err(kind_token.error(format!("unknown texture type `{kind}`"))),
+        };
+        self.block(|p, key| {
+            if !allowed.contains(&key.as_str()) {
+                return Ok(false);
+            }
+            match key.as_str() {
+                "color" => color = Some(p.color()?),
+                "scale" => scale = p.positive()?,
+                "even" => even = p.color()?,
+                "odd" => odd = p.color()?,
+                "light" => light = p.color()?,
+                "dark" => dark = p.color()?,
+                "octaves" => octaves = Some(p.count()?),
+                "distortion" => distortion = Some(p.number()?),
+                "seed" => seed = p.count()? as u64,
+                "file" => file = Some(p.string()?),
+                "space" => {
+                    space = p.keyword("checker space", |value| match value {
+                        "solid" => Some(CheckerSpace::Solid),
+                        "uv" => Some(CheckerSpace::Uv),
+                        _ => None,
+                    })?
+                }
+                "filter" => {
+                    filter = p.keyword("filter", |value| match value {
+                        "nearest" => Some(Filter::Nearest),
+                        "bilinear" => Some(Filter::Bilinear),
+                        _ => None,
+                    })?
+                }
+                _ => {
+                    address = p.keyword("address mode", |value| match value {
+                        "wrap" => Some(AddressMode::Wrap),
+                        "clamp" => Some(AddressMode::Clamp),
+                        "mirror" => Some(AddressMode::Mirror),
+                        _ => None,
+                    })?
+                }
+            }
+            Ok(true)
+        })?;
+
+        let white = Color::new(1.0, 1.0, 1.0);
+        let texture: Arc<dyn Texture> = match kind.as_str() {
+            "solid" => Arc::new(SolidColor::new(color.unwrap_or(Color::new(0.5, 0.5, 0.5)))),
+            "checker" => {
+                let mut checker = CheckerTexture::from_colors(scale, even, odd);
+                checker.space = space;
+                Arc::new(checker)
+            }
+            "noise" => Arc::new(NoiseTexture::new(scale, color.unwrap_or(white), seed)),
+            "turbulence" => {
+                let mut turbulence = TurbulenceTexture::new(scale, color.unwrap_or(white), seed);
+                turbulence.octaves = octaves.unwrap_or(turbulence.octaves);
+                Arc::new(turbulence)
+            }
+            "marble" => {
+                let mut marble = MarbleTexture::new(scale, light, dark, seed);
+                marble.octaves = octaves.unwrap_or(marble.octaves);
+                marble.distortion = distortion.unwrap_or(marble.distortion);
+                Arc::new(marble)
+            }
+            "wood" => {
+                let mut wood = WoodTexture::new(scale, light, dark, seed);
+                wood.distortion = distortion.unwrap_or(wood.distortion);
+                Arc::new(wood)
+            }
+            _ => {
+                let (file, file_token) = required(file, "file", &kind_token)?;
+                let mut image = ImageTexture::load(self.base_dir.join(&file))
+                    .map_err(|err| file_token.error(format!("{file}: {err}")))?;
+                image.filter = filter;
+                image.address = address;
+                Arc::new(image)
+            }
+        };
+        self.textures.insert(name, texture);
+        Ok(())
+    }
+
+    fn material(&mut self) -> Result<(), SceneError> {
+        let (name, name_token) = self.ident()?;
+        if self.materials.contains_key(&name) {
+            return Err(name_token.error(format!("material `{name}` is already defined")));
+        }
+        let (kind, kind_token) = self.ident()?;
+
+        let mut albedo = Color::new(0.5, 0.5, 0.5);
+        let mut texture = None;
+        let mut fuzz = 0.0;
+        let mut ior = 1.5;
+        let mut emit = Color::new(1.0, 1.0, 1.0);
+        let allowed: &[&str] = match kind.as_str() {
+            "lambertian" => &["albedo", "texture"],
+            "metal" => &["albedo", "texture", "fuzz"],
+            "dielectric" => &["ior"],
+            "light" => &["emit", "texture"],
+            "isotropic" => &["albedo", "texture"],
+            _ => return Err(kind_token.error(format!("unknown material type `{kind}`"))),
+        };
+        self.block(|p, key| {
+            if !allowed.contains(&key.as_str()) {
+                return Ok(false);
+            }
+            match key.as_str() {
+                "albedo" => albedo = p.color()?,
+                "texture" => texture = Some(p.texture_ref()?),
+                "fuzz" => fuzz = p.number()?,
+                "ior" => ior = p.positive()?,
+                _ => emit = p.color()?,
+            }
+            Ok(true)
+        })?;
+
+        let material: Arc<dyn Material> = match kind.as_str() {
+            "lambertian" => match texture {
+                Some(texture) => Arc::new(Lambertian::from_texture(texture)),
+                None => Arc::new(Lambertian::new(albedo)),
+            },
+            "metal" => match texture {
+                Some(texture) => Arc::new(Metal::from_texture(texture, fuzz)),
+                None => Arc::new(Metal::new(albedo, fuzz)),
+            },
+            "dielectric" => Arc::new(Dialectric::new(ior)),
+            "isotropic" => match texture {
+                Some(texture) => Arc::new(Isotropic::from_texture(texture)),
+                None => Arc::new(Isotropic::new(albedo)),
+            },
+            _ => match texture {
+                Some(texture) => Arc::new(DiffuseLight::from_texture(texture)),
+                None => Arc::new(DiffuseLight::new(emit)),
+            },
+        };
+        self.materials.insert(name, material);
+        Ok(())
+    }
+
+    /// Parses the object statement `keyword` starts, or returns `None` if it
+    /// does not name an object.
+    fn object(&mut self, keyword: &Token) -> Result<Option<Object>, SceneError> {
+        let TokenKind::Ident(name) = &keyword.kind else {
+            return Ok(None);
+        };
+        let object = match name.as_str() {
+            "sphere" => self.sphere(keyword)?,
+            "triangle" => self.triangle(keyword)?,
+            "quad" => self.quad(keyword)?,
+            "disk" => self.disk(keyword)?,
+            "box" => self.box_shape(keyword)?,
+            "mesh" => self.mesh(keyword)?,
+            "medium" => self.medium(keyword)?,
+            _ => return Ok(None),
+        };
+        Ok(Some(object))
+    }
+
+    fn sphere(&mut self, keyword: &Token) -> Result<Object, SceneError> {
+        let (mut center, mut radius, mut material) = (None, None, None);
+        let mut to_world = Mat4::IDENTITY;
+        self.block(|p, key| {
+            match key.as_str() {
+                "center" => center = Some(p.point()?),
+                "radius" => radius = Some(p.positive()?),
+                "material" => material = Some(p.material_ref()?),
+                _ => return p.placement(key, &mut to_world),
+            }
+            Ok(true)
+        })?;
+        let material = self.surface(material, keyword)?;
+        let sphere = Sphere::new(
+            required(center, "center", keyword)?,
+            required(radius, "radius", keyword)?,
+            material.clone(),
+        );
+        Ok(self.light(place(sphere, to_world), &*material))
+    }
+
+    fn triangle(&mut self, keyword: &Token) -> Result<Object, SceneError> {
+        let (mut vertices, mut normals, mut uvs, mut material) = (None, None, None, None);
+        let mut to_world = Mat4::IDENTITY;
+        self.block(|p, key| {
+            match key.as_str() {
+                "vertices" => vertices = Some([p.point()?, p.point()?, p.point()?]),
+                "normals" => normals = Some([p.vec3()?, p.vec3()?, p.vec3()?]),
+                "uvs" => {
+                    uvs = Some([
+                        (p.number()?, p.number()?),
+                        (p.number()?, p.number()?),
+                        (p.number()?, p.number()?),
+                    ])
+                }
+                "material" => material = Some(p.material_ref()?),
+                _ => return p.placement(key, &mut to_world),
+            }
+            Ok(true)
+        })?;
+        let [p0, p1, p2] = required(vertices, "vertices", keyword)?;
+        let material = self.surface(material, keyword)?;
+        let mut triangle = Triangle::new(p0, p1, p2, material.clone());
+        triangle.normals = normals;
+        triangle.uvs = uvs;
+        Ok(self.light(place(triangle, to_world), &*material))
+    }
+
+    fn quad(&mut self, keyword: &Token) -> Result<Object, SceneError> {
+        let (mut corner, mut u, mut v, mut material) = (None, None, None, None);
+        let mut to_world = Mat4::IDENTITY;
+        self.block(|p, key| {
+            match key.as_str() {
+                "corner" => corner = Some(p.point()?),
+                "u" => u = Some(p.side()?),
+                "v" => v = Some(p.side()?),
+                "material" => material = Some(p.material_ref()?),
+                _ => return p.placement(key, &mut to_world),
+            }
+            Ok(true)
+        })?;
+        let material = self.surface(material, keyword)?;
+        let (u, _) = required(u, "u", keyword)?;
+        let (v, v_token) = required(v, "v", keyword)?;
+        // The sides span the plane; parallel ones leave it without a normal.
+        if Vec3::cross(u, v).length() <= 1e-12 * u.length() * v.length() {
+            return Err(v_token.error("quad sides `u` and `v` must not be parallel"));
+        }
+        let quad = Quad::new(required(corner, "corner", keyword)?, u, v, material.clone());
+        Ok(self.light(place(quad, to_world), &*material))
+    }
+
+    fn disk(&mut self, keyword: &Token) -> Result<Object, SceneError> {
+        let (mut center, mut normal, mut radius, mut material) = (None, None, None, None);
+        let mut to_world = Mat4::IDENTITY;
+        self.block(|p, key| {
+            match key.as_str() {
+                "center" => center = Some(p.point()?),
+                "normal" => {
+                    let token = p.tokens[p.pos].clone();
+                    let value = p.vec3()?;
+                    if value.near_zero() {
+                        return Err(token.error("disk normal must not be zero"));
+                    }
+                    normal = Some(value);
+                }
+                "radius" => radius = Some(p.positive()?),
+                "material" => material = Some(p.material_ref()?),
+                _ => return p.placement(key, &mut to_world),
+            }
+            Ok(true)
+        })?;
+        let material = self.surface(material, keyword)?;
+        let disk = Disk::new(
+            required(center, "center", keyword)?,
+            required(normal, "normal", keyword)?,
+            required(radius, "radius", keyword)?,
+            material.clone(),
+        );
+        Ok(self.light(place(disk, to_world), &*material))
+    }
+
+    fn box_shape(&mut self, keyword: &Token) -> Result<Object, SceneError> {
+        let (mut min, mut max, mut material) = (None, None, None);
+        let mut to_world = Mat4::IDENTITY;
+        self.block(|p, key| {
+            match key.as_str() {
+                "min" => min = Some(p.point()?),
+                "max" => max = Some(p.point()?),
+                "material" => material = Some(p.material_ref()?),
+                _ => return p.placement(key, &mut to_world),
+            }
+            Ok(true)
+        })?;
+        let shape = BoxShape::new(
+            required(min, "min", keyword)?,
+            required(max, "max", keyword)?,
+            self.surface(material, keyword)?,
+        );
+        Ok(place(shape, to_world))
+    }
+
+    fn mesh(&mut self, keyword: &Token) -> Result<Object, SceneError> {
+        let (mut file, mut material) = (None, None);
+        let mut to_world = Mat4::IDENTITY;
+        self.block(|p, key| {
+            match key.as_str() {
+                "file" => file = Some(p.string()?),
+                "material" => material = Some(p.ident()?),
+                _ => return p.placement(key, &mut to_world),
+            }
+            Ok(true)
+        })?;
+        let (file, file_token) = required(file, "file", keyword)?;
+        let (name, material) = match material {
+            Some((name, token)) => {
+                let material = self.material_named(&name, &token)?;
+                (name, Some(material))
+            }
+            None => (String::new(), None),
+        };
+        let material = self.surface(material, keyword)?;
+        // Loading the same file with the same material again reuses the
+        // mesh, so placing it many times costs one copy of the geometry.
+        let path = self.base_dir.join(&file);
+        let mesh = match self.meshes.get(&(path.clone(), name.clone())) {
+            Some(mesh) => mesh.clone(),
+            None => {
+                let mesh = Arc::new(
+                    load_obj(&path, material).map_err(|err| file_token.error(err.to_string()))?,
+                );
+                self.meshes.insert((path, name), mesh.clone());
+                mesh
+            }
+        };
+        Ok(place(mesh, to_world))
+    }
+
+    fn medium(&mut self, keyword: &Token) -> Result<Object, SceneError> {
+        let (mut boundary, mut density, mut material) = (None, None, None);
+        let mut field = None;
+        let mut to_world = Mat4::IDENTITY;
+        self.block(|p, key| {
+            match key.as_str() {
+                "boundary" => {
+                    // The boundary only shapes the volume and is never
+                    // shaded, so it needs no material of its own.
+                    let shape = p.next();
+                    let outer = p
+                        .boundary_material
+                        .replace(Arc::new(Lambertian::new(Color::new(0.0, 0.0, 0.0))));
+                    let object = p.object(&shape);
+                    p.boundary_material = outer;
+                    boundary = Some(object?.ok_or_else(|| {
+                        shape.error(format!("expected a shape, found {}", shape.kind))
+                    })?);
+                }
+                "density" => density = Some(p.positive()?),
+                "material" => material = Some(p.material_ref()?),
+                "grid" => field = Some(p.grid()?),
+                "noise" => field = Some(p.noise_density()?),
+                _ => return p.placement(key, &mut to_world),
+            }
+            Ok(true)
+        })?;
+        let boundary = required(boundary, "boundary", keyword)?;
+        let density = required(density, "density", keyword)?;
+        let material = required(material, "material", keyword)?;
+        let medium: Object = match field {
+            None => Box::new(ConstantMedium::new(boundary, density, material)),
+            Some(DensitySpec::Noise(noise)) => {
+                Box::new(HeterogeneousMedium::new(boundary, noise, density, material))
+            }
+            Some(DensitySpec::Grid { file, size }) => {
+                // The grid fills the boundary's bounding box.
+                let (file, file_token) = file;
+                let grid =
+                    VoxelGrid::load(self.base_dir.join(&file), size, boundary.bounding_box())
+                        .map_err(|err| file_token.error(format!("{file}: {err}")))?;
+                Box::new(HeterogeneousMedium::new(boundary, grid, density, material))
+            }
+        };
+        Ok(place(medium, to_world))
+    }
+
+    fn grid(&mut self) -> Result<DensitySpec, SceneError> {
+        let keyword = self.tokens[self.pos - 1].clone();
+        let (mut file, mut size) = (None, None);
+        self.block(|p, key| {
+            match key.as_str() {
+                "file" => file = Some(p.string()?),
+                "size" => size = Some([p.count()?, p.count()?, p.count()?]),
+                _ => return Ok(false),
+            }
+            Ok(true)
+        })?;
+        Ok(DensitySpec::Grid {
+            file: required(file, "file", &keyword)?,
+            size,
+        })
+    }
+
+    fn noise_density(&mut self) -> Result<DensitySpec, SceneError> {
+        let (mut scale, mut seed, mut octaves, mut threshold) = (1.0, 0, None, 0.0);
+        self.block(|p, key| {
+            match key.as_str() {
+                "scale" => scale = p.positive()?,
+                "seed" => seed = p.count()? as u64,
+                "octaves" => octaves = Some(p.count()?),
+                "threshold" => {
+                    // 1 would leave nothing to stretch back to [0, 1].
+                    let (value, token) = p.number_token()?;
+                    if !(0.0..1.0).contains(&value) {
+                        return Err(token.error(format!(
+                            "threshold must be at least 0 and below 1, found {value}"
+                        )));
+                    }
+                    threshold = value;
+                }
+                _ => return Ok(false),
+            }
+            Ok(true)
+        })?;
+        let mut noise = NoiseDensity::new(scale, seed);
+        noise.octaves = octaves.unwrap_or(noise.octaves);
+        noise.threshold = threshold;
+        Ok(DensitySpec::Noise(noise))
+    }
+
+    /// Placement properties shared by every object; each one applies after
+    /// those before it.
+    fn placement(&mut self, key: &str, to_world: &mut Mat4) -> Result<bool, SceneError> {
+        let key_token = self.tokens[self.pos - 1].clone();
+        let step = match key {
+            "translate" => Mat4::translation(self.vec3()?),
+            "rotate" => {
+                let axis_token = self.tokens[self.pos].clone();
+                let axis = self.vec3()?;
+                if axis.near_zero() {
+                    return Err(axis_token.error("rotation axis must not be zero"));
+                }
+                Mat4::rotation(axis, self.number()?)
+            }
+            "euler" => Mat4::rotation_euler(self.vec3()?),
+            "scale" => {
+                let (x, y, z) = (self.nonzero()?, self.nonzero()?, self.nonzero()?);
+                Mat4::scaling(Vec3::new(x, y, z))
+            }
+            _ => return Ok(false),
+        };
+        // Steps that are each invertible can still multiply out to a matrix
+        // too small to invert.
+        let placed = step * *to_world;
+        if placed.inverse().is_none() {
+            return Err(key_token.error("object transform cannot be inverted"));
+        }
+        *to_world = placed;
+        Ok(true)
+    }
+
+    /// Parses `{ key values... }`, calling `property` for each key; it returns
+    /// `false` for keys the enclosing statement does not understand.
+    fn block(
+        &mut self,
+        mut property: impl FnMut(&mut Self, &String) -> Result<bool, SceneError>,
+    ) -> Result<(), SceneError> {
+        self.expect(TokenKind::LBrace)?;
+        loop {
+            let token = self.next();
+            match &token.kind {
+                TokenKind::RBrace => return Ok(()),
+                TokenKind::Ident(key) => {
+                    if !property(self, key)? {
+                        return Err(token.error(format!("unknown property `{key}`")));
+                    }
+                }
+                other => {
+                    return Err(token.error(format!("expected a property or `}}`, found {other}")));
+                }
+            }
+        }
+    }
+
+    fn next(&mut self) -> Token {
+        let token = self.tokens[self.pos].clone();
+        if token.kind != TokenKind::Eof {
+            self.pos += 1;
+        }
+        token
+    }
+
+    fn expect(&mut self, kind: TokenKind) -> Result<(), SceneError> {
+        let token = self.next();
+        if token.kind == kind {
+            Ok(())
+        } else {
+            Err(token.error(format!("expected {kind}, found {}", token.kind)))
+        }
+    }
+
+    fn ident(&mut self) -> Result<(String, Token), SceneError> {
+        let token = self.next();
+        match &token.kind {
+            TokenKind::Ident(name) => Ok((name.clone(), token.clone())),
+            other => Err(token.error(format!("expected a name, found {other}"))),
+        }
+    }
+
+    fn string(&mut self) -> Result<(String, Token), SceneError> {
+        let token = self.next();
+        match &token.kind {
+            TokenKind::Str(text) => Ok((text.clone(), token.clone())),
+            other => Err(token.error(format!("expected a string, found {other}"))),
+        }
+    }
+
+    fn number_token(&mut self) -> Result<(f64, Token), SceneError> {
+        let token = self.next();
+        match token.kind {
+            TokenKind::Number(value) => Ok((value, token)),
+            ref other => Err(token.error(format!("expected a number, found {other}"))),
+        }
+    }
+
+    fn number(&mut self) -> Result<f64, SceneError> {
+        Ok(self.number_token()?.0)
+    }
+
+    fn nonzero(&mut self) -> Result<f64, SceneError> {
+        let (value, token) = self.number_token()?;
+        if value != 0.0 {
+            Ok(value)
+        } else {
+            Err(token.error("expected a non-zero number"))
+        }
+    }
+
+    fn positive(&mut self) -> Result<f64, SceneError> {
+        let (value, token) = self.number_token()?;
+        if value > 0.0 {
+            Ok(value)
+        } else {
+            Err(token.error(format!("expected a positive number, found {value}")))
+        }
+    }
+
+    fn count(&mut self) -> Result<usize, SceneError> {
+        let (value, token) = self.number_token()?;
+        if value >= 0.0 && value.fract() == 0.0 {
+            Ok(value as usize)
+        } else {
+            Err(token.error(format!("expected a whole number, found {value}")))
+        }
+    }
+
+    fn positive_count(&mut self) -> Result<usize, SceneError> {
+        let token = self.tokens[self.pos].clone();
+        match self.count()? {
+            0 => Err(token.error("expected a positive whole number, found 0")),
+            count => Ok(count),
+        }
+    }
+
+    /// A quad side, which must not be zero, and where it was written.
+    fn side(&mut self) -> Result<(Vec3, Token), SceneError> {
+        let token = self.tokens[self.pos].clone();
+        let side = self.vec3()?;
+        if side.near_zero() {
+            return Err(token.error("quad side must not be zero"));
+        }
+        Ok((side, token))
+    }
+
+    fn vec3(&mut self) -> Result<Vec3, SceneError> {
+        Ok(Vec3::new(self.number()?, self.number()?, self.number()?))
+    }
+
+    fn point(&mut self) -> Result<Point3, SceneError> {
+        Ok(Point3::from(self.vec3()?))
+    }
+
+    fn color(&mut self) -> Result<Color, SceneError> {
+        Ok(Color::from(self.vec3()?))
+    }
+
+    /// One of a fixed set of names, mapped by `lookup`.
+    fn keyword<T>(
+        &mut self,
+        what: &str,
+        lookup: impl Fn(&str) -> Option<T>,
+    ) -> Result<T, SceneError> {
+        let (value, token) = self.ident()?;
+        lookup(&value).ok_or_else(|| token.error(format!("unknown {what} `{value}`")))
+    }
+
+    fn texture_ref(&mut self) -> Result<Arc<dyn Texture>, SceneError> {
+        let (name, token) = self.ident()?;
+        self.textures
+            .get(&name)
+            .cloned()
+            .ok_or_else(|| token.error(format!("unknown texture `{name}`")))
+    }
+
+    fn material_ref(&mut self) -> Result<Arc<dyn Material>, SceneError> {
+        let (name, token) = self.ident()?;
+        self.material_named(&name, &token)
+    }
+
+    /// An object's material, which only medium boundaries may leave out.
+    fn surface(
+        &self,
+        material: Option<Arc<dyn Material>>,
+        keyword: &Token,
+    ) -> Result<Arc<dyn Material>, SceneError> {
+        required(
+            material.or_else(|| self.boundary_material.clone()),
+            "material",
+            keyword,
+        )
+    }
+
+    /// Also lists `object` as a light when its material glows, sharing it
+    /// with the world. A medium's boundary is never shaded, so never is one.
+    fn light(&mut self, object: Object, material: &dyn Material) -> Object {
+        if !material.is_emissive() || self.boundary_material.is_some() {
+            return object;
+        }
+        let shared: Arc<dyn Hittable> = Arc::from(object);
+        self.lights.add(shared.clone());
+        Box::new(shared)
+    }
+
+    fn material_named(&self, name: &str, token: &Token) -> Result<Arc<dyn Material>, SceneError> {
+        self.materials
+            .get(name)
+            .cloned()
+            .ok_or_else(|| token.error(format!("unknown material `{name}`")))
+    }
+}
+
+type Object = Box<dyn Hittable + 'static>;
+
+/// The density field of a `medium`, loaded once its boundary is known.
+enum DensitySpec {
+    Grid {
+        file: (String, Token),
+        size: Option<[usize; 3]>,
+    },
+    Noise(NoiseDensity),
+}
+
+/// Boxes `object`, wrapping it in a [`Transform`] only when it was moved.
+/// [`Parser::placement`] only builds invertible transforms.
+fn place(object: impl Hittable + 'static, to_world: Mat4) -> Object {
+    if to_world == Mat4::IDENTITY {
+        Box::new(object)
+    } else {
+        Box::new(Transform::new(object, to_world))
+    }
+}
+
+fn required<T>(value: Option<T>, name: &str, keyword: &Token) -> Result<T, SceneError> {
+    value.ok_or_else(|| keyword.error(format!("{} is missing `{name}`", keyword.kind)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{hittable::Hittable, inretval::Interval, ray::Ray, vec3::Point3};
+
+    fn parse(source: &str) -> Result<Scene, SceneError> {
+        Scene::parse(source, Path::new("."))
+    }
+
+    fn parse_error(source: &str) -> String {
+        match parse(source) {
+            Err(err) => err.to_string(),
+            Ok(_) => panic!("expected an error"),
+        }
+    }
+
+    #[test]
+    fn test_parse_scene() {
+        let scene = parse(
+            "# comment\n\
+             render { width 64 aspect 2 samples 3 depth 4 roulette 2 seed 5 mis balance }\n\
+             camera {\n  lookfrom 0 0 1\n  lookat 0 0 0\n  vfov 45\n}\n\
+             environment solid { color 0.1 0.2 0.3 }\n\
+             material red lambertian { albedo 1 0 0 }\n\
+             sphere { center 0 0 -1 radius 0.5 material red }\n\
+             triangle { vertices 0 0 0 1 0 0 0 1 0 material red }\n",
+        )
+        .unwrap();
+        assert_eq!(scene.camera.image_width, 64);
+        assert_eq!(scene.camera.aspect_ratio, 2.0);
+        assert_eq!(scene.camera.samples_per_pixel, 3);
+        assert_eq!(scene.camera.max_depth, 4);
+        assert_eq!(scene.camera.roulette_depth, 2);
+        assert_eq!(scene.camera.seed, 5);
+        assert_eq!(scene.camera.heuristic, Heuristic::Balance);
+        assert_eq!(scene.camera.vfov, 45.0);
+        assert_eq!(scene.camera.lookfrom, Point3::new(0.0, 0.0, 1.0));
+        assert_eq!(
+            scene.camera.background.value(Vec3::new(0.0, 1.0, 0.0)),
+            Color::new(0.1, 0.2, 0.3)
+        );
+        assert_eq!(scene.world.len(), 2);
+
+        let r = Ray::new(Point3::new(0.0, 0.0, 1.0), Vec3::new(0.0, 0.0, -1.0));
+        let rec = scene
+            .world
+            .hit(&r, Interval::new(0.001, f64::INFINITY))
+            .unwrap();
+        assert_eq!(rec.t, 1.0);
+    }
+
+    #[test]
+    fn test_image_texture() {
+        let dir = std::env::temp_dir().join(format!("rusty_rays_scene_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("tex.ppm"), "P3 1 1 255 255 0 0\n").unwrap();
+        let scene = Scene::parse(
+            "texture red image { file \"tex.ppm\" filter nearest address clamp }\n\
+             material m lambertian { texture red }\n\
+             sphere { center 0 0 -2 radius 1 material m }\n",
+            &dir,
+        )
+        .unwrap();
+        let r = Ray::new(Point3::origin(), Vec3::new(0.0, 0.0, -1.0));
+        let rec = scene
+            .world
+            .hit(&r, Interval::new(0.0, f64::INFINITY))
+            .unwrap();
+        let mut rng = crate::util::pixel_rng(1, 0, 0);
+        let (attenuation, _) = rec.mat.scatter(&r, &rec, &mut rng).unwrap();
+        assert_eq!(attenuation, Color::new(1.0, 0.0, 0.0));
+
+        let err = match Scene::parse("texture t image { file \"missing.png\" }", &dir) {
+            Err(err) => err.to_string(),
+            Ok(_) => panic!("expected an error"),
+        };
+        assert!(err.starts_with("1:24: missing.png: "), "{err}");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_procedural_textures() {
+        let scene = parse(
+            "texture tiles checker { scale 0.5 even 1 1 1 odd 0 0 0 space uv }\n\
+             texture n noise { scale 2 color 1 0 0 seed 3 }\n\
+             texture t turbulence { octaves 3 }\n\
+             texture m marble { scale 4 light 1 1 1 dark 0 0 0 octaves 5 distortion 8 }\n\
+             texture w wood { scale 2 distortion 0.2 seed 1 }\n\
+             material glow light { texture tiles }\n\
+             material veined metal { texture m fuzz 0.1 }\n\
+             sphere { center 0 0 -2 radius 1 material glow }\n",
+        )
+        .unwrap();
+        let r = Ray::new(Point3::origin(), Vec3::new(0.0, 0.0, -1.0));
+        let rec = scene
+            .world
+            .hit(&r, Interval::new(0.0, f64::INFINITY))
+            .unwrap();
+        let emitted = rec.mat.emitted(rec.u, rec.v, rec.p);
+        let expected = if ((rec.u * 2.0).floor() + (rec.v * 2.0).floor()) % 2.0 == 0.0 {
+            1.0
+        } else {
+            0.0
+        };
+        assert_eq!(emitted, Color::new(expected, expected, expected));
+
+        // Solid textures default to mid gray, patterns to white.
+        let scene = parse(
+            "texture plain solid { }\n\
+             material glow light { texture plain }\n\
+             sphere { center 0 0 -2 radius 1 material glow }\n",
+        )
+        .unwrap();
+        let rec = scene
+            .world
+            .hit(&r, Interval::new(0.0, f64::INFINITY))
+            .unwrap();
+        assert_eq!(
+            rec.mat.emitted(rec.u, rec.v, rec.p),
+            Color::new(0.5, 0.5, 0.5)
+        );
+
+        assert_eq!(
+            parse_error("texture w wood { octaves 3 }"),
+            "1:18: unknown property `octaves`"
+        );
+        assert_eq!(
+            parse_error("texture c checker { space polar }"),
+            "1:27: unknown checker space `polar`"
+        );
+    }
+
+    #[test]
+    fn test_errors_have_positions() {
+        assert_eq!(
+            parse_error("material red lambertian { albedo 1 0 0 }\nsphere { center 0 0 x }"),
+            "2:21: expected a number, found `x`"
+        );
+        assert_eq!(
+            parse_error("sphere { center 0 0 0 radius 1 material red }"),
+            "1:41: unknown material `red`"
+        );
+        assert_eq!(
+            parse_error("camera {\n    zoom 2\n}"),
+            "2:5: unknown property `zoom`"
+        );
+        assert_eq!(
+            parse_error("render { mis even }"),
+            "1:14: unknown heuristic `even`"
+        );
+        assert_eq!(
+            parse_error("render { integrator bdpt }"),
+            "1:21: unknown integrator `bdpt`"
+        );
+        assert_eq!(
+            parse_error("material m metal { albedo 1 1 1 }\n  sphere { radius 1 material m }"),
+            "2:3: `sphere` is missing `center`"
+        );
+        assert_eq!(
+            parse_error("render { samples 0 }"),
+            "1:18: expected a positive whole number, found 0"
+        );
+        assert_eq!(
+            parse_error("render { width 10"),
+            "1:18: expected a property or `}`, found end of file"
+        );
+        assert_eq!(
+            parse_error("material m metal { texture wood }"),
+            "1:28: unknown texture `wood`"
+        );
+        assert_eq!(
+            parse_error("texture t image { filter cubic }"),
+            "1:26: unknown filter `cubic`"
+        );
+        assert_eq!(
+            parse_error("mesh { file \"a.obj }"),
+            "1:13: unterminated string"
+        );
+        assert_eq!(
+            parse_error("box { scale 1 0 1 }"),
+            "1:15: expected a non-zero number"
+        );
+        assert_eq!(
+            parse_error("disk { rotate 0 0 0 90 }"),
+            "1:15: rotation axis must not be zero"
+        );
+        assert_eq!(
+            parse_error("sphere { scale 1e-200 1 1 scale 1e-200 1 1 }"),
+            "1:27: object transform cannot be inverted"
+        );
+        assert_eq!(
+            parse_error("quad { u 0 0 0 }"),
+            "1:10: quad side must not be zero"
+        );
+        assert_eq!(
+            parse_error(
+                "material m lambertian { albedo 1 1 1 }\n\
+                 quad { corner 0 0 0 u 1 0 0 v -2 0 0 material m }"
+            ),
+            "2:31: quad sides `u` and `v` must not be parallel"
+        );
+        assert_eq!(
+            parse_error("disk { normal 0 0 0 }"),
+            "1:15: disk normal must not be zero"
+        );
+        assert_eq!(
+            parse_error("medium { noise { threshold 1 } }"),
+            "1:28: threshold must be at least 0 and below 1, found 1"
+        );
+    }
+
+    #[test]
+    fn test_placement() {
+        let dir = std::env::temp_dir().join(format!("rusty_rays_place_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("tri.obj"),
+            "v -1 -1 0\nv 1 -1 0\nv 0 1 0\nf 1 2 3\n",
+        )
+        .unwrap();
+        let scene = Scene::parse(
+            "material m lambertian { albedo 1 1 1 }\n\
+             box { min 0 0 0 max 1 1 1 material m translate -0.5 -0.5 -0.5 rotate 0 1 0 90\n\
+                   scale 2 1 1 translate 0 0 -5 }\n\
+             mesh { file \"tri.obj\" material m translate 10 0 0 }\n\
+             mesh { file \"tri.obj\" material m euler 0 90 0 translate -10 0 0 }\n",
+            &dir,
+        )
+        .unwrap();
+        assert_eq!(scene.world.len(), 3);
+        let ray_t = Interval::new(0.001, f64::INFINITY);
+        // The centered cube is rotated a quarter turn before its x is doubled,
+        // so its front face still sits at z = -4.5.
+        let r = Ray::new(Point3::new(0.9, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        let rec = scene.world.hit(&r, ray_t).unwrap();
+        assert!((rec.t - 4.5).abs() < 1e-9);
+        assert!((rec.normal - Vec3::new(0.0, 0.0, 1.0)).length() < 1e-9);
+
+        // One copy of the mesh faces +z, the other was turned to face +x.
+        let r = Ray::new(Point3::new(10.0, 0.0, 3.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!((scene.world.hit(&r, ray_t).unwrap().t - 3.0).abs() < 1e-9);
+        let r = Ray::new(Point3::new(-7.0, 0.0, 0.0), Vec3::new(-1.0, 0.0, 0.0));
+        assert!((scene.world.hit(&r, ray_t).unwrap().t - 3.0).abs() < 1e-9);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_example_scene_parses() {
+        let scene = parse(include_str!("../scenes/three_spheres.scene")).unwrap();
+        assert_eq!(scene.world.len(), 5);
+        assert!(scene.lights.is_empty());
+        let scene = parse(include_str!("../scenes/cornell_box.scene")).unwrap();
+        assert_eq!(scene.world.len(), 8);
+        assert_eq!(scene.lights.len(), 1);
+        let scene = parse(include_str!("../scenes/cornell_smoke.scene")).unwrap();
+        assert_eq!(scene.world.len(), 8);
+        assert_eq!(scene.lights.len(), 1);
+        let scene = parse(include_str!("../scenes/cloud.scene")).unwrap();
+        assert_eq!(scene.world.len(), 2);
+    }
+
+    #[test]
+    fn test_lights() {
+        let scene = parse(
+            "material lamp light { emit 4 4 4 }\n\
+             material fog isotropic { albedo 1 1 1 }\n\
+             quad { corner 2 2 0 u 1 0 0 v 0 0 1 material lamp }\n\
+             sphere { center 0 5 0 radius 1 material lamp translate 1 0 0 }\n\
+             box { min 5 0 0 max 6 1 1 material lamp }\n\
+             medium { boundary sphere { center 0 0 0 radius 1 material lamp }\n\
+                      density 1 material fog }\n",
+        )
+        .unwrap();
+        // A box is not sampled and a boundary is never seen.
+        assert_eq!(scene.world.len(), 4);
+        assert_eq!(scene.lights.len(), 2);
+        // Lights are shared with the world, not copies.
+        let r = Ray::new(Point3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+        let ray_t = Interval::new(0.001, f64::INFINITY);
+        assert_eq!(scene.lights.hit(&r, ray_t).unwrap().t, 4.0);
+        assert_eq!(scene.world.hit(&r, ray_t).unwrap().t, 4.0);
+    }
+
+    #[test]
+    fn test_medium() {
+        let scene = parse(
+            "material fog isotropic { albedo 1 1 1 }\n\
+             medium { boundary sphere { center 0 0 0 radius 1 } density 1000 material fog\n\
+                      translate 0 0 -5 }\n",
+        )
+        .unwrap();
+        // Dense enough that the ray scatters just inside the boundary.
+        let r = Ray::new(Point3::origin(), Vec3::new(0.0, 0.0, -1.0));
+        let rec = scene
+            .world
+            .hit(&r, Interval::new(0.001, f64::INFINITY))
+            .unwrap();
+        assert!(rec.t > 4.0 && rec.t < 4.1, "{}", rec.t);
+
+        assert_eq!(
+            parse_error("material fog isotropic { }\nmedium { density 1 material fog }"),
+            "2:1: `medium` is missing `boundary`"
+        );
+        assert_eq!(
+            parse_error("medium { boundary fog }"),
+            "1:19: expected a shape, found `fog`"
+        );
+        // Only boundaries may leave out their material.
+        assert_eq!(
+            parse_error(
+                "medium { boundary sphere { center 0 0 0 radius 1 } }\nsphere { center 0 0 0 radius 1 }"
+            ),
+            "1:1: `medium` is missing `density`"
+        );
+        assert_eq!(
+            parse_error("sphere { center 0 0 0 radius 1 }"),
+            "1:1: `sphere` is missing `material`"
+        );
+    }
+
+    #[test]
+    fn test_heterogeneous_medium() {
+        let dir = std::env::temp_dir().join(format!("rusty_rays_grid_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        // Density ramps from zero up to 1000 between the voxel centers at
+        // z = -1 and 1, so a ray along +z scatters soon after z = -1.
+        fs::write(dir.join("half.txt"), "1 1 2\n0 1\n").unwrap();
+        let scene = Scene::parse(
+            "material fog isotropic { albedo 1 1 1 }\n\
+             medium { boundary box { min -1 -1 -2 max 1 1 2 } density 1000 material fog\n\
+                      grid { file \"half.txt\" } }\n\
+             medium { boundary sphere { center 5 0 0 radius 1 } density 2 material fog\n\
+                      noise { scale 3 seed 4 octaves 2 } }\n",
+            &dir,
+        )
+        .unwrap();
+        assert_eq!(scene.world.len(), 2);
+        let r = Ray::new(Point3::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0));
+        let rec = scene
+            .world
+            .hit(&r, Interval::new(0.001, f64::INFINITY))
+            .unwrap();
+        assert!(rec.p.z > -1.0 && rec.p.z < -0.8, "{:?}", rec.p);
+
+        let error = |source: &str| match Scene::parse(source, &dir) {
+            Err(err) => err.to_string(),
+            Ok(_) => panic!("expected an error"),
+        };
+        assert_eq!(
+            error("material fog isotropic { }\nmedium { grid { size 1 1 1 } }"),
+            "2:10: `grid` is missing `file`"
+        );
+        assert!(
+            error(
+                "material fog isotropic { }\n\
+                 medium { boundary sphere { center 0 0 0 radius 1 } density 1 material fog\n\
+                 grid { file \"half.txt\" size 2 2 2 } }"
+            )
+            .starts_with("3:13: half.txt: ")
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
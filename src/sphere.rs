@@ -1,21 +1,52 @@
+use std::f64::consts::PI;
+
 use crate::{
-    hittable::{HitRecord, Hittable}, inretval::Interval, material::Material, ray::Ray, vec3::{Vec3, Point3}
+    aabb::Aabb,
+    hittable::{HitRecord, Hittable},
+    inretval::Interval,
+    material::Material,
+    ray::Ray,
+    util::{Rng, random_f64},
+    vec3::{Onb, Point3, Vec3},
 };
 
 pub struct Sphere<'a> {
     pub center: Point3,
     pub radius: f64,
     pub mat: Box<dyn Material + 'a>,
+    bbox: Aabb,
 }
 
 impl<'a> Sphere<'a> {
     pub fn new(center: Point3, radius: f64, mat: impl Material + 'a) -> Self {
-        Self { center, radius, mat: Box::new(mat) }
+        let rvec = Vec3::new(radius, radius, radius);
+        Self {
+            center,
+            radius,
+            mat: Box::new(mat),
+            bbox: Aabb::from_points(center - rvec, center + rvec),
+        }
+    }
+
+    /// Maps a point on the unit sphere to (u, v) in [0, 1], with u running
+    /// around the Y axis starting at -X and v running from -Y up to +Y.
+    fn get_sphere_uv(p: Vec3) -> (f64, f64) {
+        let theta = (-p.y).clamp(-1.0, 1.0).acos();
+        let phi = f64::atan2(-p.z, p.x) + PI;
+        (phi / (2.0 * PI), theta / PI)
+    }
+
+    /// Cosine of the half-angle of the cone the sphere fills seen from
+    /// `origin`, or `None` from inside, where it fills every direction.
+    fn cone_cos_theta_max(&self, origin: Point3) -> Option<f64> {
+        let distance_squared = (self.center - origin).length_squared();
+        let ratio = self.radius * self.radius / distance_squared;
+        (ratio < 1.0).then(|| (1.0 - ratio).sqrt())
     }
 }
 
 impl<'a> Hittable for Sphere<'a> {
-    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord> {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
         let oc = r.origin - self.center;
         let a = r.direction.length_squared();
         let h = Vec3::dot(r.direction, oc);
@@ -36,7 +67,36 @@ impl<'a> Hittable for Sphere<'a> {
         let p = r.at(t);
         let normal = (p - self.center) / self.radius;
         let mut rec = HitRecord::new(t, p, normal, &*self.mat);
+        (rec.u, rec.v) = Self::get_sphere_uv(normal);
         rec.set_face_normal(r);
         Some(rec)
     }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    /// Uniform over the cone of directions the sphere covers, which wastes
+    /// none on its hidden back.
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f64 {
+        let Some(cos_theta_max) = self.cone_cos_theta_max(origin) else {
+            return 1.0 / (4.0 * PI);
+        };
+        let r = Ray::new(origin, direction);
+        if self.hit(&r, Interval::new(0.001, f64::INFINITY)).is_none() {
+            return 0.0;
+        }
+        1.0 / (2.0 * PI * (1.0 - cos_theta_max))
+    }
+
+    fn random(&self, origin: Point3, rng: &mut Rng) -> Vec3 {
+        let Some(cos_theta_max) = self.cone_cos_theta_max(origin) else {
+            return Vec3::random_unit_vector(rng);
+        };
+        let z = 1.0 + random_f64(rng) * (cos_theta_max - 1.0);
+        let phi = 2.0 * PI * random_f64(rng);
+        let sin_theta = (1.0 - z * z).max(0.0).sqrt();
+        let local = Vec3::new(phi.cos() * sin_theta, phi.sin() * sin_theta, z);
+        Onb::new(self.center - origin).to_world(local)
+    }
 }
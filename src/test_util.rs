@@ -0,0 +1,8 @@
+//! Fixtures shared by the unit tests.
+
+use crate::{color::Color, material::Lambertian};
+
+/// A plain diffuse material, for tests where the surface hardly matters.
+pub fn gray() -> Lambertian {
+    Lambertian::new(Color::new(0.5, 0.5, 0.5))
+}
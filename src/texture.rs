@@ -0,0 +1,399 @@
+//! Colors that vary over a surface.
+
+use std::{fs, io, path::Path, sync::Arc};
+
+use crate::{
+    color::Color, framebuffer::Framebuffer, perlin::Perlin, png::read_png, ppm::read_ppm,
+    vec3::Point3,
+};
+
+pub trait Texture: Send + Sync {
+    /// Color at the surface coordinates `u`, `v` of the hit point `p`.
+    fn value(&self, u: f64, v: f64, p: Point3) -> Color;
+}
+
+pub struct SolidColor {
+    albedo: Color,
+}
+
+impl SolidColor {
+    pub fn new(albedo: Color) -> Self {
+        Self { albedo }
+    }
+}
+
+impl Texture for SolidColor {
+    fn value(&self, _u: f64, _v: f64, _p: Point3) -> Color {
+        self.albedo
+    }
+}
+
+/// Where a [`CheckerTexture`] lays out its squares.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CheckerSpace {
+    /// Cubes in world space, so the pattern cuts through objects.
+    #[default]
+    Solid,
+    /// Squares over the surface coordinates.
+    Uv,
+}
+
+/// Alternates two textures in squares (or cubes) of edge `scale`.
+pub struct CheckerTexture {
+    inv_scale: f64,
+    even: Arc<dyn Texture>,
+    odd: Arc<dyn Texture>,
+    pub space: CheckerSpace,
+}
+
+impl CheckerTexture {
+    pub fn new(scale: f64, even: Arc<dyn Texture>, odd: Arc<dyn Texture>) -> Self {
+        Self {
+            inv_scale: 1.0 / scale,
+            even,
+            odd,
+            space: CheckerSpace::default(),
+        }
+    }
+
+    pub fn from_colors(scale: f64, even: Color, odd: Color) -> Self {
+        Self::new(
+            scale,
+            Arc::new(SolidColor::new(even)),
+            Arc::new(SolidColor::new(odd)),
+        )
+    }
+}
+
+impl Texture for CheckerTexture {
+    fn value(&self, u: f64, v: f64, p: Point3) -> Color {
+        let cell = |x: f64| (self.inv_scale * x).floor() as i64;
+        let sum = match self.space {
+            CheckerSpace::Solid => cell(p.x) + cell(p.y) + cell(p.z),
+            CheckerSpace::Uv => cell(u) + cell(v),
+        };
+        if sum.rem_euclid(2) == 0 {
+            self.even.value(u, v, p)
+        } else {
+            self.odd.value(u, v, p)
+        }
+    }
+}
+
+fn lerp(a: Color, b: Color, t: f64) -> Color {
+    (1.0 - t) * a + t * b
+}
+
+/// Perlin noise remapped to `[0, 1]` and tinted with `color`; `scale` is the
+/// frequency.
+pub struct NoiseTexture {
+    perlin: Perlin,
+    scale: f64,
+    color: Color,
+}
+
+impl NoiseTexture {
+    pub fn new(scale: f64, color: Color, seed: u64) -> Self {
+        Self {
+            perlin: Perlin::new(seed),
+            scale,
+            color,
+        }
+    }
+}
+
+impl Texture for NoiseTexture {
+    fn value(&self, _u: f64, _v: f64, p: Point3) -> Color {
+        0.5 * (1.0 + self.perlin.noise(self.scale * p)) * self.color
+    }
+}
+
+/// Fractal sum of noise octaves tinted with `color`.
+pub struct TurbulenceTexture {
+    perlin: Perlin,
+    scale: f64,
+    color: Color,
+    pub octaves: usize,
+}
+
+impl TurbulenceTexture {
+    pub fn new(scale: f64, color: Color, seed: u64) -> Self {
+        Self {
+            perlin: Perlin::new(seed),
+            scale,
+            color,
+            octaves: 7,
+        }
+    }
+}
+
+impl Texture for TurbulenceTexture {
+    fn value(&self, _u: f64, _v: f64, p: Point3) -> Color {
+        let t = self.perlin.turbulence(self.scale * p, self.octaves);
+        t.min(1.0) * self.color
+    }
+}
+
+/// Veins along the z axis, a sine wave phase shifted by turbulence.
+pub struct MarbleTexture {
+    perlin: Perlin,
+    scale: f64,
+    light: Color,
+    dark: Color,
+    pub octaves: usize,
+    /// How strongly turbulence bends the veins.
+    pub distortion: f64,
+}
+
+impl MarbleTexture {
+    pub fn new(scale: f64, light: Color, dark: Color, seed: u64) -> Self {
+        Self {
+            perlin: Perlin::new(seed),
+            scale,
+            light,
+            dark,
+            octaves: 7,
+            distortion: 10.0,
+        }
+    }
+}
+
+impl Texture for MarbleTexture {
+    fn value(&self, _u: f64, _v: f64, p: Point3) -> Color {
+        let turbulence = self.perlin.turbulence(p, self.octaves);
+        let t = 0.5 * (1.0 + (self.scale * p.z + self.distortion * turbulence).sin());
+        lerp(self.dark, self.light, t)
+    }
+}
+
+/// Growth rings around the y axis, `scale` rings per unit, warped by noise.
+pub struct WoodTexture {
+    perlin: Perlin,
+    scale: f64,
+    light: Color,
+    dark: Color,
+    /// How far, in rings, noise pushes the rings around.
+    pub distortion: f64,
+}
+
+impl WoodTexture {
+    pub fn new(scale: f64, light: Color, dark: Color, seed: u64) -> Self {
+        Self {
+            perlin: Perlin::new(seed),
+            scale,
+            light,
+            dark,
+            distortion: 0.4,
+        }
+    }
+}
+
+impl Texture for WoodTexture {
+    fn value(&self, _u: f64, _v: f64, p: Point3) -> Color {
+        let q = self.scale * p;
+        let radius = (q.x * q.x + q.z * q.z).sqrt();
+        let rings = radius + self.distortion * self.perlin.noise(q);
+        // Light early wood fading into a darker band.
+        let t = (rings - rings.floor()).powi(3);
+        lerp(self.light, self.dark, t)
+    }
+}
+
+/// How texels are combined into a value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Filter {
+    Nearest,
+    #[default]
+    Bilinear,
+}
+
+/// What lies outside the `[0, 1]` range of texture coordinates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AddressMode {
+    /// Repeats the image.
+    #[default]
+    Wrap,
+    /// Extends the edge texels.
+    Clamp,
+    /// Repeats the image, flipping every other copy.
+    Mirror,
+}
+
+impl AddressMode {
+    /// Maps a texel index, possibly outside the image, to one inside it.
+    fn apply(self, i: i64, size: usize) -> usize {
+        let n = size as i64;
+        let i = match self {
+            AddressMode::Wrap => i.rem_euclid(n),
+            AddressMode::Clamp => i.clamp(0, n - 1),
+            AddressMode::Mirror => {
+                let i = i.rem_euclid(2 * n);
+                if i < n { i } else { 2 * n - 1 - i }
+            }
+        };
+        i as usize
+    }
+}
+
+/// A bitmap mapped onto the surface with `u` running left to right and `v`
+/// bottom to top.
+pub struct ImageTexture {
+    image: Framebuffer,
+    pub filter: Filter,
+    pub address: AddressMode,
+}
+
+impl ImageTexture {
+    pub fn new(image: Framebuffer) -> Self {
+        Self {
+            image,
+            filter: Filter::default(),
+            address: AddressMode::default(),
+        }
+    }
+
+    /// Loads a PNG, PPM or PGM file, told apart by their first bytes.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let data = fs::read(path)?;
+        let image = if data.starts_with(b"\x89PNG") {
+            read_png(&data[..])?
+        } else if data.starts_with(b"P") {
+            read_ppm(&data[..])?
+        } else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "unsupported image format",
+            ));
+        };
+        Ok(Self::new(image))
+    }
+
+    pub fn image(&self) -> &Framebuffer {
+        &self.image
+    }
+
+    fn texel(&self, x: i64, y: i64) -> Color {
+        let x = self.address.apply(x, self.image.width());
+        let y = self.address.apply(y, self.image.height());
+        self.image.get(x, y)
+    }
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, u: f64, v: f64, _p: Point3) -> Color {
+        // Cyan makes a missing image stand out.
+        if self.image.width() == 0 || self.image.height() == 0 {
+            return Color::new(0.0, 1.0, 1.0);
+        }
+        let x = u * self.image.width() as f64;
+        let y = (1.0 - v) * self.image.height() as f64;
+        match self.filter {
+            Filter::Nearest => self.texel(x.floor() as i64, y.floor() as i64),
+            Filter::Bilinear => {
+                // Texel centers sit at half-integer coordinates.
+                let (x, y) = (x - 0.5, y - 0.5);
+                let (x0, y0) = (x.floor(), y.floor());
+                let (fx, fy) = (x - x0, y - y0);
+                let (x0, y0) = (x0 as i64, y0 as i64);
+                let top = (1.0 - fx) * self.texel(x0, y0) + fx * self.texel(x0 + 1, y0);
+                let bottom = (1.0 - fx) * self.texel(x0, y0 + 1) + fx * self.texel(x0 + 1, y0 + 1);
+                (1.0 - fy) * top + fy * bottom
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 2x2 image: black, red on top; green, blue below.
+    fn texture() -> ImageTexture {
+        let mut image = Framebuffer::new(2, 2);
+        image.set(1, 0, Color::new(1.0, 0.0, 0.0));
+        image.set(0, 1, Color::new(0.0, 1.0, 0.0));
+        image.set(1, 1, Color::new(0.0, 0.0, 1.0));
+        ImageTexture::new(image)
+    }
+
+    #[test]
+    fn test_address_modes() {
+        let modes = [AddressMode::Wrap, AddressMode::Clamp, AddressMode::Mirror];
+        let indices = |mode: AddressMode| [-3, -1, 0, 3, 4, 5].map(|i| mode.apply(i, 3));
+        assert_eq!(indices(modes[0]), [0, 2, 0, 0, 1, 2]);
+        assert_eq!(indices(modes[1]), [0, 0, 0, 2, 2, 2]);
+        assert_eq!(indices(modes[2]), [2, 0, 0, 2, 1, 0]);
+    }
+
+    #[test]
+    fn test_nearest() {
+        let mut tex = texture();
+        tex.filter = Filter::Nearest;
+        let p = Point3::origin();
+        assert_eq!(tex.value(0.75, 0.75, p), Color::new(1.0, 0.0, 0.0));
+        assert_eq!(tex.value(0.25, 0.25, p), Color::new(0.0, 1.0, 0.0));
+        assert_eq!(tex.value(1.25, -0.75, p), Color::new(0.0, 1.0, 0.0));
+        tex.address = AddressMode::Clamp;
+        assert_eq!(tex.value(1.25, -0.75, p), Color::new(0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn test_bilinear() {
+        let mut tex = texture();
+        let p = Point3::origin();
+        // The image center averages all four texels.
+        assert_eq!(tex.value(0.5, 0.5, p), Color::new(0.25, 0.25, 0.25));
+        // Texel centers are exact.
+        assert_eq!(tex.value(0.75, 0.25, p), Color::new(0.0, 0.0, 1.0));
+        // Clamped edges do not bleed into the opposite side.
+        tex.address = AddressMode::Clamp;
+        assert_eq!(tex.value(0.0, 1.0, p), Color::new(0.0, 0.0, 0.0));
+        tex.address = AddressMode::Wrap;
+        assert_eq!(tex.value(0.0, 1.0, p), Color::new(0.25, 0.25, 0.25));
+    }
+
+    #[test]
+    fn test_checker() {
+        let black = Color::new(0.0, 0.0, 0.0);
+        let white = Color::new(1.0, 1.0, 1.0);
+        let mut checker = CheckerTexture::from_colors(0.5, white, black);
+        let at = |x, y, z| Point3::new(x, y, z);
+        assert_eq!(checker.value(0.0, 0.0, at(0.1, 0.1, 0.1)), white);
+        assert_eq!(checker.value(0.0, 0.0, at(0.6, 0.1, 0.1)), black);
+        assert_eq!(checker.value(0.0, 0.0, at(-0.1, 0.1, 0.1)), black);
+        assert_eq!(checker.value(0.0, 0.0, at(-0.1, -0.1, 0.1)), white);
+
+        checker.space = CheckerSpace::Uv;
+        let p = at(0.6, 0.1, 0.1);
+        assert_eq!(checker.value(0.25, 0.25, p), white);
+        assert_eq!(checker.value(0.75, 0.25, p), black);
+        assert_eq!(checker.value(0.75, 0.75, p), white);
+    }
+
+    #[test]
+    fn test_procedural_ranges() {
+        let light = Color::new(0.9, 0.8, 0.7);
+        let dark = Color::new(0.3, 0.2, 0.1);
+        let textures: [Box<dyn Texture>; 4] = [
+            Box::new(NoiseTexture::new(4.0, light, 1)),
+            Box::new(TurbulenceTexture::new(4.0, light, 1)),
+            Box::new(MarbleTexture::new(4.0, light, dark, 1)),
+            Box::new(WoodTexture::new(4.0, light, dark, 1)),
+        ];
+        let mut rng = crate::util::Rng::with_seed(3);
+        for tex in &textures {
+            let mut seen = Vec::new();
+            for _ in 0..200 {
+                let p = Point3::random_range(&mut rng, -2.0, 2.0);
+                let c = tex.value(0.0, 0.0, p);
+                assert!(c.r >= 0.0 && c.r <= light.r + 1e-9, "{c:?}");
+                assert!(c.b >= 0.0 && c.b <= light.b + 1e-9, "{c:?}");
+                seen.push(c.r);
+            }
+            // Every pattern actually varies.
+            let min = seen.iter().cloned().fold(f64::INFINITY, f64::min);
+            let max = seen.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+            assert!(max - min > 0.2, "{min} {max}");
+        }
+    }
+}
@@ -0,0 +1,222 @@
+use crate::{
+    aabb::Aabb,
+    hittable::{HitRecord, Hittable},
+    inretval::Interval,
+    ray::Ray,
+    util::Rng,
+    vec3::{Mat4, Normal3, Point3, Vec3},
+};
+
+/// Places an object in the world through an affine matrix.
+///
+/// Rays are taken into object space and hits brought back, so the wrapped
+/// object never changes. Wrap an `Arc` to place one object, such as a large
+/// mesh, several times without copying it.
+pub struct Transform<'a> {
+    object: Box<dyn Hittable + 'a>,
+    to_world: Mat4,
+    to_object: Mat4,
+    bbox: Aabb,
+}
+
+impl<'a> Transform<'a> {
+    /// Panics if `to_world` cannot be inverted, e.g. scales an axis by zero;
+    /// see [`try_new`](Self::try_new).
+    pub fn new(object: impl Hittable + 'a, to_world: Mat4) -> Self {
+        Self::try_new(object, to_world).expect("object transform must be invertible")
+    }
+
+    /// `None` if `to_world` cannot be inverted.
+    pub fn try_new(object: impl Hittable + 'a, to_world: Mat4) -> Option<Self> {
+        let to_object = to_world.inverse()?;
+        let bbox = transform_box(object.bounding_box(), &to_world);
+        Some(Self {
+            object: Box::new(object),
+            to_world,
+            to_object,
+            bbox,
+        })
+    }
+
+    pub fn translate(object: impl Hittable + 'a, offset: Vec3) -> Self {
+        Self::new(object, Mat4::translation(offset))
+    }
+
+    /// Rotation by `degrees` about `axis` through the origin.
+    pub fn rotate(object: impl Hittable + 'a, axis: Vec3, degrees: f64) -> Self {
+        Self::new(object, Mat4::rotation(axis, degrees))
+    }
+
+    /// Rotation about x, then y, then z through the origin.
+    pub fn rotate_euler(object: impl Hittable + 'a, degrees: Vec3) -> Self {
+        Self::new(object, Mat4::rotation_euler(degrees))
+    }
+
+    pub fn scale(object: impl Hittable + 'a, factors: Vec3) -> Self {
+        Self::new(object, Mat4::scaling(factors))
+    }
+
+    pub fn to_world(&self) -> &Mat4 {
+        &self.to_world
+    }
+
+    /// `r` in object space. The direction is not renormalized, so `t` means
+    /// the same in both spaces.
+    fn local_ray(&self, r: &Ray) -> Ray {
+        Ray::new(
+            self.to_object.transform_point(r.origin),
+            self.to_object.transform_vector(r.direction),
+        )
+    }
+}
+
+impl Hittable for Transform<'_> {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        let mut rec = self.object.hit(&self.local_ray(r), ray_t)?;
+        rec.p = self.to_world.transform_point(rec.p);
+        // Mapping the normal keeps the sign of its dot product with the ray,
+        // so `front_face` still holds.
+        let normal = Normal3::from(rec.normal).transform(&self.to_object);
+        rec.normal = Vec3::from(normal).unit_vector();
+        Some(rec)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    fn transmittance(&self, r: &Ray, ray_t: Interval, rng: &mut Rng) -> f64 {
+        self.object.transmittance(&self.local_ray(r), ray_t, rng)
+    }
+
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f64 {
+        let local = self.local_ray(&Ray::new(origin, direction));
+        let pdf = self.object.pdf_value(local.origin, local.direction);
+        if pdf == 0.0 {
+            return pdf;
+        }
+        // A linear map M stretches solid angle around a unit direction w by
+        // |det M| / |M w|^3; scaling unevenly or shearing changes it.
+        let to_object = self.to_object.linear();
+        let stretch = (to_object * direction.unit_vector()).length().powi(3);
+        pdf * to_object.determinant().abs() / stretch
+    }
+
+    fn random(&self, origin: Point3, rng: &mut Rng) -> Vec3 {
+        let local = self
+            .object
+            .random(self.to_object.transform_point(origin), rng);
+        self.to_world.transform_vector(local)
+    }
+}
+
+/// World box around the eight transformed corners of `bbox`.
+fn transform_box(bbox: Aabb, m: &Mat4) -> Aabb {
+    if bbox.is_empty() {
+        return bbox;
+    }
+    let mut min = Point3::new(f64::INFINITY, f64::INFINITY, f64::INFINITY);
+    let mut max = Point3::new(-f64::INFINITY, -f64::INFINITY, -f64::INFINITY);
+    for corner in 0..8 {
+        let pick = |axis: usize, interval: Interval| {
+            if corner >> axis & 1 == 0 {
+                interval.min
+            } else {
+                interval.max
+            }
+        };
+        let p = m.transform_point(Point3::new(
+            pick(0, bbox.x),
+            pick(1, bbox.y),
+            pick(2, bbox.z),
+        ));
+        min = Point3::min(min, p);
+        max = Point3::max(max, p);
+    }
+    Aabb::from_points(min, max)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{hittable_list::HittableList, quad::BoxShape, sphere::Sphere, test_util::gray};
+
+    #[test]
+    fn test_translate_and_scale() {
+        let ray_t = Interval::new(0.001, f64::INFINITY);
+        let sphere = Transform::translate(
+            Sphere::new(Point3::origin(), 1.0, gray()),
+            Vec3::new(0.0, 0.0, -5.0),
+        );
+        let rec = sphere
+            .hit(
+                &Ray::new(Point3::origin(), Vec3::new(0.0, 0.0, -1.0)),
+                ray_t,
+            )
+            .unwrap();
+        assert_eq!(rec.t, 4.0);
+        assert_eq!(rec.p, Point3::new(0.0, 0.0, -4.0));
+        assert_eq!(rec.normal, Vec3::new(0.0, 0.0, 1.0));
+        assert!(rec.front_face);
+
+        // On an ellipsoid stretched along x the normal is the gradient of
+        // x^2 / 4 + y^2 + z^2, not the scaled sphere normal.
+        let ellipsoid = Transform::scale(
+            Sphere::new(Point3::origin(), 1.0, gray()),
+            Vec3::new(2.0, 1.0, 1.0),
+        );
+        let z = 0.5f64.sqrt();
+        let r = Ray::new(Point3::new(1.0, 0.5, 5.0), Vec3::new(0.0, 0.0, -1.0));
+        let rec = ellipsoid.hit(&r, ray_t).unwrap();
+        assert!((rec.t - (5.0 - z)).abs() < 1e-9);
+        let expected = Vec3::new(0.25, 0.5, z).unit_vector();
+        assert!((rec.normal - expected).length() < 1e-6);
+        let bbox = ellipsoid.bounding_box();
+        assert!((bbox.x.max - 2.0).abs() < 1e-9 && (bbox.y.max - 1.0).abs() < 1e-9);
+
+        let flat = Mat4::scaling(Vec3::new(0.0, 1.0, 1.0));
+        assert!(Transform::try_new(Sphere::new(Point3::origin(), 1.0, gray()), flat).is_none());
+    }
+
+    #[test]
+    fn test_rotated_box() {
+        let cube = Transform::rotate(
+            BoxShape::new(Point3::origin(), Point3::new(1.0, 1.0, 1.0), gray()),
+            Vec3::new(0.0, 1.0, 0.0),
+            45.0,
+        );
+        // The corner at (1, 0, 1) swings round to (sqrt(2), 0, 0); the box's
+        // flat sides are padded slightly.
+        let bbox = cube.bounding_box();
+        assert!((bbox.x.max - 2f64.sqrt()).abs() < 1e-3);
+        assert!((bbox.z.min + 2f64.sqrt() / 2.0).abs() < 1e-3);
+
+        let ray_t = Interval::new(0.001, f64::INFINITY);
+        // Just behind the corner the ray meets the rotated x = 1 face.
+        let r = Ray::new(Point3::new(5.0, 0.5, -0.3), Vec3::new(-1.0, 0.0, 0.0));
+        let rec = cube.hit(&r, ray_t).unwrap();
+        assert!((rec.p.x - (2f64.sqrt() - 0.3)).abs() < 1e-9);
+        let expected = Vec3::new(1.0, 0.0, -1.0).unit_vector();
+        assert!((rec.normal - expected).length() < 1e-9);
+        assert!(rec.front_face);
+    }
+
+    #[test]
+    fn test_shared_instances() {
+        let sphere = Arc::new(Sphere::new(Point3::origin(), 1.0, gray()));
+        let mut world = HittableList::new();
+        for x in [-3.0, 3.0] {
+            world.add(Transform::translate(sphere.clone(), Vec3::new(x, 0.0, 0.0)));
+        }
+        assert_eq!(Arc::strong_count(&sphere), 3);
+        let ray_t = Interval::new(0.001, f64::INFINITY);
+        for x in [-3.0, 3.0] {
+            let r = Ray::new(Point3::new(x, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0));
+            assert_eq!(world.hit(&r, ray_t).unwrap().t, 4.0);
+        }
+        let r = Ray::new(Point3::new(0.0, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(world.hit(&r, ray_t).is_none());
+    }
+}
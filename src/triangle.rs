@@ -0,0 +1,252 @@
+use crate::{
+    aabb::Aabb,
+    hittable::{HitRecord, Hittable, area_to_solid_angle},
+    inretval::Interval,
+    material::Material,
+    ray::Ray,
+    util::{Rng, random_f64},
+    vec3::{Point3, Vec3},
+};
+
+pub struct Triangle<'a> {
+    pub vertices: [Point3; 3],
+    /// Per-vertex shading normals; the flat face normal is used without them.
+    pub normals: Option<[Vec3; 3]>,
+    /// Per-vertex texture coordinates; barycentrics are used without them.
+    pub uvs: Option<[(f64, f64); 3]>,
+    pub mat: Box<dyn Material + 'a>,
+    bbox: Aabb,
+}
+
+impl<'a> Triangle<'a> {
+    pub fn new(p0: Point3, p1: Point3, p2: Point3, mat: impl Material + 'a) -> Self {
+        let bbox = Aabb::surrounding(Aabb::from_points(p0, p1), Aabb::from_points(p1, p2));
+        Self {
+            vertices: [p0, p1, p2],
+            normals: None,
+            uvs: None,
+            mat: Box::new(mat),
+            bbox,
+        }
+    }
+
+    pub fn with_normals(mut self, normals: [Vec3; 3]) -> Self {
+        self.normals = Some(normals);
+        self
+    }
+
+    pub fn with_uvs(mut self, uvs: [(f64, f64); 3]) -> Self {
+        self.uvs = Some(uvs);
+        self
+    }
+}
+
+impl<'a> Hittable for Triangle<'a> {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        let (t, b) = intersect(r, ray_t, &self.vertices)?;
+        Some(surface_hit(
+            r,
+            t,
+            b,
+            &self.vertices,
+            self.normals.as_ref(),
+            self.uvs.as_ref(),
+            &*self.mat,
+        ))
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f64 {
+        let r = Ray::new(origin, direction);
+        let Some((t, _)) = intersect(&r, Interval::new(0.001, f64::INFINITY), &self.vertices)
+        else {
+            return 0.0;
+        };
+        let [p0, p1, p2] = self.vertices;
+        let n = Vec3::cross(p1 - p0, p2 - p0);
+        area_to_solid_angle(direction, t, n, n.length() / 2.0)
+    }
+
+    /// Uniform over the triangle's area.
+    fn random(&self, origin: Point3, rng: &mut Rng) -> Vec3 {
+        let [p0, p1, p2] = self.vertices;
+        let s = random_f64(rng).sqrt();
+        let t = random_f64(rng);
+        let p = p0 + s * (1.0 - t) * (p1 - p0) + s * t * (p2 - p0);
+        p - origin
+    }
+}
+
+/// Watertight ray/triangle test (Woop, Benthin and Wald, 2013).
+///
+/// Returns the ray parameter and the barycentric weights of the three
+/// vertices. Rays through a shared edge or vertex hit at least one of the
+/// adjacent triangles, so closed meshes do not leak.
+pub(crate) fn intersect(r: &Ray, ray_t: Interval, p: &[Point3; 3]) -> Option<(f64, [f64; 3])> {
+    let dir = r.direction;
+
+    // Permute axes so the dominant direction component becomes z.
+    let kz = if dir.x.abs() > dir.y.abs() {
+        if dir.x.abs() > dir.z.abs() { 0 } else { 2 }
+    } else if dir.y.abs() > dir.z.abs() {
+        1
+    } else {
+        2
+    };
+    let mut kx = (kz + 1) % 3;
+    let mut ky = (kx + 1) % 3;
+    if dir[kz] < 0.0 {
+        std::mem::swap(&mut kx, &mut ky);
+    }
+
+    // Shear so the ray points along +z in its own coordinate frame.
+    let sx = dir[kx] / dir[kz];
+    let sy = dir[ky] / dir[kz];
+    let sz = 1.0 / dir[kz];
+
+    let a = p[0] - r.origin;
+    let b = p[1] - r.origin;
+    let c = p[2] - r.origin;
+    let ax = a[kx] - sx * a[kz];
+    let ay = a[ky] - sy * a[kz];
+    let bx = b[kx] - sx * b[kz];
+    let by = b[ky] - sy * b[kz];
+    let cx = c[kx] - sx * c[kz];
+    let cy = c[ky] - sy * c[kz];
+
+    // Scaled barycentrics; an edge function of exactly zero counts as inside.
+    let u = cx * by - cy * bx;
+    let v = ax * cy - ay * cx;
+    let w = bx * ay - by * ax;
+    if (u < 0.0 || v < 0.0 || w < 0.0) && (u > 0.0 || v > 0.0 || w > 0.0) {
+        return None;
+    }
+    let det = u + v + w;
+    if det == 0.0 {
+        return None;
+    }
+
+    let t = (u * sz * a[kz] + v * sz * b[kz] + w * sz * c[kz]) / det;
+    if !ray_t.surrounds(t) {
+        return None;
+    }
+    Some((t, [u / det, v / det, w / det]))
+}
+
+/// Builds the hit record for a triangle hit, interpolating the optional
+/// per-vertex normals and texture coordinates with the barycentrics `b`.
+pub(crate) fn surface_hit<'m>(
+    r: &Ray,
+    t: f64,
+    b: [f64; 3],
+    p: &[Point3; 3],
+    normals: Option<&[Vec3; 3]>,
+    uvs: Option<&[(f64, f64); 3]>,
+    mat: &'m dyn Material,
+) -> HitRecord<'m> {
+    let geometric = Vec3::cross(p[1] - p[0], p[2] - p[0]).unit_vector();
+    let front_face = Vec3::dot(r.direction, geometric) < 0.0;
+
+    let mut normal = match normals {
+        Some(n) => (b[0] * n[0] + b[1] * n[1] + b[2] * n[2]).unit_vector(),
+        None => geometric,
+    };
+    // Keep the shading normal on the same side as the geometric one.
+    if Vec3::dot(normal, geometric) < 0.0 {
+        normal = -normal;
+    }
+
+    let mut rec = HitRecord::new(t, r.at(t), normal, mat);
+    (rec.u, rec.v) = match uvs {
+        Some(uv) => (
+            b[0] * uv[0].0 + b[1] * uv[1].0 + b[2] * uv[2].0,
+            b[0] * uv[0].1 + b[1] * uv[1].1 + b[2] * uv[2].1,
+        ),
+        None => (b[1], b[2]),
+    };
+    rec.front_face = front_face;
+    if !front_face {
+        rec.normal = -rec.normal;
+    }
+    rec
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        test_util::gray,
+        util::{Rng, random_f64},
+    };
+
+    #[test]
+    fn test_barycentric_hit() {
+        let tri = Triangle::new(
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(1.0, 0.0, 0.0),
+            Point3::new(0.0, 1.0, 0.0),
+            gray(),
+        );
+        let r = Ray::new(Point3::new(0.25, 0.5, 2.0), Vec3::new(0.0, 0.0, -1.0));
+        let rec = tri.hit(&r, Interval::new(0.001, f64::INFINITY)).unwrap();
+        assert_eq!(rec.t, 2.0);
+        assert_eq!(rec.p, Point3::new(0.25, 0.5, 0.0));
+        assert_eq!((rec.u, rec.v), (0.25, 0.5));
+        assert!(rec.front_face);
+        assert_eq!(rec.normal, Vec3::new(0.0, 0.0, 1.0));
+
+        let miss = Ray::new(Point3::new(0.75, 0.5, 2.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(
+            tri.hit(&miss, Interval::new(0.001, f64::INFINITY))
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_shared_edge_is_watertight() {
+        let p = [
+            Point3::new(-1.3, -0.7, 0.1),
+            Point3::new(2.1, -0.9, -0.4),
+            Point3::new(1.7, 1.9, 0.3),
+            Point3::new(-0.8, 1.6, -0.2),
+        ];
+        let first = Triangle::new(p[0], p[1], p[2], gray());
+        let second = Triangle::new(p[0], p[2], p[3], gray());
+
+        let mut rng = Rng::with_seed(9);
+        let origin = Point3::new(0.1, 0.2, 5.0);
+        for _ in 0..10_000 {
+            let s = random_f64(&mut rng);
+            let target = p[0] + s * (p[2] - p[0]);
+            let r = Ray::new(origin, target - origin);
+            let ray_t = Interval::new(0.001, f64::INFINITY);
+            assert!(first.hit(&r, ray_t).is_some() || second.hit(&r, ray_t).is_some());
+        }
+    }
+
+    #[test]
+    fn test_interpolated_attributes() {
+        let tri = Triangle::new(
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(1.0, 0.0, 0.0),
+            Point3::new(0.0, 1.0, 0.0),
+            gray(),
+        )
+        .with_normals([
+            Vec3::new(0.0, 0.0, 1.0),
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, 1.0),
+        ])
+        .with_uvs([(0.0, 0.0), (1.0, 0.0), (1.0, 1.0)]);
+
+        let r = Ray::new(Point3::new(0.5, 0.0, -1.0), Vec3::new(0.0, 0.0, 1.0));
+        let rec = tri.hit(&r, Interval::new(0.001, f64::INFINITY)).unwrap();
+        assert!(!rec.front_face);
+        let expected = -Vec3::new(0.5, 0.0, 0.5).unit_vector();
+        assert!((rec.normal - expected).near_zero());
+        assert_eq!((rec.u, rec.v), (0.5, 0.0));
+    }
+}
@@ -1,7 +1,34 @@
-pub fn random_f64() -> f64 {
-    fastrand::f64()
+pub use fastrand::Rng;
+
+pub fn random_f64(rng: &mut Rng) -> f64 {
+    rng.f64()
+}
+
+pub fn random_f64_range(rng: &mut Rng, min: f64, max: f64) -> f64 {
+    min + (max - min) * (random_f64(rng))
+}
+
+/// Random stream for a single pixel, independent of the order pixels are rendered in.
+pub fn pixel_rng(seed: u64, i: usize, j: usize) -> Rng {
+    let mut h = splitmix64(seed);
+    h = splitmix64(h ^ i as u64);
+    h = splitmix64(h ^ j as u64);
+    Rng::with_seed(h)
+}
+
+/// Random stream keyed by the exact bits of `values`, for code that has no
+/// stream of its own to draw from: the same values always give the same
+/// numbers, so renders stay reproducible.
+pub fn hash_rng(values: &[f64]) -> Rng {
+    let h = values
+        .iter()
+        .fold(0, |h, value| splitmix64(h ^ value.to_bits()));
+    Rng::with_seed(h)
 }
 
-pub fn random_f64_range(min: f64, max: f64) -> f64 {
-    min + (max - min) * (random_f64())
+fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
 }
@@ -1,9 +1,6 @@
-use std::{
-    cmp,
-    ops::{Add, Div, Mul, Neg, Sub},
-};
+use std::ops::{Add, AddAssign, Div, DivAssign, Index, Mul, MulAssign, Neg, Sub, SubAssign};
 
-use crate::util::{random_f64, random_f64_range};
+use crate::util::{Rng, random_f64, random_f64_range};
 
 #[derive(Debug, Clone, Copy, PartialEq, Default)]
 pub struct Vec3 {
@@ -20,14 +17,14 @@ impl Vec3 {
     pub fn zero() -> Self {
         Vec3::new(0.0, 0.0, 0.0)
     }
-    pub fn random() -> Vec3 {
-        Vec3::new(random_f64(), random_f64(), random_f64())
+    pub fn random(rng: &mut Rng) -> Vec3 {
+        Vec3::new(random_f64(rng), random_f64(rng), random_f64(rng))
     }
-    pub fn random_range(min: f64, max: f64) -> Vec3 {
+    pub fn random_range(rng: &mut Rng, min: f64, max: f64) -> Vec3 {
         Vec3::new(
-            random_f64_range(min, max),
-            random_f64_range(min, max),
-            random_f64_range(min, max),
+            random_f64_range(rng, min, max),
+            random_f64_range(rng, min, max),
+            random_f64_range(rng, min, max),
         )
     }
 
@@ -45,17 +42,17 @@ impl Vec3 {
     pub fn unit_vector(self) -> Vec3 {
         self / self.length()
     }
-    pub fn random_unit_vector() -> Vec3 {
+    pub fn random_unit_vector(rng: &mut Rng) -> Vec3 {
         loop {
-            let p = Vec3::random_range(-1.0, 1.0);
+            let p = Vec3::random_range(rng, -1.0, 1.0);
             let lensq = p.length_squared();
             if 1e-160 < lensq && lensq <= 1.0 {
                 return p / lensq.sqrt();
             }
         }
     }
-    pub fn random_on_hemisphere(normal: Vec3) -> Vec3 {
-        let on_unit_sphere = Vec3::random_unit_vector();
+    pub fn random_on_hemisphere(rng: &mut Rng, normal: Vec3) -> Vec3 {
+        let on_unit_sphere = Vec3::random_unit_vector(rng);
         if Vec3::dot(on_unit_sphere, normal) > 0.0 {
             on_unit_sphere
         } else {
@@ -81,11 +78,11 @@ impl Vec3 {
     pub fn dot(a: Vec3, b: Vec3) -> f64 {
         a.x * b.x + a.y * b.y + a.z * b.z
     }
-    pub fn random_in_unit_disk() -> Vec3 {
+    pub fn random_in_unit_disk(rng: &mut Rng) -> Vec3 {
         loop {
             let p = Vec3::new(
-                random_f64_range(-1.0, 1.0),
-                random_f64_range(-1.0, 1.0),
+                random_f64_range(rng, -1.0, 1.0),
+                random_f64_range(rng, -1.0, 1.0),
                 0.0,
             );
             if p.length_squared() < 1.0 {
@@ -135,7 +132,593 @@ impl Neg for Vec3 {
     }
 }
 
-pub type Point3 = Vec3;
+impl Index<usize> for Vec3 {
+    type Output = f64;
+
+    fn index(&self, axis: usize) -> &Self::Output {
+        match axis {
+            0 => &self.x,
+            1 => &self.y,
+            2 => &self.z,
+            _ => panic!("Vec3 axis out of range: {axis}"),
+        }
+    }
+}
+
+impl Mul<f64> for Vec3 {
+    type Output = Vec3;
+
+    fn mul(self, rhs: f64) -> Self::Output {
+        rhs * self
+    }
+}
+
+impl AddAssign for Vec3 {
+    fn add_assign(&mut self, rhs: Self) {
+        *self = *self + rhs;
+    }
+}
+
+impl SubAssign for Vec3 {
+    fn sub_assign(&mut self, rhs: Self) {
+        *self = *self - rhs;
+    }
+}
+
+impl MulAssign<f64> for Vec3 {
+    fn mul_assign(&mut self, rhs: f64) {
+        *self = rhs * *self;
+    }
+}
+
+impl DivAssign<f64> for Vec3 {
+    fn div_assign(&mut self, rhs: f64) {
+        *self = *self / rhs;
+    }
+}
+
+/// A position. Unlike [`Vec3`] two points cannot be added: the difference of
+/// two points is a `Vec3`, and a point moves by adding one.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Point3 {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+impl Point3 {
+    pub fn new(x: f64, y: f64, z: f64) -> Self {
+        Self { x, y, z }
+    }
+
+    pub fn origin() -> Self {
+        Point3::new(0.0, 0.0, 0.0)
+    }
+
+    pub fn random_range(rng: &mut Rng, min: f64, max: f64) -> Point3 {
+        Point3::from(Vec3::random_range(rng, min, max))
+    }
+
+    pub fn distance(a: Point3, b: Point3) -> f64 {
+        (a - b).length()
+    }
+
+    /// Componentwise minimum, the low corner of a box around both points.
+    pub fn min(a: Point3, b: Point3) -> Point3 {
+        Point3::new(a.x.min(b.x), a.y.min(b.y), a.z.min(b.z))
+    }
+
+    pub fn max(a: Point3, b: Point3) -> Point3 {
+        Point3::new(a.x.max(b.x), a.y.max(b.y), a.z.max(b.z))
+    }
+
+    /// Affine combination `(1 - t) a + t b`.
+    pub fn lerp(a: Point3, b: Point3, t: f64) -> Point3 {
+        a + t * (b - a)
+    }
+}
+
+impl From<Vec3> for Point3 {
+    fn from(v: Vec3) -> Self {
+        Point3::new(v.x, v.y, v.z)
+    }
+}
+
+/// The offset of the point from the origin.
+impl From<Point3> for Vec3 {
+    fn from(p: Point3) -> Self {
+        Vec3::new(p.x, p.y, p.z)
+    }
+}
+
+impl Add<Vec3> for Point3 {
+    type Output = Point3;
+
+    fn add(self, rhs: Vec3) -> Self::Output {
+        Point3::new(self.x + rhs.x, self.y + rhs.y, self.z + rhs.z)
+    }
+}
+
+impl Sub<Vec3> for Point3 {
+    type Output = Point3;
+
+    fn sub(self, rhs: Vec3) -> Self::Output {
+        Point3::new(self.x - rhs.x, self.y - rhs.y, self.z - rhs.z)
+    }
+}
+
+impl Sub for Point3 {
+    type Output = Vec3;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        Vec3::new(self.x - rhs.x, self.y - rhs.y, self.z - rhs.z)
+    }
+}
+
+/// Scales the point about the origin, e.g. into a texture's frequency.
+impl Mul<Point3> for f64 {
+    type Output = Point3;
+
+    fn mul(self, rhs: Point3) -> Self::Output {
+        Point3::new(self * rhs.x, self * rhs.y, self * rhs.z)
+    }
+}
+
+impl AddAssign<Vec3> for Point3 {
+    fn add_assign(&mut self, rhs: Vec3) {
+        *self = *self + rhs;
+    }
+}
+
+impl Index<usize> for Point3 {
+    type Output = f64;
+
+    fn index(&self, axis: usize) -> &Self::Output {
+        match axis {
+            0 => &self.x,
+            1 => &self.y,
+            2 => &self.z,
+            _ => panic!("Point3 axis out of range: {axis}"),
+        }
+    }
+}
+
+/// A surface normal. It stays perpendicular to the surface under a
+/// transform by following the inverse transpose, see [`Normal3::transform`].
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Normal3 {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+impl Normal3 {
+    pub fn new(x: f64, y: f64, z: f64) -> Self {
+        Self { x, y, z }
+    }
+
+    /// Maps the normal through the transform whose inverse is `inverse`,
+    /// which object transforms keep for their rays anyway. The result is not
+    /// normalized.
+    pub fn transform(self, inverse: &Mat4) -> Normal3 {
+        Normal3::from(inverse.linear().transpose() * Vec3::from(self))
+    }
+
+    pub fn unit_vector(self) -> Normal3 {
+        Normal3::from(Vec3::from(self).unit_vector())
+    }
+}
+
+impl From<Vec3> for Normal3 {
+    fn from(v: Vec3) -> Self {
+        Normal3::new(v.x, v.y, v.z)
+    }
+}
+
+impl From<Normal3> for Vec3 {
+    fn from(n: Normal3) -> Self {
+        Vec3::new(n.x, n.y, n.z)
+    }
+}
+
+impl Neg for Normal3 {
+    type Output = Normal3;
+
+    fn neg(self) -> Self::Output {
+        Normal3::new(-self.x, -self.y, -self.z)
+    }
+}
+
+/// Row-major 3x3 matrix applied to column vectors.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Mat3 {
+    pub m: [[f64; 3]; 3],
+}
+
+impl Mat3 {
+    pub const IDENTITY: Mat3 = Mat3 {
+        m: [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]],
+    };
+
+    pub fn new(m: [[f64; 3]; 3]) -> Self {
+        Self { m }
+    }
+
+    /// The matrix with `a`, `b` and `c` as its columns.
+    pub fn from_columns(a: Vec3, b: Vec3, c: Vec3) -> Self {
+        Self::new([[a.x, b.x, c.x], [a.y, b.y, c.y], [a.z, b.z, c.z]])
+    }
+
+    pub fn scaling(factors: Vec3) -> Self {
+        Self::new([
+            [factors.x, 0.0, 0.0],
+            [0.0, factors.y, 0.0],
+            [0.0, 0.0, factors.z],
+        ])
+    }
+
+    /// Right-handed rotation by `degrees` about `axis`, which need not be
+    /// normalized.
+    pub fn rotation(axis: Vec3, degrees: f64) -> Self {
+        let Vec3 { x, y, z } = axis.unit_vector();
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        let k = 1.0 - cos;
+        Self::new([
+            [cos + x * x * k, x * y * k - z * sin, x * z * k + y * sin],
+            [y * x * k + z * sin, cos + y * y * k, y * z * k - x * sin],
+            [z * x * k - y * sin, z * y * k + x * sin, cos + z * z * k],
+        ])
+    }
+
+    pub fn transpose(&self) -> Self {
+        let m = &self.m;
+        Self::new([
+            [m[0][0], m[1][0], m[2][0]],
+            [m[0][1], m[1][1], m[2][1]],
+            [m[0][2], m[1][2], m[2][2]],
+        ])
+    }
+
+    pub fn determinant(&self) -> f64 {
+        let [a, b, c] = self.rows();
+        Vec3::dot(a, Vec3::cross(b, c))
+    }
+
+    /// Adjugate over determinant; `None` if singular.
+    pub fn inverse(&self) -> Option<Self> {
+        let det = self.determinant();
+        let [a, b, c] = self.rows();
+        // The determinant is at most the product of the row lengths, so
+        // comparing against that product leaves scaling out of it.
+        if det.abs() <= 1e-12 * a.length() * b.length() * c.length() {
+            return None;
+        }
+        // Cross products of pairs of rows are the columns of the adjugate.
+        let adjugate = Self::from_columns(Vec3::cross(b, c), Vec3::cross(c, a), Vec3::cross(a, b));
+        Some(adjugate * (1.0 / det))
+    }
+
+    fn rows(&self) -> [Vec3; 3] {
+        self.m.map(|[x, y, z]| Vec3::new(x, y, z))
+    }
+}
+
+impl Mul for Mat3 {
+    type Output = Mat3;
+
+    /// `a * b` applies `b` first, then `a`.
+    fn mul(self, rhs: Self) -> Self::Output {
+        let mut product = [[0.0; 3]; 3];
+        for (i, row) in product.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = (0..3).map(|k| self.m[i][k] * rhs.m[k][j]).sum();
+            }
+        }
+        Mat3::new(product)
+    }
+}
+
+impl Mul<Vec3> for Mat3 {
+    type Output = Vec3;
+
+    fn mul(self, v: Vec3) -> Self::Output {
+        let [a, b, c] = self.rows();
+        Vec3::new(Vec3::dot(a, v), Vec3::dot(b, v), Vec3::dot(c, v))
+    }
+}
+
+impl Mul<f64> for Mat3 {
+    type Output = Mat3;
+
+    fn mul(self, rhs: f64) -> Self::Output {
+        Mat3::new(self.m.map(|row| row.map(|value| value * rhs)))
+    }
+}
+
+/// Row-major 4x4 matrix applied to column vectors; used for affine placement
+/// of objects, so the bottom row is expected to stay `0 0 0 1`. Compose with
+/// `*`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Mat4 {
+    pub m: [[f64; 4]; 4],
+}
+
+impl Mat4 {
+    pub const IDENTITY: Mat4 = Mat4 {
+        m: [
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ],
+    };
+
+    pub fn new(m: [[f64; 4]; 4]) -> Self {
+        Self { m }
+    }
+
+    pub fn translation(offset: Vec3) -> Self {
+        let mut t = Self::IDENTITY;
+        t.m[0][3] = offset.x;
+        t.m[1][3] = offset.y;
+        t.m[2][3] = offset.z;
+        t
+    }
+
+    pub fn scaling(factors: Vec3) -> Self {
+        let mut s = Self::IDENTITY;
+        s.m[0][0] = factors.x;
+        s.m[1][1] = factors.y;
+        s.m[2][2] = factors.z;
+        s
+    }
+
+    /// Right-handed rotation by `degrees` about `axis`, which need not be
+    /// normalized.
+    pub fn rotation(axis: Vec3, degrees: f64) -> Self {
+        Self::from(Mat3::rotation(axis, degrees))
+    }
+
+    /// Rotates about x, then y, then z by the angles in `degrees`.
+    pub fn rotation_euler(degrees: Vec3) -> Self {
+        Self::rotation(Vec3::new(0.0, 0.0, 1.0), degrees.z)
+            * Self::rotation(Vec3::new(0.0, 1.0, 0.0), degrees.y)
+            * Self::rotation(Vec3::new(1.0, 0.0, 0.0), degrees.x)
+    }
+
+    pub fn transpose(&self) -> Self {
+        let mut t = Self::IDENTITY;
+        for (i, row) in t.m.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = self.m[j][i];
+            }
+        }
+        t
+    }
+
+    /// Gauss-Jordan elimination with partial pivoting; `None` if singular.
+    pub fn inverse(&self) -> Option<Self> {
+        let mut a = self.m;
+        let mut inv = Self::IDENTITY.m;
+        // Pivots are judged against their column's size, so scaling an axis
+        // by any non-zero amount stays invertible.
+        let sizes = [0, 1, 2, 3].map(|col| (0..4).map(|row| a[row][col].abs()).fold(0.0, f64::max));
+        for col in 0..4 {
+            let pivot = (col..4)
+                .max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))
+                .unwrap();
+            if a[pivot][col].abs() <= 1e-12 * sizes[col] {
+                return None;
+            }
+            a.swap(col, pivot);
+            inv.swap(col, pivot);
+            let scale = 1.0 / a[col][col];
+            for k in 0..4 {
+                a[col][k] *= scale;
+                inv[col][k] *= scale;
+            }
+            for row in 0..4 {
+                let factor = a[row][col];
+                if row == col || factor == 0.0 {
+                    continue;
+                }
+                for k in 0..4 {
+                    a[row][k] -= factor * a[col][k];
+                    inv[row][k] -= factor * inv[col][k];
+                }
+            }
+        }
+        Some(Self::new(inv))
+    }
+
+    /// The upper-left 3x3 block: rotation, scale and shear without the
+    /// translation.
+    pub fn linear(&self) -> Mat3 {
+        Mat3::new([0, 1, 2].map(|i| [self.m[i][0], self.m[i][1], self.m[i][2]]))
+    }
+
+    pub fn transform_point(&self, p: Point3) -> Point3 {
+        let offset = Vec3::new(self.m[0][3], self.m[1][3], self.m[2][3]);
+        Point3::from(self.transform_vector(Vec3::from(p)) + offset)
+    }
+
+    /// Applies the linear part only, ignoring translation.
+    pub fn transform_vector(&self, v: Vec3) -> Vec3 {
+        self.linear() * v
+    }
+}
+
+impl From<Mat3> for Mat4 {
+    fn from(linear: Mat3) -> Self {
+        let mut m = Self::IDENTITY;
+        for (row, values) in m.m.iter_mut().zip(linear.m) {
+            row[..3].copy_from_slice(&values);
+        }
+        m
+    }
+}
+
+impl Mul for Mat4 {
+    type Output = Mat4;
+
+    /// `a * b` applies `b` first, then `a`.
+    fn mul(self, rhs: Self) -> Self::Output {
+        let mut product = [[0.0; 4]; 4];
+        for (i, row) in product.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = (0..4).map(|k| self.m[i][k] * rhs.m[k][j]).sum();
+            }
+        }
+        Mat4::new(product)
+    }
+}
+
+/// Rotation quaternion `w + xi + yj + zk`, kept at unit length.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quat {
+    pub w: f64,
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+impl Quat {
+    pub const IDENTITY: Quat = Quat {
+        w: 1.0,
+        x: 0.0,
+        y: 0.0,
+        z: 0.0,
+    };
+
+    pub fn new(w: f64, x: f64, y: f64, z: f64) -> Self {
+        Self { w, x, y, z }
+    }
+
+    /// Right-handed rotation by `degrees` about `axis`, matching
+    /// [`Mat3::rotation`].
+    pub fn from_axis_angle(axis: Vec3, degrees: f64) -> Self {
+        let (sin, cos) = (0.5 * degrees.to_radians()).sin_cos();
+        let v = sin * axis.unit_vector();
+        Self::new(cos, v.x, v.y, v.z)
+    }
+
+    pub fn dot(a: Quat, b: Quat) -> f64 {
+        a.w * b.w + a.x * b.x + a.y * b.y + a.z * b.z
+    }
+
+    pub fn normalize(self) -> Quat {
+        let len = Quat::dot(self, self).sqrt();
+        Quat::new(self.w / len, self.x / len, self.y / len, self.z / len)
+    }
+
+    /// The inverse rotation.
+    pub fn conjugate(self) -> Quat {
+        Quat::new(self.w, -self.x, -self.y, -self.z)
+    }
+
+    pub fn rotate(self, v: Vec3) -> Vec3 {
+        self.to_mat3() * v
+    }
+
+    pub fn to_mat3(self) -> Mat3 {
+        let Quat { w, x, y, z } = self;
+        Mat3::new([
+            [
+                1.0 - 2.0 * (y * y + z * z),
+                2.0 * (x * y - w * z),
+                2.0 * (x * z + w * y),
+            ],
+            [
+                2.0 * (x * y + w * z),
+                1.0 - 2.0 * (x * x + z * z),
+                2.0 * (y * z - w * x),
+            ],
+            [
+                2.0 * (x * z - w * y),
+                2.0 * (y * z + w * x),
+                1.0 - 2.0 * (x * x + y * y),
+            ],
+        ])
+    }
+
+    /// Constant speed interpolation along the shorter arc from `a` to `b`.
+    pub fn slerp(a: Quat, b: Quat, t: f64) -> Quat {
+        // q and -q are the same rotation; flip one to take the short way.
+        let (b, cos) = match Quat::dot(a, b) {
+            cos if cos < 0.0 => (Quat::new(-b.w, -b.x, -b.y, -b.z), -cos),
+            cos => (b, cos),
+        };
+        let (wa, wb) = if cos > 0.9995 {
+            // Nearly parallel: sin(theta) vanishes, a lerp is close enough.
+            (1.0 - t, t)
+        } else {
+            let theta = cos.acos();
+            let sin = theta.sin();
+            (((1.0 - t) * theta).sin() / sin, (t * theta).sin() / sin)
+        };
+        Quat::new(
+            wa * a.w + wb * b.w,
+            wa * a.x + wb * b.x,
+            wa * a.y + wb * b.y,
+            wa * a.z + wb * b.z,
+        )
+        .normalize()
+    }
+}
+
+impl Mul for Quat {
+    type Output = Quat;
+
+    /// Hamilton product; `a * b` rotates by `b` first, then `a`.
+    fn mul(self, rhs: Self) -> Self::Output {
+        let (a, b) = (self, rhs);
+        Quat::new(
+            a.w * b.w - a.x * b.x - a.y * b.y - a.z * b.z,
+            a.w * b.x + a.x * b.w + a.y * b.z - a.z * b.y,
+            a.w * b.y - a.x * b.z + a.y * b.w + a.z * b.x,
+            a.w * b.z + a.x * b.y - a.y * b.x + a.z * b.w,
+        )
+    }
+}
+
+/// Orthonormal basis with `w` along a given direction, for working in a
+/// frame where that direction is +z.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Onb {
+    pub u: Vec3,
+    pub v: Vec3,
+    pub w: Vec3,
+}
+
+impl Onb {
+    /// Builds the basis around `n`, which need not be normalized.
+    pub fn new(n: Vec3) -> Self {
+        let w = n.unit_vector();
+        // Any helper axis not parallel to w will do.
+        let a = if w.x.abs() > 0.9 {
+            Vec3::new(0.0, 1.0, 0.0)
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let v = Vec3::cross(w, a).unit_vector();
+        let u = Vec3::cross(v, w);
+        Self { u, v, w }
+    }
+
+    /// Maps coordinates in this basis to world space.
+    pub fn to_world(&self, a: Vec3) -> Vec3 {
+        a.x * self.u + a.y * self.v + a.z * self.w
+    }
+
+    /// Coordinates of the world vector `a` in this basis.
+    pub fn to_local(&self, a: Vec3) -> Vec3 {
+        Vec3::new(
+            Vec3::dot(a, self.u),
+            Vec3::dot(a, self.v),
+            Vec3::dot(a, self.w),
+        )
+    }
+}
 
 #[cfg(test)]
 mod tests {
@@ -176,10 +759,172 @@ mod tests {
     }
 
     #[test]
-    fn test_point3_alias() {
-        let p: Point3 = Point3::new(1.0, 2.0, 3.0);
-        assert_eq!(p.x, 1.0);
-        assert_eq!(p.y, 2.0);
-        assert_eq!(p.z, 3.0);
+    fn test_compound_assignment() {
+        let mut v = Vec3::new(1.0, 2.0, 3.0);
+        v += Vec3::new(1.0, 1.0, 1.0);
+        v -= Vec3::new(0.0, 1.0, 0.0);
+        v *= 2.0;
+        v /= 4.0;
+        assert_eq!(v, Vec3::new(1.0, 1.0, 2.0));
+        assert_eq!(v * 2.0, 2.0 * v);
+    }
+
+    #[test]
+    fn test_point3_arithmetic() {
+        let p = Point3::new(1.0, 2.0, 3.0);
+        let q = Point3::new(4.0, 6.0, 3.0);
+        assert_eq!(q - p, Vec3::new(3.0, 4.0, 0.0));
+        assert_eq!(p + (q - p), q);
+        assert_eq!(q - Vec3::new(3.0, 4.0, 0.0), p);
+        assert_eq!(Point3::distance(p, q), 5.0);
+        assert_eq!(Point3::lerp(p, q, 0.5), Point3::new(2.5, 4.0, 3.0));
+        assert_eq!(Point3::min(p, q), p);
+        assert_eq!(p[1], 2.0);
+        assert_eq!(Vec3::from(p), Vec3::new(1.0, 2.0, 3.0));
+    }
+
+    fn assert_near<T: Into<Vec3> + std::fmt::Debug + Copy>(a: T, b: T) {
+        assert!((a.into() - b.into()).length() < 1e-9, "{a:?} != {b:?}");
+    }
+
+    #[test]
+    fn test_mat4_transforms() {
+        let t = Mat4::translation(Vec3::new(1.0, 2.0, 3.0));
+        assert_eq!(
+            t.transform_point(Point3::origin()),
+            Point3::new(1.0, 2.0, 3.0)
+        );
+        assert_eq!(
+            t.transform_vector(Vec3::new(1.0, 0.0, 0.0)),
+            Vec3::new(1.0, 0.0, 0.0)
+        );
+
+        let r = Mat4::rotation(Vec3::new(0.0, 0.0, 2.0), 90.0);
+        assert_near(
+            r.transform_vector(Vec3::new(1.0, 0.0, 0.0)),
+            Vec3::new(0.0, 1.0, 0.0),
+        );
+        assert_near(
+            Mat4::rotation_euler(Vec3::new(0.0, 90.0, 0.0))
+                .transform_vector(Vec3::new(0.0, 0.0, 1.0)),
+            Vec3::new(1.0, 0.0, 0.0),
+        );
+
+        // Scale first, then translate.
+        let m = t * Mat4::scaling(Vec3::new(2.0, 2.0, 2.0));
+        assert_eq!(
+            m.transform_point(Point3::new(1.0, 1.0, 1.0)),
+            Point3::new(3.0, 4.0, 5.0)
+        );
+        assert_eq!(m.linear(), Mat3::scaling(Vec3::new(2.0, 2.0, 2.0)));
+    }
+
+    #[test]
+    fn test_mat4_inverse() {
+        let m = Mat4::translation(Vec3::new(5.0, -1.0, 2.0))
+            * Mat4::rotation(Vec3::new(1.0, 1.0, 0.0), 33.0)
+            * Mat4::scaling(Vec3::new(2.0, 0.5, 3.0));
+        let inv = m.inverse().unwrap();
+        let p = Point3::new(0.3, -2.0, 7.0);
+        assert_near(inv.transform_point(m.transform_point(p)), p);
+        assert_near((m * inv).transform_point(p), p);
+        assert_eq!(m.transpose().transpose(), m);
+        assert_eq!(Mat4::scaling(Vec3::new(1.0, 0.0, 1.0)).inverse(), None);
+        let flat = Mat4::new([
+            [1.0, 2.0, 0.0, 0.0],
+            [2.0, 4.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ]);
+        assert_eq!(flat.inverse(), None);
+        let thin = Mat4::scaling(Vec3::new(1e-13, 1.0, 1.0)).inverse().unwrap();
+        assert_eq!(
+            thin.transform_vector(Vec3::new(1e-13, 1.0, 0.0)),
+            Vec3::new(1.0, 1.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn test_mat3() {
+        let m = Mat3::new([[2.0, 0.0, 1.0], [1.0, 3.0, 0.0], [0.0, 1.0, 4.0]]);
+        assert_eq!(m.determinant(), 25.0);
+        let product = m * m.inverse().unwrap();
+        for (i, row) in product.m.iter().enumerate() {
+            for (j, value) in row.iter().enumerate() {
+                let expected = if i == j { 1.0 } else { 0.0 };
+                assert!((value - expected).abs() < 1e-12);
+            }
+        }
+        assert_eq!(m.transpose().m[0], [2.0, 1.0, 0.0]);
+        assert_eq!(m * Vec3::new(1.0, 0.0, 0.0), Vec3::new(2.0, 1.0, 0.0));
+        assert_eq!(Mat3::scaling(Vec3::new(1.0, 0.0, 1.0)).inverse(), None);
+        // Tiny but invertible scales invert.
+        let tiny = Mat3::scaling(Vec3::new(1e-5, 1e-5, 1e-5))
+            .inverse()
+            .unwrap();
+        assert!((tiny * Vec3::new(1e-5, 0.0, 0.0) - Vec3::new(1.0, 0.0, 0.0)).length() < 1e-9);
+    }
+
+    #[test]
+    fn test_normal_transform() {
+        // Under non-uniform scaling a tangent and the normal only stay
+        // perpendicular if the normal follows the inverse transpose.
+        let m = Mat4::scaling(Vec3::new(3.0, 1.0, 1.0))
+            * Mat4::rotation(Vec3::new(0.0, 0.0, 1.0), 20.0);
+        let tangent = m.transform_vector(Vec3::new(1.0, 1.0, 0.0));
+        let normal = Normal3::new(1.0, -1.0, 0.0);
+        let n = normal.transform(&m.inverse().unwrap());
+        assert!(Vec3::dot(tangent, Vec3::from(n)).abs() < 1e-12);
+        let as_vector = m.transform_vector(Vec3::from(normal));
+        assert!(Vec3::dot(tangent, as_vector).abs() > 0.1);
+    }
+
+    #[test]
+    fn test_quat() {
+        let axis = Vec3::new(1.0, 2.0, -1.0);
+        let q = Quat::from_axis_angle(axis, 70.0);
+        let v = Vec3::new(0.5, -1.0, 2.0);
+        assert_near(q.rotate(v), Mat3::rotation(axis, 70.0) * v);
+        assert_near((q * q.conjugate()).rotate(v), v);
+
+        let z = Vec3::new(0.0, 0.0, 1.0);
+        let composed = Quat::from_axis_angle(z, 30.0) * Quat::from_axis_angle(z, 60.0);
+        assert_near(
+            composed.rotate(Vec3::new(1.0, 0.0, 0.0)),
+            Vec3::new(0.0, 1.0, 0.0),
+        );
+    }
+
+    #[test]
+    fn test_slerp() {
+        let a = Quat::IDENTITY;
+        let b = Quat::from_axis_angle(Vec3::new(0.0, 0.0, 1.0), 90.0);
+        let x = Vec3::new(1.0, 0.0, 0.0);
+        let s = 0.5f64.sqrt();
+        assert_near(Quat::slerp(a, b, 0.0).rotate(x), x);
+        assert_near(Quat::slerp(a, b, 1.0).rotate(x), Vec3::new(0.0, 1.0, 0.0));
+        assert_near(Quat::slerp(a, b, 0.5).rotate(x), Vec3::new(s, s, 0.0));
+        // -b is the same rotation; slerp still takes the short arc.
+        let neg_b = Quat::new(-b.w, -b.x, -b.y, -b.z);
+        assert_near(Quat::slerp(a, neg_b, 0.5).rotate(x), Vec3::new(s, s, 0.0));
+    }
+
+    #[test]
+    fn test_onb() {
+        for n in [
+            Vec3::new(0.0, 0.0, 1.0),
+            Vec3::new(-2.0, 0.0, 0.0),
+            Vec3::new(-0.3, 0.8, 2.0),
+        ] {
+            let onb = Onb::new(n);
+            assert_near(onb.w, n.unit_vector());
+            assert!(Vec3::dot(onb.u, onb.v).abs() < 1e-12);
+            assert!(Vec3::dot(onb.u, onb.w).abs() < 1e-12);
+            assert!((onb.u.length() - 1.0).abs() < 1e-12);
+            // Right-handed, so local +z maps to w.
+            assert_near(Vec3::cross(onb.u, onb.v), onb.w);
+            let a = Vec3::new(0.2, -0.5, 0.7);
+            assert_near(onb.to_local(onb.to_world(a)), a);
+        }
     }
 }